@@ -0,0 +1,40 @@
+#import bevy_pbr::{
+    forward_io::VertexOutput,
+    mesh_view_bindings::{fog, view},
+    mesh_view_types::FOG_MODE_OFF,
+    pbr_functions::apply_fog,
+}
+
+@group(2) @binding(0) var block_textures: texture_2d_array<f32>;
+@group(2) @binding(1) var block_sampler: sampler;
+@group(2) @binding(2) var<uniform> alpha_cutoff: f32;
+@group(2) @binding(3) var<uniform> daylight: f32;
+@group(2) @binding(4) var<uniform> tint: vec4<f32>;
+
+const MIN_BRIGHTNESS: f32 = 0.05;
+
+// Maps a 0..1 light level to brightness, dropping off faster near darkness.
+fn light_curve(level: f32) -> f32 {
+    return level / (4.0 - 3.0 * level);
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The texture array layer is carried in the second UV channel.
+    let layer = i32(round(in.uv_b.x));
+    var color = textureSample(block_textures, block_sampler, in.uv, layer);
+    if color.a < alpha_cutoff {
+        discard;
+    }
+#ifdef VERTEX_COLORS
+    // Vertex colors carry sky light, block light and ambient occlusion.
+    let light = max(light_curve(in.color.r) * daylight, light_curve(in.color.g));
+    let brightness = max(light, MIN_BRIGHTNESS) * in.color.b;
+    color = vec4<f32>(color.rgb * brightness, color.a);
+#endif
+    color = color * tint;
+    if fog.mode != FOG_MODE_OFF {
+        color = apply_fog(fog, color, in.world_position.xyz, view.world_position.xyz);
+    }
+    return color;
+}
@@ -0,0 +1,174 @@
+use anyhow::anyhow;
+use bevy::prelude::*;
+use nipahblocks::chunk::{world_to_block, BlockChange, ChunkMap};
+use nipahblocks::command::{help, Command, GameMode, TimeAction, AIR};
+use nipahblocks::history::EditHistory;
+use nipahblocks::net::{FromClient, Target, ToClients};
+use nipahblocks::persistence::WorldSave;
+use nipahblocks::protocol::{ClientMessage, ServerMessage, MAX_BLOCKS_CHANGED};
+use nipahblocks::world_edit::{EditCommand, EditSession};
+use nipahblocks::world_time::WorldTime;
+
+use crate::world::{ConnectedPlayer, ServerResources};
+
+/// Relays chat and runs commands players send from their console.
+pub struct ServerCommandsPlugin;
+
+impl Plugin for ServerCommandsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, handle_chat.run_if(resource_exists::<WorldSave>));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_chat(
+    mut from_client: EventReader<FromClient>,
+    mut to_clients: EventWriter<ToClients>,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut world_time: ResMut<WorldTime>,
+    world_save: Res<WorldSave>,
+    resources: Res<ServerResources>,
+    mut players_q: Query<(
+        &ConnectedPlayer,
+        &mut Transform,
+        &mut GameMode,
+        &mut EditSession,
+        &mut EditHistory,
+    )>,
+) {
+    for FromClient { client, message } in from_client.read() {
+        let ClientMessage::Chat { text } = message else {
+            continue;
+        };
+        let Some((player, mut transform, mut game_mode, mut session, mut history)) = players_q
+            .iter_mut()
+            .find(|(player, ..)| player.client == *client)
+        else {
+            continue;
+        };
+        let Some(line) = text.strip_prefix('/') else {
+            let text = format!("<{}> {text}", player.name);
+            info!("{text}");
+            to_clients.send(ToClients {
+                target: Target::All,
+                message: ServerMessage::Chat { text },
+            });
+            continue;
+        };
+        info!("{} ran /{line}", player.name);
+        let reply = |message| ToClients {
+            target: Target::Client(*client),
+            message,
+        };
+        let output = Command::parse(line).and_then(|command| {
+            Ok(match command {
+                Command::Help => help(),
+                Command::Teleport(pos) => {
+                    transform.translation = pos.resolve(transform.translation);
+                    to_clients.send(reply(ServerMessage::Teleport {
+                        translation: transform.translation,
+                    }));
+                    format!("Teleported to {:.2}", transform.translation)
+                }
+                Command::Seed => format!("Seed: {}", world_save.seed()),
+                Command::Time(action) => {
+                    match action {
+                        TimeAction::Query => return Ok(format!("Time is {}", world_time.clock())),
+                        TimeAction::Set(hours) => {
+                            world_time.time_of_day = WorldTime::new(hours).time_of_day;
+                        }
+                        TimeAction::Add(hours) => {
+                            world_time.time_of_day =
+                                WorldTime::new(world_time.time_of_day + hours).time_of_day;
+                        }
+                        TimeAction::Pause(paused) => world_time.paused = paused,
+                    }
+                    to_clients.send(ToClients {
+                        target: Target::All,
+                        message: ServerMessage::TimeOfDay {
+                            time_of_day: world_time.time_of_day,
+                            paused: world_time.paused,
+                        },
+                    });
+                    format!(
+                        "Set the time to {}{}",
+                        world_time.clock(),
+                        if world_time.paused { " (paused)" } else { "" }
+                    )
+                }
+                Command::SetBlock { pos, block } => {
+                    let id = match block.as_str() {
+                        AIR => None,
+                        name => Some(
+                            *resources
+                                .blocks_map
+                                .get(name)
+                                .ok_or_else(|| anyhow!("Unknown block \"{name}\""))?,
+                        ),
+                    };
+                    let base = world_to_block(transform.translation).as_vec3();
+                    let pos = pos.resolve(base).floor().as_ivec3();
+                    if !chunk_map.contains_chunk(ChunkMap::chunk_pos(pos)) {
+                        return Err(anyhow!("{pos} isn't loaded"));
+                    }
+                    let changes = chunk_map.set_blocks([(pos, id)]);
+                    broadcast_changes(&mut to_clients, &changes);
+                    history.record(changes);
+                    format!("Set {pos} to {block}")
+                }
+                Command::GameMode(mode) => {
+                    *game_mode = mode;
+                    to_clients.send(reply(ServerMessage::SetGameMode { mode }));
+                    format!("Game mode set to {}", mode.name())
+                }
+                Command::Edit(command) => {
+                    let selecting = matches!(command, EditCommand::SetCorner(..));
+                    let outcome = session.execute(
+                        command,
+                        &mut chunk_map,
+                        &resources.blocks_map,
+                        &transform,
+                    )?;
+                    if selecting {
+                        to_clients.send(reply(ServerMessage::Selection {
+                            corners: session.corners,
+                        }));
+                    }
+                    broadcast_changes(&mut to_clients, &outcome.changes);
+                    history.record(outcome.changes);
+                    outcome.message
+                }
+                Command::Undo => match history.undo(&mut chunk_map) {
+                    Some(reverted) => {
+                        broadcast_changes(&mut to_clients, &reverted.changes);
+                        reverted.describe("Undid")
+                    }
+                    None => "Nothing to undo".to_string(),
+                },
+                Command::Redo => match history.redo(&mut chunk_map) {
+                    Some(reverted) => {
+                        broadcast_changes(&mut to_clients, &reverted.changes);
+                        reverted.describe("Redid")
+                    }
+                    None => "Nothing to redo".to_string(),
+                },
+            })
+        });
+        let text = output.unwrap_or_else(|e| e.to_string());
+        to_clients.send(reply(ServerMessage::Chat { text }));
+    }
+}
+
+fn broadcast_changes(to_clients: &mut EventWriter<ToClients>, changes: &[BlockChange]) {
+    for changes in changes.chunks(MAX_BLOCKS_CHANGED) {
+        to_clients.send(ToClients {
+            target: Target::All,
+            message: ServerMessage::BlocksChanged {
+                changes: changes
+                    .iter()
+                    .map(|change| (change.pos, change.new.map(|id| id as u16)))
+                    .collect(),
+            },
+        });
+    }
+}
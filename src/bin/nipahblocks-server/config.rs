@@ -0,0 +1,61 @@
+use bevy::prelude::*;
+use nipahblocks::world_edit::SCHEMATICS_DIR;
+use serde::Deserialize;
+use std::{
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+const DEFAULT_PORT: u16 = 24860;
+
+/// Server settings read from the config file, overridable from the command
+/// line. Missing fields keep their defaults.
+#[derive(Debug, Clone, Resource, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Address the TCP listener and UDP socket bind to.
+    pub bind: SocketAddr,
+    pub world: String,
+    /// Seed used when creating a new world.
+    pub seed: Option<u32>,
+    pub saves_dir: PathBuf,
+    pub block_registry: PathBuf,
+    /// Where players' `/schematic` commands save and load schematics.
+    pub schematics_dir: PathBuf,
+    /// Chunks kept loaded around each player, in chunks.
+    pub view_distance: u32,
+    /// Simulation ticks per second.
+    pub tick_rate: f64,
+    pub autosave_interval_secs: f32,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([0, 0, 0, 0], DEFAULT_PORT)),
+            world: "world".to_string(),
+            seed: None,
+            saves_dir: PathBuf::from("saves"),
+            block_registry: PathBuf::from("assets/block_registry.json"),
+            schematics_dir: PathBuf::from(SCHEMATICS_DIR),
+            view_distance: 3,
+            tick_rate: 20.0,
+            autosave_interval_secs: 30.0,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Reads the config at `path`, falling back to defaults if it doesn't exist.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        match path.exists() {
+            true => Ok(serde_json::from_str(&fs::read_to_string(path)?)?),
+            false => Ok(Self::default()),
+        }
+    }
+
+    pub fn world_dir(&self) -> PathBuf {
+        self.saves_dir.join(&self.world)
+    }
+}
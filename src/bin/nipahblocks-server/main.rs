@@ -0,0 +1,93 @@
+use bevy::{
+    app::{ScheduleRunnerPlugin, TerminalCtrlCHandlerPlugin},
+    log::LogPlugin,
+    prelude::*,
+};
+use clap::{value_parser, Arg, Command};
+use nipahblocks::net::{NetServer, NetServerPlugin};
+use std::{net::SocketAddr, path::Path, time::Duration};
+
+mod commands;
+mod config;
+mod network;
+mod world;
+
+use commands::ServerCommandsPlugin;
+use config::ServerConfig;
+use network::ServerNetworkPlugin;
+use world::ServerWorldPlugin;
+
+fn main() -> anyhow::Result<()> {
+    let matches = Command::new("nipahblocks-server")
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .default_value("server.json")
+                .help("Server config file, defaults are used if it doesn't exist"),
+        )
+        .arg(
+            Arg::new("bind")
+                .long("bind")
+                .value_parser(value_parser!(SocketAddr))
+                .help("Address to listen on"),
+        )
+        .arg(
+            Arg::new("world")
+                .long("world")
+                .help("Name of the world to host"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_parser(value_parser!(u32))
+                .help("Seed used when creating a new world"),
+        )
+        .arg(
+            Arg::new("view-distance")
+                .long("view-distance")
+                .value_parser(value_parser!(u32))
+                .help("Chunks kept loaded around each player"),
+        )
+        .arg(
+            Arg::new("tick-rate")
+                .long("tick-rate")
+                .value_parser(value_parser!(f64))
+                .help("Simulation ticks per second"),
+        )
+        .get_matches();
+    let mut config = ServerConfig::load(Path::new(matches.get_one::<String>("config").unwrap()))?;
+    if let Some(&bind) = matches.get_one::<SocketAddr>("bind") {
+        config.bind = bind;
+    }
+    if let Some(world) = matches.get_one::<String>("world") {
+        config.world = world.clone();
+    }
+    if let Some(&seed) = matches.get_one::<u32>("seed") {
+        config.seed = Some(seed);
+    }
+    if let Some(&view_distance) = matches.get_one::<u32>("view-distance") {
+        config.view_distance = view_distance;
+    }
+    if let Some(&tick_rate) = matches.get_one::<f64>("tick-rate") {
+        config.tick_rate = tick_rate;
+    }
+
+    let server = NetServer::bind(config.bind)?;
+
+    App::new()
+        .add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+                1.0 / config.tick_rate.max(1.0),
+            ))),
+            LogPlugin::default(),
+            TerminalCtrlCHandlerPlugin,
+            NetServerPlugin,
+            ServerWorldPlugin,
+            ServerNetworkPlugin,
+            ServerCommandsPlugin,
+        ))
+        .insert_resource(config)
+        .insert_resource(server)
+        .run();
+    Ok(())
+}
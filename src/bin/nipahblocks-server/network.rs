@@ -0,0 +1,218 @@
+use bevy::{prelude::*, utils::hashbrown::HashSet};
+use nipahblocks::chunk::{BlockChange, ChunkMap};
+use nipahblocks::command::GameMode;
+use nipahblocks::history::EditHistory;
+use nipahblocks::net::{ClientId, FromClient, ServerEvent, Target, ToClients};
+use nipahblocks::persistence::WorldSave;
+use nipahblocks::protocol::{encode_chunk, ClientMessage, ServerMessage};
+use nipahblocks::world_edit::EditSession;
+use nipahblocks::world_time::WorldTime;
+
+use crate::config::ServerConfig;
+use crate::world::{chunks_around, ConnectedPlayer, ServerResources};
+
+/// Chunks streamed to each player per tick.
+const CHUNKS_PER_TICK: usize = 4;
+
+pub struct ServerNetworkPlugin;
+
+impl Plugin for ServerNetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (handle_server_events, handle_client_messages, stream_chunks)
+                .chain()
+                .run_if(resource_exists::<WorldSave>),
+        );
+    }
+}
+
+/// Chunks already sent to a player.
+#[derive(Debug, Default, Component, Deref, DerefMut)]
+struct SentChunks(HashSet<IVec3>);
+
+#[allow(clippy::too_many_arguments)]
+fn handle_server_events(
+    mut commands: Commands,
+    config: Res<ServerConfig>,
+    mut server_events: EventReader<ServerEvent>,
+    mut to_clients: EventWriter<ToClients>,
+    world_save: Res<WorldSave>,
+    world_time: Res<WorldTime>,
+    resources: Res<ServerResources>,
+    players_q: Query<(Entity, &ConnectedPlayer)>,
+) {
+    for event in server_events.read() {
+        match event {
+            ServerEvent::Connected { client, name } => {
+                to_clients.send(ToClients {
+                    target: Target::Client(*client),
+                    message: ServerMessage::WorldInfo {
+                        seed: world_save.seed(),
+                        time_of_day: world_time.time_of_day,
+                        block_names: resources.block_names.to_vec(),
+                    },
+                });
+                for (_, player) in &players_q {
+                    to_clients.send(ToClients {
+                        target: Target::Client(*client),
+                        message: ServerMessage::PlayerJoined {
+                            client_id: player.client.0,
+                            name: player.name.clone(),
+                        },
+                    });
+                }
+                to_clients.send(ToClients {
+                    target: Target::AllExcept(*client),
+                    message: ServerMessage::PlayerJoined {
+                        client_id: client.0,
+                        name: name.clone(),
+                    },
+                });
+                commands.spawn((
+                    ConnectedPlayer {
+                        client: *client,
+                        name: name.clone(),
+                    },
+                    SentChunks::default(),
+                    Transform::default(),
+                    GameMode::default(),
+                    EditSession::new(config.schematics_dir.clone()),
+                    EditHistory::default(),
+                ));
+            }
+            ServerEvent::Disconnected { client } => {
+                for (entity, player) in &players_q {
+                    if player.client == *client {
+                        info!("{} left the game", player.name);
+                        commands.entity(entity).despawn();
+                    }
+                }
+                to_clients.send(ToClients {
+                    target: Target::All,
+                    message: ServerMessage::PlayerLeft {
+                        client_id: client.0,
+                    },
+                });
+            }
+        }
+    }
+}
+
+/// Applies a block change requested by `client`, returning whether it was valid.
+fn set_block(
+    chunk_map: &mut ChunkMap,
+    resources: &ServerResources,
+    client: ClientId,
+    pos: IVec3,
+    block: Option<u16>,
+) -> bool {
+    let block = block.map(usize::from);
+    if block.is_some_and(|id| id >= resources.blocks.len()) {
+        warn!("Client {} placed unknown block {block:?}", client.0);
+        return false;
+    }
+    chunk_map.set_block(pos, block)
+}
+
+fn handle_client_messages(
+    mut from_client: EventReader<FromClient>,
+    mut to_clients: EventWriter<ToClients>,
+    mut chunk_map: ResMut<ChunkMap>,
+    resources: Res<ServerResources>,
+    mut players_q: Query<(
+        &ConnectedPlayer,
+        &mut Transform,
+        &GameMode,
+        &mut EditHistory,
+    )>,
+) {
+    for FromClient { client, message } in from_client.read() {
+        match *message {
+            ClientMessage::PlayerTransform {
+                tick,
+                translation,
+                rotation,
+            } => {
+                if let Some((_, mut transform, ..)) = players_q
+                    .iter_mut()
+                    .find(|(player, ..)| player.client == *client)
+                {
+                    *transform = Transform::from_translation(translation).with_rotation(rotation);
+                }
+                to_clients.send(ToClients {
+                    target: Target::AllExcept(*client),
+                    message: ServerMessage::PlayerTransform {
+                        client_id: client.0,
+                        tick,
+                        translation,
+                        rotation,
+                    },
+                });
+            }
+            ClientMessage::SetBlock { pos, block } => {
+                let Some((_, _, mode, mut history)) = players_q
+                    .iter_mut()
+                    .find(|(player, ..)| player.client == *client)
+                else {
+                    continue;
+                };
+                let old = chunk_map.block_at(pos);
+                let valid = *mode == GameMode::Creative
+                    && set_block(&mut chunk_map, &resources, *client, pos, block);
+                let (target, block) = match valid {
+                    true => {
+                        history.record(vec![BlockChange {
+                            pos,
+                            old,
+                            new: block.map(usize::from),
+                        }]);
+                        (Target::AllExcept(*client), block)
+                    }
+                    // Undo the client's local change.
+                    false => (
+                        Target::Client(*client),
+                        chunk_map.block_at(pos).map(|id| id as u16),
+                    ),
+                };
+                to_clients.send(ToClients {
+                    target,
+                    message: ServerMessage::BlockChanged { pos, block },
+                });
+            }
+            // Handled by the command systems.
+            ClientMessage::Chat { .. } => {}
+            ClientMessage::Hello { .. } | ClientMessage::Disconnect => {}
+        }
+    }
+}
+
+fn stream_chunks(
+    chunk_map: Res<ChunkMap>,
+    config: Res<ServerConfig>,
+    mut to_clients: EventWriter<ToClients>,
+    mut players_q: Query<(&ConnectedPlayer, &Transform, &mut SentChunks)>,
+) -> Result {
+    for (player, transform, mut sent) in &mut players_q {
+        let mut missing = chunks_around(transform.translation, config.view_distance)
+            .filter(|(chunk_pos, _)| !sent.contains(chunk_pos))
+            .filter_map(|(chunk_pos, distance)| {
+                chunk_map
+                    .chunk(chunk_pos)
+                    .map(|chunk| (chunk_pos, chunk, distance))
+            })
+            .collect::<Vec<_>>();
+        missing.sort_by_key(|&(_, _, distance)| distance);
+        for (chunk_pos, chunk, _) in missing.into_iter().take(CHUNKS_PER_TICK) {
+            to_clients.send(ToClients {
+                target: Target::Client(player.client),
+                message: ServerMessage::Chunk {
+                    chunk_pos,
+                    data: encode_chunk(chunk)?,
+                },
+            });
+            sent.insert(chunk_pos);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,229 @@
+use anyhow::anyhow;
+use bevy::{
+    prelude::*,
+    utils::hashbrown::{HashMap, HashSet},
+};
+use nipahblocks::block::Block;
+use nipahblocks::block_registry::BlockInfoRegistry;
+use nipahblocks::chunk::{world_to_block, Chunk, ChunkMap, CHUNK_SIZE};
+use nipahblocks::generator::generate_chunk;
+use nipahblocks::net::ClientId;
+use nipahblocks::persistence::{WorldSave, UNKNOWN_BLOCK};
+use nipahblocks::protocol::MAX_BLOCK_IDS;
+use nipahblocks::world_time::WorldTime;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::config::ServerConfig;
+
+/// Time per tick spent loading or generating chunks.
+const CHUNK_LOAD_BUDGET: Duration = Duration::from_millis(10);
+/// Extra chunks a player has to move away before chunks are unloaded, so
+/// walking along a chunk border doesn't reload the same chunks every tick.
+const UNLOAD_MARGIN: u32 = 1;
+
+pub struct ServerWorldPlugin;
+
+impl Plugin for ServerWorldPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkMap>()
+            .add_systems(Startup, open_world)
+            .add_systems(
+                Update,
+                (advance_time, load_chunks, unload_chunks, autosave)
+                    .chain()
+                    .run_if(resource_exists::<WorldSave>),
+            )
+            .add_systems(
+                Last,
+                save_on_exit.run_if(resource_exists::<WorldSave>.and(on_event::<AppExit>)),
+            );
+    }
+}
+
+/// Block registry without any rendering data.
+#[derive(Debug, Resource)]
+pub struct ServerResources {
+    pub blocks_map: Arc<HashMap<String, usize>>,
+    pub block_names: Arc<Vec<String>>,
+    pub blocks: Arc<Vec<Block>>,
+}
+
+/// A player connected to the server. Chunks are kept loaded around its
+/// `Transform`.
+#[derive(Debug, Component)]
+pub struct ConnectedPlayer {
+    pub client: ClientId,
+    pub name: String,
+}
+
+#[derive(Debug, Resource, Deref, DerefMut)]
+struct AutosaveTimer(Timer);
+
+fn open_world(
+    mut commands: Commands,
+    config: Res<ServerConfig>,
+    mut chunk_map: ResMut<ChunkMap>,
+) -> Result {
+    info!("Starting server with {:?}", *config);
+    let registry = BlockInfoRegistry::load(&config.block_registry)?;
+    // The server never renders, so every face uses texture layer 0.
+    let blocks = Arc::new(registry.build_blocks(|_| Ok(0))?);
+    let block_names = Arc::new(registry.block_names());
+    if block_names.len() > MAX_BLOCK_IDS {
+        return Err(anyhow!(
+            "Block registry has {} blocks, the protocol supports {MAX_BLOCK_IDS}",
+            block_names.len()
+        )
+        .into());
+    }
+    let blocks_map = block_names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (name.clone(), i))
+        .collect();
+    let world_save = WorldSave::open(&config.world_dir(), config.seed, &block_names)?;
+    info!(
+        "Hosting world {} with seed {}",
+        config.world_dir().display(),
+        world_save.seed()
+    );
+    chunk_map.set_registry(block_names.clone(), blocks.clone(), UNKNOWN_BLOCK);
+    commands.insert_resource(WorldTime::new(world_save.time_of_day()));
+    commands.insert_resource(AutosaveTimer(Timer::from_seconds(
+        config.autosave_interval_secs,
+        TimerMode::Repeating,
+    )));
+    commands.insert_resource(ServerResources {
+        blocks_map: Arc::new(blocks_map),
+        block_names,
+        blocks,
+    });
+    commands.insert_resource(world_save);
+    Ok(())
+}
+
+/// Chunks within `radius` chunks of `pos` on its chunk layer, with their
+/// squared distance in chunks.
+pub fn chunks_around(pos: Vec3, radius: u32) -> impl Iterator<Item = (IVec3, i32)> {
+    let center = ChunkMap::chunk_pos(world_to_block(pos));
+    let radius = radius as i32;
+    (-radius..=radius)
+        .flat_map(move |x| (-radius..=radius).map(move |z| (x, z)))
+        .map(move |(x, z)| {
+            (
+                center + IVec3::new(x, 0, z) * CHUNK_SIZE as i32,
+                x * x + z * z,
+            )
+        })
+}
+
+fn advance_time(time: Res<Time>, mut world_time: ResMut<WorldTime>) {
+    world_time.advance(time.delta_secs());
+}
+
+fn load_chunk(world_save: &WorldSave, resources: &ServerResources, chunk_pos: IVec3) -> Chunk {
+    let loaded = world_save
+        .load_chunk(chunk_pos, resources.blocks.clone())
+        .unwrap_or_else(|e| {
+            error!("Failed to load chunk {chunk_pos}, regenerating: {e}");
+            None
+        });
+    loaded.unwrap_or_else(|| {
+        generate_chunk(
+            chunk_pos.as_vec3(),
+            world_save.seed(),
+            resources.blocks_map.clone(),
+            resources.blocks.clone(),
+        )
+    })
+}
+
+fn load_chunks(
+    mut chunk_map: ResMut<ChunkMap>,
+    config: Res<ServerConfig>,
+    world_save: Res<WorldSave>,
+    resources: Res<ServerResources>,
+    players_q: Query<&Transform, With<ConnectedPlayer>>,
+) {
+    let mut missing = HashMap::<IVec3, i32>::new();
+    for transform in &players_q {
+        for (chunk_pos, distance) in chunks_around(transform.translation, config.view_distance) {
+            if chunk_map.contains_chunk(chunk_pos) {
+                continue;
+            }
+            let nearest = missing.entry(chunk_pos).or_insert(distance);
+            *nearest = (*nearest).min(distance);
+        }
+    }
+    let mut missing = missing.into_iter().collect::<Vec<_>>();
+    missing.sort_by_key(|&(_, distance)| distance);
+    let start = Instant::now();
+    for (chunk_pos, _) in missing {
+        if start.elapsed() > CHUNK_LOAD_BUDGET {
+            break;
+        }
+        let chunk = load_chunk(&world_save, &resources, chunk_pos);
+        chunk_map.insert_chunk(chunk_pos, chunk);
+    }
+}
+
+fn unload_chunks(
+    mut chunk_map: ResMut<ChunkMap>,
+    config: Res<ServerConfig>,
+    world_save: Res<WorldSave>,
+    players_q: Query<&Transform, With<ConnectedPlayer>>,
+) -> Result {
+    let kept = players_q
+        .iter()
+        .flat_map(|transform| {
+            chunks_around(transform.translation, config.view_distance + UNLOAD_MARGIN)
+        })
+        .map(|(chunk_pos, _)| chunk_pos)
+        .collect::<HashSet<_>>();
+    let unloaded = chunk_map
+        .chunks()
+        .map(|(chunk_pos, _)| *chunk_pos)
+        .filter(|chunk_pos| !kept.contains(chunk_pos))
+        .collect::<Vec<_>>();
+    for chunk_pos in unloaded {
+        let Some(mut chunk) = chunk_map.remove_chunk(chunk_pos) else {
+            continue;
+        };
+        if chunk.take_dirty() {
+            world_save.save_chunk(chunk_pos, &chunk)?;
+        }
+    }
+    Ok(())
+}
+
+fn autosave(
+    time: Res<Time>,
+    mut timer: ResMut<AutosaveTimer>,
+    mut world_save: ResMut<WorldSave>,
+    mut chunk_map: ResMut<ChunkMap>,
+    world_time: Res<WorldTime>,
+) -> Result {
+    if !timer.tick(time.delta()).just_finished() {
+        return Ok(());
+    }
+    world_save.save_time(world_time.time_of_day)?;
+    let saved = world_save.save_dirty_chunks(&mut chunk_map)?;
+    if saved > 0 {
+        info!("Autosaved {saved} chunks");
+    }
+    Ok(())
+}
+
+fn save_on_exit(
+    mut world_save: ResMut<WorldSave>,
+    mut chunk_map: ResMut<ChunkMap>,
+    world_time: Res<WorldTime>,
+) -> Result {
+    world_save.save_time(world_time.time_of_day)?;
+    let saved = world_save.save_dirty_chunks(&mut chunk_map)?;
+    info!("Saved {saved} chunks to {}", world_save.dir().display());
+    Ok(())
+}
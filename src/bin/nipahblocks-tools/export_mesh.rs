@@ -0,0 +1,374 @@
+use anyhow::anyhow;
+use bevy::prelude::*;
+use clap::{value_parser, Arg, ArgMatches, Command};
+use nipahblocks::block::MeshData;
+use nipahblocks::block_registry::{load_texture, Transparency};
+use nipahblocks::chunk::{ChunkMap, CHUNK_SIZE};
+use serde_json::{json, Value};
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::headless::{get_position, position_arg, ChunkSource, HeadlessResources};
+
+/// Keeps exports to a size external tools can still open.
+const MAX_CHUNKS: usize = 4096;
+
+pub fn command() -> Command {
+    Command::new("export-mesh")
+        .about("Exports the terrain meshes of a box of chunks as glTF or OBJ")
+        .arg(
+            Arg::new("output")
+                .required(true)
+                .value_parser(value_parser!(PathBuf))
+                .help(".gltf or .obj file to write, block textures go into a PNG atlas next to it"),
+        )
+        .arg(
+            position_arg("from")
+                .required(true)
+                .help("Block in one corner of the box, whole chunks are exported"),
+        )
+        .arg(
+            position_arg("to")
+                .required(true)
+                .help("Block in the opposite corner of the box"),
+        )
+        .arg(
+            Arg::new("world")
+                .long("world")
+                .help("Saved world to export, chunks it doesn't have are generated"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_parser(value_parser!(u32))
+                .conflicts_with("world")
+                .help("Seed to generate the chunks with when not exporting a world"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> anyhow::Result<()> {
+    let output = matches.get_one::<PathBuf>("output").unwrap();
+    let extension = output.extension().and_then(|extension| extension.to_str());
+    if !matches!(extension, Some("gltf" | "obj")) {
+        return Err(anyhow!("Output has to be a .gltf or .obj file"));
+    }
+    let from = get_position(matches, "from").unwrap();
+    let to = get_position(matches, "to").unwrap();
+    let min = ChunkMap::chunk_pos(from.min(to));
+    let max = ChunkMap::chunk_pos(from.max(to));
+    let step = CHUNK_SIZE as usize;
+    let chunk_positions = (min.y..=max.y)
+        .step_by(step)
+        .flat_map(|y| {
+            (min.z..=max.z).step_by(step).flat_map(move |z| {
+                (min.x..=max.x)
+                    .step_by(step)
+                    .map(move |x| IVec3::new(x, y, z))
+            })
+        })
+        .collect::<Vec<_>>();
+    if chunk_positions.len() > MAX_CHUNKS {
+        return Err(anyhow!(
+            "Box spans {} chunks, more than {MAX_CHUNKS}",
+            chunk_positions.len()
+        ));
+    }
+
+    let resources = HeadlessResources::load()?;
+    let source = ChunkSource::open(
+        matches.get_one::<String>("world").map(String::as_str),
+        matches.get_one::<u32>("seed").copied(),
+        &resources,
+    )?;
+    let mut chunk_map = resources.chunk_map();
+    for &chunk_pos in &chunk_positions {
+        chunk_map.insert_chunk(chunk_pos, source.chunk(chunk_pos, &resources)?);
+    }
+    let mut layers: [MeshData; 3] = default();
+    for &chunk_pos in &chunk_positions {
+        let chunk = chunk_map.chunk(chunk_pos).unwrap();
+        for (layer, mut mesh) in layers
+            .iter_mut()
+            .zip(chunk.build_mesh_data(Some((&chunk_map, chunk_pos)), true))
+        {
+            for position in &mut mesh.positions {
+                *position = (Vec3::from(*position) + chunk_pos.as_vec3()).to_array();
+            }
+            layer.merge(mesh);
+        }
+    }
+
+    let atlas = Atlas::build(&resources)?;
+    let stem = output
+        .file_stem()
+        .ok_or(anyhow!("Output has no file name"))?
+        .to_string_lossy();
+    let atlas_file = format!("{stem}_atlas.png");
+    let layers = Transparency::ALL
+        .into_iter()
+        .zip(layers)
+        .filter(|(_, mesh)| !mesh.is_empty())
+        .map(|(transparency, mut mesh)| {
+            for (uv, layer) in mesh.uvs.iter_mut().zip(&mesh.layers) {
+                *uv = atlas.uv(*uv, layer[0] as u32);
+            }
+            (transparency, mesh)
+        })
+        .collect::<Vec<_>>();
+    if let Some(dir) = output.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    atlas.image.save(output.with_file_name(&atlas_file))?;
+    match extension {
+        Some("gltf") => write_gltf(output, &stem, &atlas_file, &layers)?,
+        _ => write_obj(output, &stem, &atlas_file, &layers)?,
+    }
+    println!(
+        "Exported {} chunks with {} triangles to {}",
+        chunk_positions.len(),
+        layers
+            .iter()
+            .map(|(_, mesh)| mesh.indices.len() / 3)
+            .sum::<usize>(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// Block textures laid out in a grid, standing in for the texture array the
+/// game renders with.
+struct Atlas {
+    image: image::RgbaImage,
+    columns: u32,
+    rows: u32,
+}
+
+impl Atlas {
+    fn build(resources: &HeadlessResources) -> anyhow::Result<Self> {
+        let textures = resources
+            .textures
+            .iter()
+            .map(|name| load_texture(&resources.textures_dir().join(name)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let size = textures.first().ok_or(anyhow!("No block textures"))?.size();
+        let columns = (textures.len() as f32).sqrt().ceil() as u32;
+        let rows = (textures.len() as u32).div_ceil(columns);
+        let width = (columns * size.x) as usize;
+        let mut data = vec![0; width * (rows * size.y) as usize * 4];
+        for (i, (texture, name)) in textures.iter().zip(&resources.textures).enumerate() {
+            if texture.size() != size {
+                return Err(anyhow!(
+                    "Block texture {name} size {} doesn't match {size}",
+                    texture.size()
+                ));
+            }
+            let pixels = texture
+                .data
+                .as_ref()
+                .ok_or(anyhow!("Block texture {name} has no pixel data"))?;
+            let origin = UVec2::new(i as u32 % columns, i as u32 / columns) * size;
+            for (y, row) in pixels.chunks_exact(size.x as usize * 4).enumerate() {
+                let start = ((origin.y as usize + y) * width + origin.x as usize) * 4;
+                data[start..start + row.len()].copy_from_slice(row);
+            }
+        }
+        let image = image::RgbaImage::from_raw(columns * size.x, rows * size.y, data)
+            .ok_or(anyhow!("Atlas data doesn't match its size"))?;
+        Ok(Self {
+            image,
+            columns,
+            rows,
+        })
+    }
+
+    /// Maps a UV within a texture layer to the atlas.
+    fn uv(&self, uv: [f32; 2], layer: u32) -> [f32; 2] {
+        let column = (layer % self.columns) as f32;
+        let row = (layer / self.columns) as f32;
+        [
+            (column + uv[0]) / self.columns as f32,
+            (row + uv[1]) / self.rows as f32,
+        ]
+    }
+}
+
+fn material_name(transparency: Transparency) -> &'static str {
+    match transparency {
+        Transparency::Opaque => "opaque",
+        Transparency::Cutout => "cutout",
+        Transparency::Translucent => "translucent",
+    }
+}
+
+/// Binary buffer of a glTF file with its buffer views and accessors.
+#[derive(Default)]
+struct GltfBuffer {
+    data: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl GltfBuffer {
+    /// Appends floats with `components` per element as a new accessor,
+    /// returning its index.
+    fn push_floats(&mut self, values: &[f32], components: usize, kind: &str) -> usize {
+        let mut accessor = json!({ "componentType": 5126, "type": kind });
+        if kind == "VEC3" && !values.is_empty() {
+            // Required for positions, harmless for normals.
+            let (min, max) =
+                values
+                    .chunks_exact(3)
+                    .fold((Vec3::MAX, Vec3::MIN), |(min, max), value| {
+                        let value = Vec3::from_slice(value);
+                        (min.min(value), max.max(value))
+                    });
+            accessor["min"] = json!(min.to_array());
+            accessor["max"] = json!(max.to_array());
+        }
+        let bytes = values.iter().flat_map(|value| value.to_le_bytes());
+        self.push(bytes, values.len() / components, 34962, accessor)
+    }
+
+    fn push_indices(&mut self, indices: &[u32]) -> usize {
+        let bytes = indices.iter().flat_map(|index| index.to_le_bytes());
+        let accessor = json!({ "componentType": 5125, "type": "SCALAR" });
+        self.push(bytes, indices.len(), 34963, accessor)
+    }
+
+    fn push(
+        &mut self,
+        bytes: impl Iterator<Item = u8>,
+        count: usize,
+        target: u32,
+        mut accessor: Value,
+    ) -> usize {
+        let offset = self.data.len();
+        self.data.extend(bytes);
+        self.views.push(json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": self.data.len() - offset,
+            "target": target,
+        }));
+        accessor["bufferView"] = json!(self.views.len() - 1);
+        accessor["count"] = json!(count);
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+}
+
+/// Writes a `.gltf` file with its buffer in a `.bin` file next to it. Vertex
+/// colors carry the ambient occlusion the game would shade with.
+fn write_gltf(
+    path: &Path,
+    stem: &str,
+    atlas_file: &str,
+    layers: &[(Transparency, MeshData)],
+) -> anyhow::Result<()> {
+    let mut buffer = GltfBuffer::default();
+    let mut materials = Vec::new();
+    let mut primitives = Vec::new();
+    for (transparency, mesh) in layers {
+        let colors = mesh
+            .colors
+            .iter()
+            .flat_map(|&[_, _, ao, _]| [ao, ao, ao, 1.0])
+            .collect::<Vec<_>>();
+        let attributes = json!({
+            "POSITION": buffer.push_floats(mesh.positions.as_flattened(), 3, "VEC3"),
+            "NORMAL": buffer.push_floats(mesh.normals.as_flattened(), 3, "VEC3"),
+            "TEXCOORD_0": buffer.push_floats(mesh.uvs.as_flattened(), 2, "VEC2"),
+            "COLOR_0": buffer.push_floats(&colors, 4, "VEC4"),
+        });
+        let mut material = json!({
+            "name": material_name(*transparency),
+            "pbrMetallicRoughness": {
+                "baseColorTexture": { "index": 0 },
+                "metallicFactor": 0.0,
+                "roughnessFactor": 1.0,
+            },
+        });
+        match transparency {
+            Transparency::Opaque => material["alphaMode"] = json!("OPAQUE"),
+            Transparency::Cutout => {
+                material["alphaMode"] = json!("MASK");
+                material["alphaCutoff"] = json!(0.5);
+                material["doubleSided"] = json!(true);
+            }
+            Transparency::Translucent => material["alphaMode"] = json!("BLEND"),
+        }
+        materials.push(material);
+        primitives.push(json!({
+            "attributes": attributes,
+            "indices": buffer.push_indices(&mesh.indices),
+            "material": materials.len() - 1,
+        }));
+    }
+    let bin_file = format!("{stem}.bin");
+    let gltf = json!({
+        "asset": { "version": "2.0", "generator": "nipahblocks" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "name": stem, "mesh": 0 }],
+        "meshes": [{ "name": stem, "primitives": primitives }],
+        "materials": materials,
+        "textures": [{ "sampler": 0, "source": 0 }],
+        // Nearest filtering keeps the pixel art sharp.
+        "samplers": [{ "magFilter": 9728, "minFilter": 9728 }],
+        "images": [{ "uri": atlas_file }],
+        "buffers": [{ "uri": bin_file, "byteLength": buffer.data.len() }],
+        "bufferViews": buffer.views,
+        "accessors": buffer.accessors,
+    });
+    fs::write(path.with_file_name(&bin_file), &buffer.data)?;
+    fs::write(path, serde_json::to_string_pretty(&gltf)?)?;
+    Ok(())
+}
+
+/// Writes an `.obj` file with its materials in a `.mtl` file next to it.
+fn write_obj(
+    path: &Path,
+    stem: &str,
+    atlas_file: &str,
+    layers: &[(Transparency, MeshData)],
+) -> anyhow::Result<()> {
+    let mtl_file = format!("{stem}.mtl");
+    let mut mtl = BufWriter::new(fs::File::create(path.with_file_name(&mtl_file))?);
+    for (transparency, _) in layers {
+        writeln!(mtl, "newmtl {}", material_name(*transparency))?;
+        writeln!(mtl, "Kd 1 1 1")?;
+        writeln!(mtl, "map_Kd {atlas_file}")?;
+        if *transparency != Transparency::Opaque {
+            writeln!(mtl, "map_d {atlas_file}")?;
+        }
+    }
+    mtl.flush()?;
+
+    let mut obj = BufWriter::new(fs::File::create(path)?);
+    writeln!(obj, "mtllib {mtl_file}")?;
+    writeln!(obj, "o {stem}")?;
+    let mut start = 1;
+    for (transparency, mesh) in layers {
+        for [x, y, z] in &mesh.positions {
+            writeln!(obj, "v {x} {y} {z}")?;
+        }
+        // OBJ texture coordinates start at the bottom.
+        for [u, v] in &mesh.uvs {
+            writeln!(obj, "vt {u} {}", 1.0 - v)?;
+        }
+        for [x, y, z] in &mesh.normals {
+            writeln!(obj, "vn {x} {y} {z}")?;
+        }
+        writeln!(obj, "usemtl {}", material_name(*transparency))?;
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize + start);
+            writeln!(obj, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}")?;
+        }
+        start += mesh.positions.len();
+    }
+    obj.flush()?;
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use anyhow::anyhow;
+use bevy::{prelude::*, utils::hashbrown::HashMap};
+use clap::{value_parser, Arg, ArgMatches};
+use nipahblocks::block::Block;
+use nipahblocks::block_registry::{BlockInfo, BlockInfoRegistry};
+use nipahblocks::chunk::{Chunk, ChunkMap};
+use nipahblocks::generator::generate_chunk;
+use nipahblocks::persistence::{WorldSave, DEFAULT_SEED, UNKNOWN_BLOCK};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{ASSETS_DIR, BLOCK_INFO_REGISTRY, BLOCK_TEXTURES_DIR, SAVES_DIR};
+
+/// Block registry read straight from the assets folder, for subcommands that
+/// run without a window or the asset server.
+pub struct HeadlessResources {
+    pub registry: BlockInfoRegistry,
+    /// Texture file names, indexed by the texture layers `blocks` use.
+    pub textures: Vec<String>,
+    pub blocks_map: Arc<HashMap<String, usize>>,
+    pub block_names: Arc<Vec<String>>,
+    pub blocks: Arc<Vec<Block>>,
+}
+
+impl HeadlessResources {
+    pub fn load() -> anyhow::Result<Self> {
+        let registry = BlockInfoRegistry::load(&Path::new(ASSETS_DIR).join(BLOCK_INFO_REGISTRY))?;
+        let mut textures = Vec::<String>::new();
+        for name in registry.blocks.iter().flat_map(BlockInfo::faces) {
+            if !textures.iter().any(|texture| texture == name) {
+                textures.push(name.to_string());
+            }
+        }
+        let blocks = registry.build_blocks(|name| {
+            textures
+                .iter()
+                .position(|texture| texture == name)
+                .map(|layer| layer as u32)
+                .ok_or(anyhow!("Unknown block texture: {name}"))
+        })?;
+        let block_names = registry.block_names();
+        let blocks_map = block_names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.clone(), i))
+            .collect();
+        Ok(Self {
+            registry,
+            textures,
+            blocks_map: Arc::new(blocks_map),
+            block_names: Arc::new(block_names),
+            blocks: Arc::new(blocks),
+        })
+    }
+
+    pub fn textures_dir(&self) -> PathBuf {
+        Path::new(ASSETS_DIR).join(BLOCK_TEXTURES_DIR)
+    }
+
+    /// An empty chunk map using this registry.
+    pub fn chunk_map(&self) -> ChunkMap {
+        let mut chunk_map = ChunkMap::default();
+        chunk_map.set_registry(self.block_names.clone(), self.blocks.clone(), UNKNOWN_BLOCK);
+        chunk_map
+    }
+}
+
+/// Where headless subcommands get chunks from.
+pub enum ChunkSource {
+    /// A saved world, generating the chunks it doesn't have yet.
+    Saved(WorldSave),
+    /// Freshly generated terrain with this seed.
+    Generated(u32),
+}
+
+impl ChunkSource {
+    /// Opens the saved `world` if given, which has to exist already.
+    pub fn open(
+        world: Option<&str>,
+        seed: Option<u32>,
+        resources: &HeadlessResources,
+    ) -> anyhow::Result<Self> {
+        let Some(world) = world else {
+            return Ok(Self::Generated(seed.unwrap_or(DEFAULT_SEED)));
+        };
+        let dir = Path::new(SAVES_DIR).join(world);
+        if !dir.exists() {
+            return Err(anyhow!("World {} doesn't exist", dir.display()));
+        }
+        Ok(Self::Saved(WorldSave::open(
+            &dir,
+            None,
+            &resources.block_names,
+        )?))
+    }
+
+    pub fn seed(&self) -> u32 {
+        match self {
+            Self::Saved(world_save) => world_save.seed(),
+            Self::Generated(seed) => *seed,
+        }
+    }
+
+    pub fn chunk(&self, chunk_pos: IVec3, resources: &HeadlessResources) -> anyhow::Result<Chunk> {
+        let saved = match self {
+            Self::Saved(world_save) => {
+                world_save.load_chunk(chunk_pos, resources.blocks.clone())?
+            }
+            Self::Generated(_) => None,
+        };
+        Ok(saved.unwrap_or_else(|| {
+            generate_chunk(
+                chunk_pos.as_vec3(),
+                self.seed(),
+                resources.blocks_map.clone(),
+                resources.blocks.clone(),
+            )
+        }))
+    }
+
+    /// Writes changed chunks back to the saved world, returning how many were
+    /// saved.
+    pub fn save(&self, chunk_map: &mut ChunkMap) -> anyhow::Result<usize> {
+        match self {
+            Self::Saved(world_save) => world_save.save_dirty_chunks(chunk_map),
+            Self::Generated(_) => Ok(0),
+        }
+    }
+}
+
+/// An argument taking a block position as three numbers.
+pub fn position_arg(id: &'static str) -> Arg {
+    Arg::new(id)
+        .long(id)
+        .num_args(3)
+        .value_names(["X", "Y", "Z"])
+        .allow_negative_numbers(true)
+        .value_parser(value_parser!(i32))
+}
+
+pub fn get_position(matches: &ArgMatches, id: &str) -> Option<IVec3> {
+    let mut values = matches.get_many::<i32>(id)?.copied();
+    Some(IVec3::new(values.next()?, values.next()?, values.next()?))
+}
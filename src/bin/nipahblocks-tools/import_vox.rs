@@ -0,0 +1,119 @@
+use bevy::{
+    prelude::*,
+    utils::hashbrown::{HashMap, HashSet},
+};
+use clap::{value_parser, Arg, ArgGroup, ArgMatches, Command};
+use nipahblocks::chunk::ChunkMap;
+use nipahblocks::schematic::{Schematic, SCHEMATIC_EXTENSION};
+use nipahblocks::vox::{block_colors, VoxModel};
+use nipahblocks::world_edit::SCHEMATICS_DIR;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::headless::{get_position, position_arg, ChunkSource, HeadlessResources};
+
+pub fn command() -> Command {
+    Command::new("import-vox")
+        .about("Imports a MagicaVoxel model as a schematic or into a world")
+        .arg(
+            Arg::new("file")
+                .required(true)
+                .value_parser(value_parser!(PathBuf))
+                .help(".vox file to import, only its first model is read"),
+        )
+        .arg(
+            Arg::new("mapping")
+                .long("mapping")
+                .value_parser(value_parser!(PathBuf))
+                .help(
+                    "JSON object mapping palette indices (\"12\") or colors (\"#a0522d\") to \
+                     block names, other colors get the block with the closest texture color",
+                ),
+        )
+        .arg(
+            Arg::new("schematic")
+                .long("schematic")
+                .help("Saves the model as a schematic with this name"),
+        )
+        .arg(
+            Arg::new("world")
+                .long("world")
+                .requires("at")
+                .help("Places the model into this saved world"),
+        )
+        .arg(position_arg("at").help("Block the bottom center of the model is placed at"))
+        .group(
+            ArgGroup::new("target")
+                .args(["schematic", "world"])
+                .required(true),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> anyhow::Result<()> {
+    let path = matches.get_one::<PathBuf>("file").unwrap();
+    let model = VoxModel::load(path)?;
+    if model.model_count > 1 {
+        println!(
+            "{} has {} models, importing the first one",
+            path.display(),
+            model.model_count
+        );
+    }
+    let resources = HeadlessResources::load()?;
+    let mapping = match matches.get_one::<PathBuf>("mapping") {
+        Some(path) => serde_json::from_str(&fs::read_to_string(path)?)?,
+        None => HashMap::new(),
+    };
+    let texture_colors = resources
+        .registry
+        .texture_colors(&resources.textures_dir())?;
+    let palette_blocks = model.map_palette(
+        &mapping,
+        &resources.blocks_map,
+        &block_colors(&resources.registry, &texture_colors),
+    )?;
+    for (index, block) in palette_blocks.iter().enumerate() {
+        if let Some(block) = block {
+            println!(
+                "Palette {index} {} -> {}",
+                model.color(index as u8).to_hex(),
+                resources.block_names[*block]
+            );
+        }
+    }
+
+    if let Some(name) = matches.get_one::<String>("schematic") {
+        let clipboard = model.to_clipboard(&palette_blocks)?;
+        let path = Path::new(SCHEMATICS_DIR)
+            .join(name)
+            .with_extension(SCHEMATIC_EXTENSION);
+        Schematic::from_clipboard(&clipboard, &resources.block_names).save(&path)?;
+        println!("Saved {} schematic {}", clipboard.size(), path.display());
+    }
+
+    if let Some(world) = matches.get_one::<String>("world") {
+        let at = get_position(matches, "at").unwrap();
+        let source = ChunkSource::open(Some(world), None, &resources)?;
+        let mut chunk_map = resources.chunk_map();
+        let edits = model
+            .blocks(&palette_blocks)
+            .map(|(pos, block)| (at + pos, Some(block)))
+            .collect::<Vec<_>>();
+        let chunk_positions = edits
+            .iter()
+            .map(|&(pos, _)| ChunkMap::chunk_pos(pos))
+            .collect::<HashSet<_>>();
+        for chunk_pos in chunk_positions {
+            chunk_map.insert_chunk(chunk_pos, source.chunk(chunk_pos, &resources)?);
+        }
+        let changes = chunk_map.set_blocks(edits);
+        let saved = source.save(&mut chunk_map)?;
+        println!(
+            "Placed {} blocks at {at} in world {world}, saving {saved} chunks",
+            changes.len()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use clap::Command;
+
+mod export_mesh;
+mod headless;
+mod import_vox;
+mod render_map;
+
+const ASSETS_DIR: &str = "assets";
+const BLOCK_INFO_REGISTRY: &str = "block_registry.json";
+const BLOCK_TEXTURES_DIR: &str = "textures/blocks";
+const SAVES_DIR: &str = "saves";
+
+fn main() -> anyhow::Result<()> {
+    let matches = Command::new("nipahblocks-tools")
+        .about("Works on worlds and models without a window or GPU")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(import_vox::command())
+        .subcommand(export_mesh::command())
+        .subcommand(render_map::command())
+        .get_matches();
+    match matches.subcommand() {
+        Some(("import-vox", matches)) => import_vox::run(matches),
+        Some(("export-mesh", matches)) => export_mesh::run(matches),
+        Some(("render-map", matches)) => render_map::run(matches),
+        _ => unreachable!("clap requires a known subcommand"),
+    }
+}
@@ -0,0 +1,227 @@
+use anyhow::anyhow;
+use bevy::prelude::*;
+use clap::{builder::PossibleValuesParser, value_parser, Arg, ArgAction, ArgMatches, Command};
+use nipahblocks::chunk::{ChunkMap, CHUNK_SIZE};
+use nipahblocks::generator::{height_band_at, HeightBand};
+use std::{fs, path::PathBuf};
+
+use crate::headless::{ChunkSource, HeadlessResources};
+
+/// Largest map side in blocks.
+const MAX_SIZE: u32 = 4096;
+/// Height between contour lines of the height overlay.
+const CONTOUR_INTERVAL: i32 = 16;
+/// How much of the height band color the biome overlay mixes in.
+const BIOME_OVERLAY_OPACITY: f32 = 0.4;
+
+pub fn command() -> Command {
+    Command::new("render-map")
+        .about("Renders a top-down map of the terrain to a PNG, one pixel per block")
+        .arg(
+            Arg::new("output")
+                .required(true)
+                .value_parser(value_parser!(PathBuf))
+                .help("PNG file to write"),
+        )
+        .arg(column_arg("from", "-256").help("Column in one corner of the map"))
+        .arg(column_arg("to", "255").help("Column in the opposite corner of the map"))
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_parser(value_parser!(u32))
+                .help("Seed to generate the terrain with"),
+        )
+        .arg(
+            Arg::new("world")
+                .long("world")
+                .conflicts_with("seed")
+                .help("Saved world to render, chunks it doesn't have are generated"),
+        )
+        .arg(
+            Arg::new("min-y")
+                .long("min-y")
+                .default_value("-128")
+                .allow_negative_numbers(true)
+                .value_parser(value_parser!(i32))
+                .help("Lowest height searched for the surface, darkest in the shading"),
+        )
+        .arg(
+            Arg::new("max-y")
+                .long("max-y")
+                .default_value("127")
+                .allow_negative_numbers(true)
+                .value_parser(value_parser!(i32))
+                .help("Highest height searched for the surface, brightest in the shading"),
+        )
+        .arg(
+            Arg::new("overlay")
+                .long("overlay")
+                .action(ArgAction::Append)
+                .value_parser(PossibleValuesParser::new(["biome", "height"]))
+                .help(
+                    "Tints columns by height band, which stands in for biomes until the \
+                     generator has them, or draws height contour lines, can be repeated",
+                ),
+        )
+}
+
+/// An argument taking a column as two numbers.
+fn column_arg(id: &'static str, default: &'static str) -> Arg {
+    Arg::new(id)
+        .long(id)
+        .num_args(2)
+        .value_names(["X", "Z"])
+        .default_values([default, default])
+        .allow_negative_numbers(true)
+        .value_parser(value_parser!(i32))
+}
+
+fn get_column(matches: &ArgMatches, id: &str) -> IVec2 {
+    let mut values = matches.get_many::<i32>(id).unwrap().copied();
+    IVec2::new(values.next().unwrap(), values.next().unwrap())
+}
+
+fn band_color(band: HeightBand) -> LinearRgba {
+    match band {
+        HeightBand::Lowlands => Srgba::rgb_u8(0x3a, 0x7b, 0xd5),
+        HeightBand::Plains => Srgba::rgb_u8(0x7c, 0xc4, 0x4a),
+        HeightBand::Hills => Srgba::rgb_u8(0xc8, 0x9b, 0x3c),
+        HeightBand::Mountains => Srgba::rgb_u8(0xf0, 0xf0, 0xf0),
+    }
+    .into()
+}
+
+pub fn run(matches: &ArgMatches) -> anyhow::Result<()> {
+    let output = matches.get_one::<PathBuf>("output").unwrap();
+    let from = get_column(matches, "from");
+    let to = get_column(matches, "to");
+    let min = from.min(to);
+    let size = (from.max(to) - min + 1).as_uvec2();
+    if size.max_element() > MAX_SIZE {
+        return Err(anyhow!("Map is {size} blocks, larger than {MAX_SIZE}"));
+    }
+    let min_y = *matches.get_one::<i32>("min-y").unwrap();
+    let max_y = *matches.get_one::<i32>("max-y").unwrap();
+    if min_y >= max_y {
+        return Err(anyhow!("--min-y has to be below --max-y"));
+    }
+    let overlay = |name: &str| {
+        matches
+            .get_many::<String>("overlay")
+            .is_some_and(|mut overlays| overlays.any(|overlay| overlay == name))
+    };
+    let (biome_overlay, height_overlay) = (overlay("biome"), overlay("height"));
+
+    let resources = HeadlessResources::load()?;
+    let source = ChunkSource::open(
+        matches.get_one::<String>("world").map(String::as_str),
+        matches.get_one::<u32>("seed").copied(),
+        &resources,
+    )?;
+    let texture_colors = resources
+        .registry
+        .texture_colors(&resources.textures_dir())?;
+    let top_colors = resources
+        .registry
+        .blocks
+        .iter()
+        .map(|info| {
+            texture_colors
+                .get(&info.top)
+                .map_or(LinearRgba::BLACK, |&color| color.into())
+        })
+        .collect::<Vec<_>>();
+
+    // Surface height and block of every column, searched from the top.
+    let mut surface = vec![None; (size.x * size.y) as usize];
+    let chunk_size = CHUNK_SIZE as i32;
+    let min_chunk = ChunkMap::chunk_pos(IVec3::new(min.x, min_y, min.y));
+    let max_chunk = ChunkMap::chunk_pos(IVec3::new(
+        min.x + size.x as i32 - 1,
+        max_y,
+        min.y + size.y as i32 - 1,
+    ));
+    for chunk_z in (min_chunk.z..=max_chunk.z).step_by(CHUNK_SIZE as usize) {
+        for chunk_x in (min_chunk.x..=max_chunk.x).step_by(CHUNK_SIZE as usize) {
+            // Columns of this chunk column inside the map.
+            let mut missing = (0..chunk_size)
+                .flat_map(|z| (0..chunk_size).map(move |x| IVec2::new(chunk_x + x, chunk_z + z)))
+                .filter(|column| {
+                    let pixel = *column - min;
+                    pixel.cmpge(IVec2::ZERO).all() && pixel.cmplt(size.as_ivec2()).all()
+                })
+                .collect::<Vec<_>>();
+            let mut chunk_y = max_chunk.y;
+            while !missing.is_empty() && chunk_y >= min_chunk.y {
+                let chunk_pos = IVec3::new(chunk_x, chunk_y, chunk_z);
+                let chunk = source.chunk(chunk_pos, &resources)?;
+                missing.retain(|&column| {
+                    let local = column - IVec2::new(chunk_x, chunk_z);
+                    let found = (0..chunk_size)
+                        .rev()
+                        .map(|y| (chunk_y + y, IVec3::new(local.x, y, local.y)))
+                        .filter(|(y, _)| (min_y..=max_y).contains(y))
+                        .find_map(|(y, local)| chunk.at(local.as_uvec3()).map(|block| (y, block)));
+                    if found.is_some() {
+                        let pixel = (column - min).as_uvec2();
+                        surface[(pixel.x + pixel.y * size.x) as usize] = found;
+                    }
+                    found.is_none()
+                });
+                chunk_y -= chunk_size;
+            }
+        }
+    }
+
+    let noise = noise::Perlin::new(source.seed());
+    let height_at = |x: u32, z: u32| surface[(x + z * size.x) as usize].map(|(y, _)| y);
+    let mut data = Vec::with_capacity(surface.len() * 4);
+    for z in 0..size.y {
+        for x in 0..size.x {
+            let Some((y, block)) = surface[(x + z * size.x) as usize] else {
+                data.extend_from_slice(&[0; 4]);
+                continue;
+            };
+            // Brighter the higher the column, with slopes facing north west
+            // lit and the others shadowed.
+            let t = (y - min_y) as f32 / (max_y - min_y) as f32;
+            let mut brightness = 0.5 + 0.8 * t;
+            let neighbors = [
+                x.checked_sub(1).and_then(|x| height_at(x, z)),
+                z.checked_sub(1).and_then(|z| height_at(x, z)),
+            ];
+            for neighbor in neighbors.into_iter().flatten() {
+                brightness *= 1.0 + (y - neighbor).clamp(-4, 4) as f32 * 0.04;
+            }
+            let mut color = top_colors[block] * brightness;
+            let column = min + IVec2::new(x as i32, z as i32);
+            if biome_overlay {
+                let band = height_band_at(&noise, column.x, column.y);
+                color = color.mix(&band_color(band), BIOME_OVERLAY_OPACITY);
+            }
+            let band = y.div_euclid(CONTOUR_INTERVAL);
+            if height_overlay
+                && neighbors
+                    .into_iter()
+                    .flatten()
+                    .any(|neighbor| neighbor.div_euclid(CONTOUR_INTERVAL) != band)
+            {
+                color *= 0.4;
+            }
+            data.extend_from_slice(&Srgba::from(color.with_alpha(1.0)).to_u8_array());
+        }
+    }
+
+    let image = image::RgbaImage::from_raw(size.x, size.y, data)
+        .ok_or(anyhow!("Map data doesn't match its size"))?;
+    if let Some(dir) = output.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    image.save(output)?;
+    println!(
+        "Rendered {size} map of seed {} from {min} to {}",
+        source.seed(),
+        min + size.as_ivec2() - 1
+    );
+    Ok(())
+}
@@ -1,217 +1,388 @@
-use bevy::{
-    asset::RenderAssetUsages,
-    prelude::*,
-    render::mesh::{Indices, PrimitiveTopology},
-};
+use bevy::prelude::*;
+
+use crate::block_registry::{BlockShapeInfo, Facing, Transparency};
 
 pub const BLOCK_HALF_SIZE: f32 = 0.5;
 
-#[derive(Debug, Clone)]
-pub struct Block {
-    front: Rect,
-    back: Rect,
-    left: Rect,
-    right: Rect,
-    top: Rect,
-    bottom: Rect,
+const SHAPE_EPSILON: f32 = 1e-4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Face {
+    Front,
+    Back,
+    Right,
+    Left,
+    Top,
+    Bottom,
 }
 
-fn build_face_mesh(verices: Vec<[f32; 3]>, normal: [f32; 3], uvs: Vec<[f32; 2]>) -> Mesh {
-    Mesh::new(
-        PrimitiveTopology::TriangleList,
-        RenderAssetUsages::default(),
-    )
-    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, verices)
-    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![normal, normal, normal, normal])
-    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
-    .with_inserted_indices(Indices::U32(vec![0, 1, 2, 2, 3, 0]))
+impl Face {
+    pub const ALL: [Face; 6] = [
+        Face::Front,
+        Face::Back,
+        Face::Right,
+        Face::Left,
+        Face::Top,
+        Face::Bottom,
+    ];
+
+    pub fn normal(self) -> IVec3 {
+        match self {
+            Face::Front => IVec3::Z,
+            Face::Back => IVec3::NEG_Z,
+            Face::Right => IVec3::X,
+            Face::Left => IVec3::NEG_X,
+            Face::Top => IVec3::Y,
+            Face::Bottom => IVec3::NEG_Y,
+        }
+    }
+
+    pub fn opposite(self) -> Face {
+        match self {
+            Face::Front => Face::Back,
+            Face::Back => Face::Front,
+            Face::Right => Face::Left,
+            Face::Left => Face::Right,
+            Face::Top => Face::Bottom,
+            Face::Bottom => Face::Top,
+        }
+    }
+
+    /// Projects a block-local point onto the plane of this face. Opposite faces
+    /// share the same projection so their rectangles can be compared directly.
+    fn project(self, p: Vec3) -> Vec2 {
+        match self {
+            Face::Front | Face::Back => Vec2::new(p.x, p.y),
+            Face::Right | Face::Left => Vec2::new(p.z, p.y),
+            Face::Top | Face::Bottom => Vec2::new(p.x, p.z),
+        }
+    }
 }
 
-impl Block {
-    pub fn new(front: Rect, back: Rect, left: Rect, right: Rect, top: Rect, bottom: Rect) -> Self {
+/// Axis-aligned box in block-local coordinates, `0.0..=1.0` on every axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockBox {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl BlockBox {
+    pub const FULL: BlockBox = BlockBox {
+        min: Vec3::ZERO,
+        max: Vec3::ONE,
+    };
+
+    pub fn new(min: Vec3, max: Vec3) -> Self {
         Self {
-            front,
-            back,
-            left,
-            right,
-            top,
-            bottom,
+            min: min.min(max),
+            max: min.max(max),
         }
     }
 
-    pub fn build_front_face_shifted(&self, shift: Vec3) -> Mesh {
-        let normal = [0.0, 0.0, 1.0];
-        let max = shift + BLOCK_HALF_SIZE;
-        let min = shift - BLOCK_HALF_SIZE;
-        let (uv_min, uv_max) = (self.front.min, self.front.max);
-        build_face_mesh(
-            vec![
-                [min.x, min.y, max.z],
-                [max.x, min.y, max.z],
-                [max.x, max.y, max.z],
-                [min.x, max.y, max.z],
-            ],
-            normal,
-            vec![
-                [uv_min.x, uv_max.y],
-                [uv_max.x, uv_max.y],
-                [uv_max.x, uv_min.y],
-                [uv_min.x, uv_min.y],
-            ],
-        )
+    fn touches(&self, face: Face) -> bool {
+        match face {
+            Face::Front => self.max.z >= 1.0 - SHAPE_EPSILON,
+            Face::Back => self.min.z <= SHAPE_EPSILON,
+            Face::Right => self.max.x >= 1.0 - SHAPE_EPSILON,
+            Face::Left => self.min.x <= SHAPE_EPSILON,
+            Face::Top => self.max.y >= 1.0 - SHAPE_EPSILON,
+            Face::Bottom => self.min.y <= SHAPE_EPSILON,
+        }
     }
 
-    pub fn build_front_face(&self) -> Mesh {
-        self.build_front_face_shifted(Vec3::ZERO)
+    fn face_rect(&self, face: Face) -> Rect {
+        Rect::from_corners(face.project(self.min), face.project(self.max))
     }
 
-    pub fn build_back_face_shifted(&self, shift: Vec3) -> Mesh {
-        let normal = [0.0, 0.0, -1.0];
-        let max = shift + BLOCK_HALF_SIZE;
-        let min = shift - BLOCK_HALF_SIZE;
-        let (uv_min, uv_max) = (self.back.min, self.back.max);
-        build_face_mesh(
-            vec![
-                [min.x, max.y, min.z],
-                [max.x, max.y, min.z],
-                [max.x, min.y, min.z],
-                [min.x, min.y, min.z],
+    fn face_corners(&self, face: Face) -> [Vec3; 4] {
+        let (min, max) = (self.min, self.max);
+        match face {
+            Face::Front => [
+                Vec3::new(min.x, min.y, max.z),
+                Vec3::new(max.x, min.y, max.z),
+                Vec3::new(max.x, max.y, max.z),
+                Vec3::new(min.x, max.y, max.z),
+            ],
+            Face::Back => [
+                Vec3::new(min.x, max.y, min.z),
+                Vec3::new(max.x, max.y, min.z),
+                Vec3::new(max.x, min.y, min.z),
+                Vec3::new(min.x, min.y, min.z),
+            ],
+            Face::Right => [
+                Vec3::new(max.x, min.y, min.z),
+                Vec3::new(max.x, max.y, min.z),
+                Vec3::new(max.x, max.y, max.z),
+                Vec3::new(max.x, min.y, max.z),
             ],
-            normal,
-            vec![
-                [uv_max.x, uv_min.y],
-                [uv_min.x, uv_min.y],
-                [uv_min.x, uv_max.y],
-                [uv_max.x, uv_max.y],
+            Face::Left => [
+                Vec3::new(min.x, min.y, max.z),
+                Vec3::new(min.x, max.y, max.z),
+                Vec3::new(min.x, max.y, min.z),
+                Vec3::new(min.x, min.y, min.z),
             ],
-        )
+            Face::Top => [
+                Vec3::new(max.x, max.y, min.z),
+                Vec3::new(min.x, max.y, min.z),
+                Vec3::new(min.x, max.y, max.z),
+                Vec3::new(max.x, max.y, max.z),
+            ],
+            Face::Bottom => [
+                Vec3::new(max.x, min.y, max.z),
+                Vec3::new(min.x, min.y, max.z),
+                Vec3::new(min.x, min.y, min.z),
+                Vec3::new(max.x, min.y, min.z),
+            ],
+        }
     }
+}
 
-    pub fn build_back_face(&self) -> Mesh {
-        self.build_back_face_shifted(Vec3::ZERO)
+fn face_uv(face: Face, p: Vec3) -> [f32; 2] {
+    match face {
+        Face::Front => [p.x, 1.0 - p.y],
+        Face::Back => [1.0 - p.x, 1.0 - p.y],
+        Face::Right => [1.0 - p.z, 1.0 - p.y],
+        Face::Left => [p.z, 1.0 - p.y],
+        Face::Top => [p.x, p.z],
+        Face::Bottom => [p.z, p.x],
     }
+}
 
-    pub fn build_right_face_shifted(&self, shift: Vec3) -> Mesh {
-        let normal = [1.0, 0.0, 0.0];
-        let max = shift + BLOCK_HALF_SIZE;
-        let min = shift - BLOCK_HALF_SIZE;
-        let (uv_min, uv_max) = (self.right.min, self.right.max);
-        build_face_mesh(
-            vec![
-                [max.x, min.y, min.z],
-                [max.x, max.y, min.z],
-                [max.x, max.y, max.z],
-                [max.x, min.y, max.z],
-            ],
-            normal,
-            vec![
-                [uv_max.x, uv_max.y],
-                [uv_max.x, uv_min.y],
-                [uv_min.x, uv_min.y],
-                [uv_min.x, uv_max.y],
-            ],
-        )
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockShape {
+    Boxes(Vec<BlockBox>),
+    Cross,
+}
+
+impl BlockShape {
+    pub fn from_info(info: &BlockShapeInfo) -> Self {
+        let pixels = |min: [f32; 3], max: [f32; 3]| {
+            BlockBox::new(Vec3::from(min) / 16.0, Vec3::from(max) / 16.0)
+        };
+        match info {
+            BlockShapeInfo::Cube => BlockShape::Boxes(vec![BlockBox::FULL]),
+            BlockShapeInfo::Slab => {
+                BlockShape::Boxes(vec![pixels([0.0, 0.0, 0.0], [16.0, 8.0, 16.0])])
+            }
+            BlockShapeInfo::Stairs { facing } => {
+                let step = match facing {
+                    Facing::North => pixels([0.0, 8.0, 0.0], [16.0, 16.0, 8.0]),
+                    Facing::South => pixels([0.0, 8.0, 8.0], [16.0, 16.0, 16.0]),
+                    Facing::East => pixels([8.0, 8.0, 0.0], [16.0, 16.0, 16.0]),
+                    Facing::West => pixels([0.0, 8.0, 0.0], [8.0, 16.0, 16.0]),
+                };
+                BlockShape::Boxes(vec![pixels([0.0, 0.0, 0.0], [16.0, 8.0, 16.0]), step])
+            }
+            BlockShapeInfo::FencePost => {
+                BlockShape::Boxes(vec![pixels([6.0, 0.0, 6.0], [10.0, 16.0, 10.0])])
+            }
+            BlockShapeInfo::Cross => BlockShape::Cross,
+            BlockShapeInfo::Boxes { boxes } => BlockShape::Boxes(
+                boxes
+                    .iter()
+                    .map(|b| pixels([b[0], b[1], b[2]], [b[3], b[4], b[5]]))
+                    .collect(),
+            ),
+        }
     }
 
-    pub fn build_right_face(&self) -> Mesh {
-        self.build_right_face_shifted(Vec3::ZERO)
+    pub fn boxes(&self) -> &[BlockBox] {
+        match self {
+            BlockShape::Boxes(boxes) => boxes,
+            BlockShape::Cross => &[],
+        }
     }
 
-    pub fn build_left_face_shifted(&self, shift: Vec3) -> Mesh {
-        let normal = [-1.0, 0.0, 0.0];
-        let max = shift + BLOCK_HALF_SIZE;
-        let min = shift - BLOCK_HALF_SIZE;
-        let (uv_min, uv_max) = (self.left.min, self.left.max);
-        build_face_mesh(
-            vec![
-                [min.x, min.y, max.z],
-                [min.x, max.y, max.z],
-                [min.x, max.y, min.z],
-                [min.x, min.y, min.z],
-            ],
-            normal,
-            vec![
-                [uv_max.x, uv_max.y],
-                [uv_max.x, uv_min.y],
-                [uv_min.x, uv_min.y],
-                [uv_min.x, uv_max.y],
-            ],
-        )
+    /// Whether this shape fully hides `rect` on the given face of the unit cube.
+    pub fn covers(&self, face: Face, rect: Rect) -> bool {
+        self.boxes().iter().filter(|b| b.touches(face)).any(|b| {
+            let covered = b.face_rect(face);
+            covered.min.x <= rect.min.x + SHAPE_EPSILON
+                && covered.min.y <= rect.min.y + SHAPE_EPSILON
+                && covered.max.x >= rect.max.x - SHAPE_EPSILON
+                && covered.max.y >= rect.max.y - SHAPE_EPSILON
+        })
     }
+}
+
+/// Renderer-agnostic triangle mesh. `layers` holds the texture array layer of
+/// each vertex in its first component.
+#[derive(Debug, Default)]
+pub struct MeshData {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub layers: Vec<[f32; 2]>,
+    pub colors: Vec<[f32; 4]>,
+    pub indices: Vec<u32>,
+}
+
+/// Vertex color of a fully lit vertex. Channels are sky light, block light and
+/// ambient occlusion, decoded by the block shader.
+pub const FULL_BRIGHT: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
 
-    pub fn build_left_face(&self) -> Mesh {
-        self.build_left_face_shifted(Vec3::ZERO)
+impl MeshData {
+    pub fn push_quad(
+        &mut self,
+        vertices: [Vec3; 4],
+        normal: Vec3,
+        uvs: [[f32; 2]; 4],
+        layer: u32,
+        colors: [[f32; 4]; 4],
+    ) {
+        let start = self.positions.len() as u32;
+        self.positions.extend(vertices.map(|v| v.to_array()));
+        self.normals.extend([normal.to_array(); 4]);
+        self.uvs.extend(uvs);
+        self.layers.extend([[layer as f32, 0.0]; 4]);
+        // Split along the brighter diagonal so occlusion interpolates evenly.
+        let indices = match colors[0][2] + colors[2][2] < colors[1][2] + colors[3][2] {
+            true => [1, 2, 3, 3, 0, 1],
+            false => [0, 1, 2, 2, 3, 0],
+        };
+        self.colors.extend(colors);
+        self.indices.extend(indices.map(|index| start + index));
     }
 
-    pub fn build_top_face_shifted(&self, shift: Vec3) -> Mesh {
-        let normal = [0.0, 1.0, 0.0];
-        let max = shift + BLOCK_HALF_SIZE;
-        let min = shift - BLOCK_HALF_SIZE;
-        let (uv_min, uv_max) = (self.top.min, self.top.max);
-        build_face_mesh(
-            vec![
-                [max.x, max.y, min.z],
-                [min.x, max.y, min.z],
-                [min.x, max.y, max.z],
-                [max.x, max.y, max.z],
-            ],
-            normal,
-            vec![
-                [uv_max.x, uv_min.y],
-                [uv_min.x, uv_min.y],
-                [uv_min.x, uv_max.y],
-                [uv_max.x, uv_max.y],
-            ],
-        )
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
     }
 
-    pub fn build_top_face(&self) -> Mesh {
-        self.build_top_face_shifted(Vec3::ZERO)
+    pub fn merge(&mut self, other: MeshData) {
+        let start = self.positions.len() as u32;
+        self.positions.extend(other.positions);
+        self.normals.extend(other.normals);
+        self.uvs.extend(other.uvs);
+        self.layers.extend(other.layers);
+        self.colors.extend(other.colors);
+        self.indices
+            .extend(other.indices.into_iter().map(|index| start + index));
     }
+}
 
-    pub fn build_bottom_face_shifted(&self, shift: Vec3) -> Mesh {
-        let normal = [0.0, -1.0, 0.0];
-        let max = shift + BLOCK_HALF_SIZE;
-        let min = shift - BLOCK_HALF_SIZE;
-        let (uv_min, uv_max) = (self.bottom.min, self.bottom.max);
-        build_face_mesh(
-            vec![
-                [max.x, min.y, max.z],
-                [min.x, min.y, max.z],
-                [min.x, min.y, min.z],
-                [max.x, min.y, min.z],
-            ],
-            normal,
-            vec![
-                [uv_max.x, uv_max.y],
-                [uv_max.x, uv_min.y],
-                [uv_min.x, uv_min.y],
-                [uv_min.x, uv_max.y],
-            ],
-        )
+#[derive(Debug, Clone)]
+pub struct Block {
+    textures: [u32; 6],
+    shape: BlockShape,
+    transparency: Transparency,
+    light_emission: u8,
+}
+
+fn to_local(shift: Vec3, p: Vec3) -> Vec3 {
+    shift + (p * 2.0 - Vec3::ONE) * BLOCK_HALF_SIZE
+}
+
+impl Block {
+    /// `textures` holds texture array layers in [`Face::ALL`] order.
+    pub fn new(
+        textures: [u32; 6],
+        shape: BlockShape,
+        transparency: Transparency,
+        light_emission: u8,
+    ) -> Self {
+        Self {
+            textures,
+            shape,
+            transparency,
+            light_emission,
+        }
     }
 
-    pub fn build_bottom_face(&self) -> Mesh {
-        self.build_bottom_face_shifted(Vec3::ZERO)
+    /// Texture array layer used for the given face.
+    pub fn texture(&self, face: Face) -> u32 {
+        self.textures[face as usize]
     }
-}
 
-impl MeshBuilder for Block {
-    fn build(&self) -> Mesh {
-        let mut mesh = self.build_front_face();
-        mesh.merge(&self.build_back_face());
-        mesh.merge(&self.build_right_face());
-        mesh.merge(&self.build_left_face());
-        mesh.merge(&self.build_top_face());
-        mesh.merge(&self.build_bottom_face());
-        mesh
+    pub fn shape(&self) -> &BlockShape {
+        &self.shape
     }
-}
 
-impl Meshable for Block {
-    type Output = Block;
+    pub fn transparency(&self) -> Transparency {
+        self.transparency
+    }
+
+    pub fn collision_boxes(&self) -> &[BlockBox] {
+        self.shape.boxes()
+    }
+
+    pub fn light_emission(&self) -> u8 {
+        self.light_emission
+    }
 
-    fn mesh(&self) -> Self::Output {
-        Block { ..*self }
+    /// Whether the block stops light from passing through its cell.
+    pub fn is_opaque(&self) -> bool {
+        self.transparency == Transparency::Opaque && self.shape.boxes() == [BlockBox::FULL]
+    }
+
+    fn build_box_face(
+        &self,
+        mesh: &mut MeshData,
+        face: Face,
+        block_box: &BlockBox,
+        shift: Vec3,
+        colors: [[f32; 4]; 4],
+    ) {
+        let corners = block_box.face_corners(face);
+        mesh.push_quad(
+            corners.map(|p| to_local(shift, p)),
+            face.normal().as_vec3(),
+            corners.map(|p| face_uv(face, p)),
+            self.texture(face),
+            colors,
+        );
+    }
+
+    fn build_cross(&self, mesh: &mut MeshData, shift: Vec3, colors: [[f32; 4]; 4]) {
+        let uvs = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+        let reversed = [uvs[1], uvs[0], uvs[3], uvs[2]];
+        for (a, b) in [
+            (Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 1.0)),
+            (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)),
+        ] {
+            let corners = [a, b, b + Vec3::Y, a + Vec3::Y].map(|p| to_local(shift, p));
+            let normal = (b - a).cross(Vec3::Y).normalize();
+            let texture = self.texture(Face::Front);
+            mesh.push_quad(corners, normal, uvs, texture, colors);
+            mesh.push_quad(
+                [corners[1], corners[0], corners[3], corners[2]],
+                -normal,
+                reversed,
+                texture,
+                [colors[1], colors[0], colors[3], colors[2]],
+            );
+        }
+    }
+
+    /// Appends the faces of this block centered at `shift`. Faces lying on the
+    /// block boundary are skipped when `is_covered` reports them hidden by the
+    /// neighbor on that side. `shade` gives vertex colors for a quad from its
+    /// block-local corners and the boundary face it lies on, `None` for quads
+    /// inside the block's own cell.
+    pub fn build_shifted(
+        &self,
+        mesh: &mut MeshData,
+        shift: Vec3,
+        is_covered: impl Fn(Face, Rect) -> bool,
+        shade: impl Fn(Option<Face>, [Vec3; 4]) -> [[f32; 4]; 4],
+    ) {
+        match &self.shape {
+            BlockShape::Cross => {
+                let corners = [Vec3::ZERO, Vec3::X, Vec3::X + Vec3::Y, Vec3::Y];
+                self.build_cross(mesh, shift, shade(None, corners));
+            }
+            BlockShape::Boxes(boxes) => {
+                for block_box in boxes {
+                    for face in Face::ALL {
+                        let on_boundary = block_box.touches(face);
+                        if on_boundary && is_covered(face, block_box.face_rect(face)) {
+                            continue;
+                        }
+                        let colors =
+                            shade(on_boundary.then_some(face), block_box.face_corners(face));
+                        self.build_box_face(mesh, face, block_box, shift, colors);
+                    }
+                }
+            }
+        }
     }
 }
@@ -1,4 +1,59 @@
+use anyhow::anyhow;
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext, RenderAssetUsages},
+    image::{CompressedImageFormats, ImageSampler, ImageType},
+    prelude::*,
+    utils::hashbrown::HashMap,
+};
 use serde::Deserialize;
+use std::{fs, path::Path};
+
+use crate::block::{Block, BlockShape};
+use crate::light::MAX_LIGHT;
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Facing {
+    #[default]
+    North,
+    South,
+    East,
+    West,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Transparency {
+    #[default]
+    Opaque,
+    Cutout,
+    Translucent,
+}
+
+impl Transparency {
+    pub const ALL: [Transparency; 3] = [
+        Transparency::Opaque,
+        Transparency::Cutout,
+        Transparency::Translucent,
+    ];
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BlockShapeInfo {
+    #[default]
+    Cube,
+    Slab,
+    Stairs {
+        #[serde(default)]
+        facing: Facing,
+    },
+    FencePost,
+    Cross,
+    Boxes {
+        boxes: Vec<[f32; 6]>,
+    },
+}
 
 #[derive(Deserialize, Debug)]
 pub struct BlockInfo {
@@ -9,9 +64,139 @@ pub struct BlockInfo {
     pub right: String,
     pub top: String,
     pub bottom: String,
+    #[serde(default)]
+    pub shape: BlockShapeInfo,
+    #[serde(default)]
+    pub transparency: Transparency,
+    #[serde(default)]
+    pub light_emission: u8,
 }
 
-#[derive(Deserialize, Debug)]
+impl BlockInfo {
+    /// Texture file names in front, back, left, right, top, bottom order.
+    pub fn faces(&self) -> [&str; 6] {
+        [
+            &self.front,
+            &self.back,
+            &self.left,
+            &self.right,
+            &self.top,
+            &self.bottom,
+        ]
+    }
+}
+
+#[derive(Asset, TypePath, Deserialize, Debug)]
 pub struct BlockInfoRegistry {
     pub blocks: Vec<BlockInfo>,
 }
+
+impl BlockInfoRegistry {
+    /// Reads a registry file without going through the asset server.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn block_names(&self) -> Vec<String> {
+        self.blocks.iter().map(|info| info.name.clone()).collect()
+    }
+
+    /// Reads every texture the registry uses from `textures_dir` and returns
+    /// its average color by file name. Transparent pixels are left out.
+    pub fn texture_colors(&self, textures_dir: &Path) -> anyhow::Result<HashMap<String, Srgba>> {
+        let mut colors = HashMap::new();
+        for name in self.blocks.iter().flat_map(BlockInfo::faces) {
+            if colors.contains_key(name) {
+                continue;
+            }
+            let path = textures_dir.join(name);
+            let pixels = load_texture(&path)?
+                .data
+                .ok_or(anyhow!("Texture {} has no pixel data", path.display()))?;
+            let (sum, count) = pixels.chunks_exact(4).filter(|pixel| pixel[3] >= 128).fold(
+                (Vec3::ZERO, 0),
+                |(sum, count), pixel| {
+                    let color = LinearRgba::from(Srgba::rgb_u8(pixel[0], pixel[1], pixel[2]));
+                    (sum + color.to_vec3(), count + 1)
+                },
+            );
+            let average = sum / count.max(1) as f32;
+            colors.insert(
+                name.to_string(),
+                LinearRgba::rgb(average.x, average.y, average.z).into(),
+            );
+        }
+        Ok(colors)
+    }
+
+    /// Builds runtime blocks, looking up the texture array layer of each face
+    /// by file name with `texture`.
+    pub fn build_blocks(
+        &self,
+        texture: impl Fn(&str) -> anyhow::Result<u32>,
+    ) -> anyhow::Result<Vec<Block>> {
+        self.blocks
+            .iter()
+            .map(|info| {
+                anyhow::Ok(Block::new(
+                    [
+                        texture(&info.front)?,
+                        texture(&info.back)?,
+                        texture(&info.right)?,
+                        texture(&info.left)?,
+                        texture(&info.top)?,
+                        texture(&info.bottom)?,
+                    ],
+                    BlockShape::from_info(&info.shape),
+                    info.transparency,
+                    info.light_emission.min(MAX_LIGHT),
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Reads a PNG texture without going through the asset server, converted to
+/// RGBA.
+pub fn load_texture(path: &Path) -> anyhow::Result<Image> {
+    let image = Image::from_buffer(
+        &fs::read(path)?,
+        ImageType::Extension("png"),
+        CompressedImageFormats::NONE,
+        true,
+        ImageSampler::Default,
+        RenderAssetUsages::default(),
+    )?;
+    // Converted through the `image` crate, as `Image::convert` needs the
+    // renderer's texture formats.
+    let rgba = image.try_into_dynamic()?.to_rgba8();
+    Ok(Image::from_dynamic(
+        rgba.into(),
+        true,
+        RenderAssetUsages::default(),
+    ))
+}
+
+#[derive(Default)]
+pub struct BlockInfoRegistryLoader;
+
+impl AssetLoader for BlockInfoRegistryLoader {
+    type Asset = BlockInfoRegistry;
+    type Settings = ();
+    type Error = anyhow::Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["json"]
+    }
+}
@@ -1,118 +1,278 @@
-use bevy::{prelude::*, utils::hashbrown::HashMap};
-use noise::NoiseFn;
+use bevy::{
+    prelude::*,
+    utils::hashbrown::{HashMap, HashSet},
+};
 use std::sync::Arc;
 
-use crate::block::{Block, BLOCK_HALF_SIZE};
-use crate::player::Player;
-use crate::{GameResources, GameState};
+use crate::block::{Block, Face, MeshData, BLOCK_HALF_SIZE};
+use crate::block_registry::Transparency;
+use crate::connectivity::FaceConnectivity;
+use crate::light::{light_chunk, relight_block, relight_blocks, LightChannel, MAX_LIGHT};
 
-const CHUNK_SIZE: u32 = 16;
-const CHUNK_LEN: u32 = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+pub const CHUNK_SIZE: u32 = 16;
+pub const CHUNK_LEN: u32 = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
 const CHUNK_OFFSET: f32 = -(CHUNK_SIZE as f32 / 2.0) + BLOCK_HALF_SIZE;
 
-const SEED: u32 = 123456;
+/// Brightness of a face corner by the number of its occluding neighbors.
+const AO_LEVELS: [f32; 4] = [0.45, 0.65, 0.82, 1.0];
 
-const DRAW_DISTANCE: u32 = 3;
-
-pub struct ChunksPlugin;
+/// Loaded chunks keyed by chunk position, which is the grid position of the
+/// chunk's first block and thus a multiple of [`CHUNK_SIZE`].
+#[derive(Debug, Resource)]
+pub struct ChunkMap {
+    chunks: HashMap<IVec3, Chunk>,
+    block_names: Arc<Vec<String>>,
+    /// Chunks whose blocks or light changed since they were last taken.
+    changed: HashSet<IVec3>,
+}
 
-impl Plugin for ChunksPlugin {
-    fn build(&self, app: &mut App) {
-        app.init_resource::<ChunkMap>()
-            .add_systems(Update, update_chunks.run_if(in_state(GameState::InGame)));
+impl Default for ChunkMap {
+    fn default() -> Self {
+        ChunkMap {
+            chunks: HashMap::new(),
+            block_names: Arc::new(Vec::new()),
+            changed: HashSet::new(),
+        }
     }
 }
 
-fn player_pos_to_chunk_block(pos: Vec3) -> (IVec3, UVec3) {
-    let size = Vec3::ONE * CHUNK_SIZE as f32;
-    let pos = pos + size / 2.0;
-    let chunk_pos = pos.div_euclid(size);
-    let block_pos = pos.rem_euclid(size);
-    (
-        IVec3::new(chunk_pos.x as i32, chunk_pos.y as i32, chunk_pos.z as i32),
-        UVec3::new(block_pos.x as u32, block_pos.y as u32, block_pos.z as u32),
-    )
-}
+impl ChunkMap {
+    pub fn chunks(&self) -> impl Iterator<Item = (&IVec3, &Chunk)> {
+        self.chunks.iter()
+    }
 
-fn update_chunks(
-    mut chunks_map: ResMut<ChunkMap>,
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    game_resources: Res<GameResources>,
-    player_q: Query<&Transform, With<Player>>,
-) {
-    let Ok(transform) = player_q.get_single() else {
-        return;
-    };
-    let (chunk_pos, _) = player_pos_to_chunk_block(transform.translation);
-    let radius = DRAW_DISTANCE as i32;
-    for x in -radius..=radius {
-        for z in -radius..=radius {
-            let chunk_pos = (chunk_pos + x * IVec3::X + z * IVec3::Z) * CHUNK_SIZE as i32;
-            if !chunks_map.chunks.contains_key(&chunk_pos) {
-                let chunk_pos_f32 =
-                    Vec3::new(chunk_pos.x as f32, chunk_pos.y as f32, chunk_pos.z as f32);
-                let chunk = generate_chunk(
-                    chunk_pos_f32,
-                    game_resources.blocks_map.clone(),
-                    game_resources.blocks.clone(),
-                );
-                if !chunk.is_empty() {
-                    info!("a: {chunk_pos_f32}");
-                    commands.spawn((
-                        Mesh3d(meshes.add(chunk.clone())),
-                        MeshMaterial3d(game_resources.material.clone()),
-                        Transform::from_translation(chunk_pos_f32),
-                    ));
-                }
-                chunks_map.chunks.insert(chunk_pos, chunk);
+    pub fn chunks_mut(&mut self) -> impl Iterator<Item = (&IVec3, &mut Chunk)> {
+        self.chunks.iter_mut()
+    }
+
+    pub fn chunk(&self, chunk_pos: IVec3) -> Option<&Chunk> {
+        self.chunks.get(&chunk_pos)
+    }
+
+    pub fn contains_chunk(&self, chunk_pos: IVec3) -> bool {
+        self.chunks.contains_key(&chunk_pos)
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Takes the positions of chunks changed since the last call.
+    pub fn take_changed(&mut self) -> HashSet<IVec3> {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// Marks every loaded chunk as changed, e.g. to rebuild all meshes.
+    pub fn mark_all_changed(&mut self) {
+        self.changed.extend(self.chunks.keys().copied());
+    }
+
+    /// Position of the chunk containing the block at `pos`.
+    pub fn chunk_pos(pos: IVec3) -> IVec3 {
+        let size = IVec3::splat(CHUNK_SIZE as i32);
+        pos.div_euclid(size) * size
+    }
+
+    fn local_pos(pos: IVec3) -> UVec3 {
+        pos.rem_euclid(IVec3::splat(CHUNK_SIZE as i32)).as_uvec3()
+    }
+
+    pub fn block_at(&self, pos: IVec3) -> Option<usize> {
+        self.chunks
+            .get(&Self::chunk_pos(pos))?
+            .at(Self::local_pos(pos))
+    }
+
+    pub fn block_info_at(&self, pos: IVec3) -> Option<(usize, &Block)> {
+        let chunk = self.chunks.get(&Self::chunk_pos(pos))?;
+        let id = chunk.at(Self::local_pos(pos))?;
+        chunk.blocks_info.get(id).map(|block| (id, block))
+    }
+
+    /// Light level at `pos`, `None` if its chunk isn't loaded.
+    pub fn light_at(&self, pos: IVec3, channel: LightChannel) -> Option<u8> {
+        self.chunks
+            .get(&Self::chunk_pos(pos))
+            .map(|chunk| chunk.light_at(Self::local_pos(pos), channel))
+    }
+
+    pub fn set_light_at(&mut self, pos: IVec3, channel: LightChannel, level: u8) {
+        if let Some(chunk) = self.chunks.get_mut(&Self::chunk_pos(pos)) {
+            chunk.set_light_at(Self::local_pos(pos), channel, level);
+        }
+    }
+
+    /// Whether light passes through the cell at `pos`, `None` if its chunk
+    /// isn't loaded.
+    pub fn transmits_light(&self, pos: IVec3) -> Option<bool> {
+        let chunk = self.chunks.get(&Self::chunk_pos(pos))?;
+        Some(
+            chunk
+                .at(Self::local_pos(pos))
+                .and_then(|id| chunk.blocks_info.get(id))
+                .is_none_or(|block| !block.is_opaque()),
+        )
+    }
+
+    pub fn light_emission_at(&self, pos: IVec3) -> u8 {
+        self.block_info_at(pos)
+            .map_or(0, |(_, block)| block.light_emission())
+    }
+
+    /// Adds a chunk, lighting it and marking it and its neighbors changed.
+    pub fn insert_chunk(&mut self, chunk_pos: IVec3, chunk: Chunk) {
+        self.chunks.insert(chunk_pos, chunk);
+        let changed = light_chunk(self, chunk_pos);
+        self.changed.extend(changed);
+        // Faces along the shared border may now be hidden.
+        for face in Face::ALL {
+            let neighbor = chunk_pos + face.normal() * CHUNK_SIZE as i32;
+            if self.chunks.contains_key(&neighbor) {
+                self.changed.insert(neighbor);
             }
         }
     }
-}
 
-#[derive(Debug, Resource)]
-struct ChunkMap {
-    chunks: HashMap<IVec3, Chunk>,
-}
+    /// Removes a chunk, e.g. once no player is near it anymore.
+    pub fn remove_chunk(&mut self, chunk_pos: IVec3) -> Option<Chunk> {
+        self.changed.remove(&chunk_pos);
+        self.chunks.remove(&chunk_pos)
+    }
 
-impl Default for ChunkMap {
-    fn default() -> Self {
-        ChunkMap {
-            chunks: HashMap::new(),
+    /// Replaces the block at `pos`, updating light and marking affected
+    /// chunks changed. Returns `false` if the chunk isn't loaded.
+    pub fn set_block(&mut self, pos: IVec3, block: Option<usize>) -> bool {
+        let Some(chunk) = self.chunks.get_mut(&Self::chunk_pos(pos)) else {
+            return false;
+        };
+        chunk.set_at(Self::local_pos(pos), block);
+        let changed = relight_block(self, pos);
+        self.changed.extend(changed);
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let neighbor = Self::chunk_pos(pos + IVec3::new(x, y, z));
+                    if self.chunks.contains_key(&neighbor) {
+                        self.changed.insert(neighbor);
+                    }
+                }
+            }
         }
+        true
+    }
+
+    /// Replaces many blocks at once, relighting and marking affected chunks
+    /// changed in one pass so each chunk is only rebuilt once. Blocks in
+    /// unloaded chunks or already set are skipped, the applied changes are
+    /// returned.
+    pub fn set_blocks(
+        &mut self,
+        edits: impl IntoIterator<Item = (IVec3, Option<usize>)>,
+    ) -> Vec<BlockChange> {
+        let mut changes = Vec::new();
+        for (pos, block) in edits {
+            let Some(chunk) = self.chunks.get_mut(&Self::chunk_pos(pos)) else {
+                continue;
+            };
+            let old = chunk.at(Self::local_pos(pos));
+            if old == block {
+                continue;
+            }
+            chunk.set_at(Self::local_pos(pos), block);
+            changes.push(BlockChange {
+                pos,
+                old,
+                new: block,
+            });
+        }
+        let positions = changes.iter().map(|change| change.pos).collect::<Vec<_>>();
+        let changed = relight_blocks(self, &positions);
+        self.changed.extend(changed);
+        let last = CHUNK_SIZE - 1;
+        for pos in positions {
+            let local = Self::local_pos(pos);
+            // Blocks on a chunk border affect the meshes of its neighbors.
+            if local.cmpgt(UVec3::ZERO).all() && local.cmplt(UVec3::splat(last)).all() {
+                self.changed.insert(Self::chunk_pos(pos));
+                continue;
+            }
+            for x in -1..=1 {
+                for y in -1..=1 {
+                    for z in -1..=1 {
+                        let neighbor = Self::chunk_pos(pos + IVec3::new(x, y, z));
+                        if self.chunks.contains_key(&neighbor) {
+                            self.changed.insert(neighbor);
+                        }
+                    }
+                }
+            }
+        }
+        changes
     }
-}
 
-impl ChunkMap {}
-
-fn generate_chunk(
-    pos: Vec3,
-    block_map: Arc<HashMap<String, usize>>,
-    blocks: Arc<Vec<Block>>,
-) -> Chunk {
-    info!("generate_chunk_pos: {pos}");
-    let noise = noise::Perlin::new(SEED);
-    let scale = 0.015;
-    let mut chunk = Chunk::new(blocks);
-    for x in 0..16 {
-        let n_x = x as f64 + pos.x as f64;
-        for z in 0..16 {
-            let n_z = z as f64 + pos.z as f64;
-            let n_y = (noise.get([n_x * scale, n_z * scale]) * 64.0).round() as i32;
-            for y in 0..16 {
-                let d = n_y - (y as i32 + pos.y.round() as i32);
-                let block_id = match d {
-                    0 => Some(block_map["grass"]),
-                    0..3 => Some(block_map["dirt"]),
-                    0.. => Some(block_map["stone"]),
-                    _ => None,
-                };
-                chunk.set_at(UVec3::new(x, y, z), block_id);
+    /// Switches loaded chunks to a new block registry. Registry indices are
+    /// carried over by name, blocks missing from the registry become
+    /// `unknown`, and all light is recomputed. The first registry set has no
+    /// names to carry over, so chunks inserted before it are taken to already
+    /// use its indices.
+    pub fn set_registry(
+        &mut self,
+        block_names: Arc<Vec<String>>,
+        blocks: Arc<Vec<Block>>,
+        unknown: &str,
+    ) {
+        if self.block_names.is_empty() {
+            for chunk in self.chunks.values_mut() {
+                chunk.blocks_info = blocks.clone();
             }
+            self.block_names = block_names;
+            return;
+        }
+        let remap = {
+            let new_ids = block_names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| (name.as_str(), i))
+                .collect::<HashMap<_, _>>();
+            let unknown = new_ids.get(unknown).copied();
+            self.block_names
+                .iter()
+                .map(|name| new_ids.get(name.as_str()).copied().or(unknown))
+                .collect::<Vec<_>>()
+        };
+        for chunk in self.chunks.values_mut() {
+            chunk.remap(&remap);
+            chunk.blocks_info = blocks.clone();
+            chunk.light.fill(0);
+        }
+        self.block_names = block_names;
+        // Light emission and opacity may have changed as well.
+        let chunk_positions = self.chunks.keys().copied().collect::<Vec<_>>();
+        for chunk_pos in chunk_positions {
+            light_chunk(self, chunk_pos);
         }
+        self.mark_all_changed();
     }
-    chunk
+}
+
+/// A block replaced by [`ChunkMap::set_blocks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockChange {
+    pub pos: IVec3,
+    pub old: Option<usize>,
+    pub new: Option<usize>,
+}
+
+pub fn world_to_block(pos: Vec3) -> IVec3 {
+    (pos + CHUNK_SIZE as f32 / 2.0).floor().as_ivec3()
+}
+
+pub fn block_min_corner(pos: IVec3) -> Vec3 {
+    pos.as_vec3() - CHUNK_SIZE as f32 / 2.0
 }
 
 fn index_to_pos(i: usize) -> UVec3 {
@@ -128,84 +288,183 @@ fn index_to_pos(i: usize) -> UVec3 {
 pub struct Chunk {
     blocks: [Option<usize>; CHUNK_LEN as usize],
     blocks_info: Arc<Vec<Block>>,
+    /// Sky light in the high and block light in the low nibble of each cell.
+    light: [u8; CHUNK_LEN as usize],
+    dirty: bool,
+}
+
+fn pos_to_index(pos: UVec3) -> usize {
+    (pos.x * CHUNK_SIZE * CHUNK_SIZE + pos.y * CHUNK_SIZE + pos.z) as usize
+}
+
+fn in_chunk(pos: IVec3) -> bool {
+    pos.cmpge(IVec3::ZERO).all() && pos.cmplt(IVec3::splat(CHUNK_SIZE as i32)).all()
 }
 
 impl Chunk {
-    fn new(blocks_info: Arc<Vec<Block>>) -> Self {
+    pub fn new(blocks_info: Arc<Vec<Block>>) -> Self {
         Self {
             blocks: [None; CHUNK_LEN as usize],
             blocks_info,
+            light: [0; CHUNK_LEN as usize],
+            dirty: false,
+        }
+    }
+
+    pub fn from_blocks(blocks: &[Option<usize>], blocks_info: Arc<Vec<Block>>) -> Self {
+        let mut chunk = Self::new(blocks_info);
+        chunk.blocks.copy_from_slice(blocks);
+        chunk
+    }
+
+    pub fn blocks(&self) -> &[Option<usize>] {
+        &self.blocks
+    }
+
+    /// Whether the chunk changed since it was last saved.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Returns whether the chunk changed since it was last saved and resets the flag.
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
+    fn remap(&mut self, remap: &[Option<usize>]) {
+        for block in self.blocks.iter_mut() {
+            *block = block.and_then(|id| remap.get(id).copied().flatten());
         }
     }
 
     pub fn at(&self, pos: UVec3) -> Option<usize> {
-        let i = pos.x * CHUNK_SIZE * CHUNK_SIZE + pos.y * CHUNK_SIZE + pos.z;
-        self.blocks[i as usize]
+        self.blocks[pos_to_index(pos)]
     }
 
     pub fn set_at(&mut self, pos: UVec3, block: Option<usize>) {
-        let i = pos.x * CHUNK_SIZE * CHUNK_SIZE + pos.y * CHUNK_SIZE + pos.z;
-        self.blocks[i as usize] = block;
-    }
-
-    fn check_at(&self, pos: IVec3) -> bool {
-        if pos.x < 0
-            || pos.y < 0
-            || pos.z < 0
-            || pos.x >= CHUNK_SIZE as i32
-            || pos.y >= CHUNK_SIZE as i32
-            || pos.z >= CHUNK_SIZE as i32
-        {
-            return false;
+        self.blocks[pos_to_index(pos)] = block;
+        self.dirty = true;
+    }
+
+    fn light_at(&self, pos: UVec3, channel: LightChannel) -> u8 {
+        let light = self.light[pos_to_index(pos)];
+        match channel {
+            LightChannel::Sky => light >> 4,
+            LightChannel::Block => light & 0x0f,
         }
-        self.at(UVec3::new(pos.x as u32, pos.y as u32, pos.z as u32))
-            .is_some()
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.blocks.iter().flatten().count() == 0
+    fn set_light_at(&mut self, pos: UVec3, channel: LightChannel, level: u8) {
+        let light = &mut self.light[pos_to_index(pos)];
+        *light = match channel {
+            LightChannel::Sky => (*light & 0x0f) | (level << 4),
+            LightChannel::Block => (*light & 0xf0) | (level & 0x0f),
+        };
+    }
+
+    fn block_info_at(&self, pos: IVec3) -> Option<(usize, &Block)> {
+        if !in_chunk(pos) {
+            return None;
+        }
+        self.at(pos.as_uvec3())
+            .and_then(|id| self.blocks_info.get(id).map(|block| (id, block)))
     }
-}
 
-impl MeshBuilder for Chunk {
-    fn build(&self) -> Mesh {
-        let mut faces = self
-            .blocks
-            .iter()
-            .enumerate()
-            .filter_map(|(i, block_id)| block_id.map(|id| (i, &self.blocks_info[id])))
-            .flat_map(|(i, block)| {
-                let pos = index_to_pos(i);
-                let pos = IVec3::new(pos.x as i32, pos.y as i32, pos.z as i32);
-                let shift =
-                    Vec3::new(pos.x as f32, pos.y as f32, pos.z as f32) + Vec3::ONE * CHUNK_OFFSET;
-                [
-                    (!self.check_at(pos + IVec3::Z))
-                        .then_some(block.build_front_face_shifted(shift)),
-                    (!self.check_at(pos - IVec3::Z))
-                        .then_some(block.build_back_face_shifted(shift)),
-                    (!self.check_at(pos + IVec3::X))
-                        .then_some(block.build_right_face_shifted(shift)),
-                    (!self.check_at(pos - IVec3::X))
-                        .then_some(block.build_left_face_shifted(shift)),
-                    (!self.check_at(pos + IVec3::Y)).then_some(block.build_top_face_shifted(shift)),
-                    (!self.check_at(pos - IVec3::Y))
-                        .then_some(block.build_bottom_face_shifted(shift)),
-                ]
-            })
-            .flatten();
-        let mesh = faces.next().unwrap();
-        faces.fold(mesh, |mut mesh, face| {
-            mesh.merge(&face);
-            mesh
+    /// Which faces of the chunk can see each other through non-opaque cells.
+    pub fn connectivity(&self) -> FaceConnectivity {
+        FaceConnectivity::compute(|pos| {
+            self.block_info_at(pos)
+                .is_some_and(|(_, block)| block.is_opaque())
         })
     }
-}
 
-impl Meshable for Chunk {
-    type Output = Chunk;
+    pub fn is_empty(&self) -> bool {
+        self.blocks.iter().flatten().count() == 0
+    }
+}
 
-    fn mesh(&self) -> Self::Output {
-        self.clone()
+impl Chunk {
+    /// Builds mesh data for every transparency layer. With `neighbors` given,
+    /// faces, light and occlusion along the chunk border take the adjacent
+    /// loaded chunks into account.
+    pub fn build_mesh_data(
+        &self,
+        neighbors: Option<(&ChunkMap, IVec3)>,
+        ambient_occlusion: bool,
+    ) -> [MeshData; 3] {
+        let neighbor_block = |pos: IVec3| match in_chunk(pos) {
+            true => self.block_info_at(pos),
+            false => neighbors
+                .and_then(|(chunk_map, chunk_pos)| chunk_map.block_info_at(chunk_pos + pos)),
+        };
+        let light = |pos: IVec3, channel: LightChannel| match in_chunk(pos) {
+            true => self.light_at(pos.as_uvec3(), channel),
+            false => neighbors
+                .and_then(|(chunk_map, chunk_pos)| chunk_map.light_at(chunk_pos + pos, channel))
+                .unwrap_or(match channel {
+                    LightChannel::Sky => MAX_LIGHT,
+                    LightChannel::Block => 0,
+                }),
+        };
+        let occludes = |pos: IVec3| neighbor_block(pos).is_some_and(|(_, block)| block.is_opaque());
+        // Classic voxel AO from the two side neighbors and the diagonal one of
+        // each corner, sampled in the layer of cells the face looks into.
+        let corner_ao = |face: Face, cell: IVec3, corner: Vec3| {
+            let normal = face.normal();
+            let mut sides = [IVec3::ZERO; 2];
+            let tangents = (0..3).filter(|&axis| normal[axis] == 0);
+            for (side, axis) in sides.iter_mut().zip(tangents) {
+                side[axis] = if corner[axis] > 0.5 { 1 } else { -1 };
+            }
+            let side1 = occludes(cell + sides[0]);
+            let side2 = occludes(cell + sides[1]);
+            let level = match side1 && side2 {
+                true => 0,
+                false => {
+                    3 - side1 as usize
+                        - side2 as usize
+                        - occludes(cell + sides[0] + sides[1]) as usize
+                }
+            };
+            AO_LEVELS[level]
+        };
+        let mut layers: [MeshData; 3] = default();
+        for (i, id, block) in self.blocks.iter().enumerate().filter_map(|(i, block_id)| {
+            block_id.and_then(|id| self.blocks_info.get(id).map(|block| (i, id, block)))
+        }) {
+            let pos = index_to_pos(i);
+            let pos = IVec3::new(pos.x as i32, pos.y as i32, pos.z as i32);
+            let shift =
+                Vec3::new(pos.x as f32, pos.y as f32, pos.z as f32) + Vec3::ONE * CHUNK_OFFSET;
+            let mesh = &mut layers[block.transparency() as usize];
+            block.build_shifted(
+                mesh,
+                shift,
+                |face: Face, rect| {
+                    neighbor_block(pos + face.normal()).is_some_and(|(neighbor_id, neighbor)| {
+                        let hides = match neighbor.transparency() {
+                            Transparency::Opaque => true,
+                            Transparency::Cutout => false,
+                            Transparency::Translucent => neighbor_id == id,
+                        };
+                        hides && neighbor.shape().covers(face.opposite(), rect)
+                    })
+                },
+                |face: Option<Face>, corners| {
+                    let cell = face.map_or(pos, |face| pos + face.normal());
+                    let max = MAX_LIGHT as f32;
+                    let sky = light(cell, LightChannel::Sky) as f32 / max;
+                    let block_light = light(cell, LightChannel::Block) as f32 / max;
+                    corners.map(|corner| {
+                        let ao = match (ambient_occlusion, face) {
+                            (true, Some(face)) => corner_ao(face, cell, corner),
+                            _ => 1.0,
+                        };
+                        [sky, block_light, ao, 1.0]
+                    })
+                },
+            );
+        }
+        layers
     }
 }
@@ -0,0 +1,153 @@
+use bevy::prelude::*;
+use nipahblocks::chunk::CHUNK_SIZE;
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    time::{Duration, Instant},
+};
+
+/// Extra cost, in chunks of distance, of work directly behind the camera.
+const BEHIND_PENALTY: f32 = 2.0;
+
+struct QueuedChunk<T> {
+    cost: f32,
+    chunk_pos: IVec3,
+    item: T,
+}
+
+impl<T> PartialEq for QueuedChunk<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl<T> Eq for QueuedChunk<T> {}
+
+impl<T> PartialOrd for QueuedChunk<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for QueuedChunk<T> {
+    // Reversed so the heap pops the cheapest chunk first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// Chunk work ordered by distance from the viewer, favoring chunks in front of
+/// the camera over those behind it.
+pub struct ChunkQueue<T> {
+    origin: Vec3,
+    forward: Vec3,
+    heap: BinaryHeap<QueuedChunk<T>>,
+}
+
+impl<T> ChunkQueue<T> {
+    pub fn new(origin: Vec3, forward: Vec3) -> Self {
+        Self {
+            origin,
+            forward: forward.normalize_or_zero(),
+            heap: BinaryHeap::new(),
+        }
+    }
+
+    fn cost(&self, chunk_pos: IVec3) -> f32 {
+        let offset = (chunk_pos.as_vec3() - self.origin) / CHUNK_SIZE as f32;
+        let distance = offset.length();
+        let facing = offset.normalize_or_zero().dot(self.forward);
+        distance + (1.0 - facing) / 2.0 * BEHIND_PENALTY
+    }
+
+    pub fn push(&mut self, chunk_pos: IVec3, item: T) {
+        self.heap.push(QueuedChunk {
+            cost: self.cost(chunk_pos),
+            chunk_pos,
+            item,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Pops items in priority order until `budget` runs out, always yielding at
+    /// least one so the queue keeps draining on slow frames.
+    pub fn drain_within(&mut self, budget: Duration) -> impl Iterator<Item = (IVec3, T)> + '_ {
+        let start = Instant::now();
+        let mut first = true;
+        std::iter::from_fn(move || {
+            if !first && start.elapsed() >= budget {
+                return None;
+            }
+            first = false;
+            self.heap
+                .pop()
+                .map(|queued| (queued.chunk_pos, queued.item))
+        })
+    }
+}
+
+impl<T> Extend<(IVec3, T)> for ChunkQueue<T> {
+    fn extend<I: IntoIterator<Item = (IVec3, T)>>(&mut self, iter: I) {
+        for (chunk_pos, item) in iter {
+            self.push(chunk_pos, item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: i32 = CHUNK_SIZE as i32;
+
+    fn drain_all(queue: &mut ChunkQueue<usize>) -> Vec<usize> {
+        queue
+            .drain_within(Duration::MAX)
+            .map(|(_, item)| item)
+            .collect()
+    }
+
+    #[test]
+    fn nearer_chunks_come_first() {
+        let mut queue = ChunkQueue::new(Vec3::ZERO, Vec3::X);
+        queue.extend([
+            (IVec3::X * SIZE * 3, 3),
+            (IVec3::X * SIZE, 1),
+            (IVec3::X * SIZE * 2, 2),
+        ]);
+        assert_eq!(drain_all(&mut queue), [1, 2, 3]);
+    }
+
+    #[test]
+    fn chunks_behind_the_camera_wait() {
+        let mut queue = ChunkQueue::new(Vec3::ZERO, Vec3::X);
+        queue.extend([
+            // Distance 1, plus the full penalty.
+            (IVec3::NEG_X * SIZE, 0),
+            // Distance 2 straight ahead.
+            (IVec3::X * SIZE * 2, 1),
+            // Distance 3 to the side, plus half the penalty.
+            (IVec3::Z * SIZE * 3, 2),
+        ]);
+        assert_eq!(drain_all(&mut queue), [1, 0, 2]);
+
+        // Without a view direction only distance counts.
+        let mut queue = ChunkQueue::new(Vec3::ZERO, Vec3::ZERO);
+        queue.extend([(IVec3::X * SIZE * 2, 1), (IVec3::NEG_X * SIZE, 0)]);
+        assert_eq!(drain_all(&mut queue), [0, 1]);
+    }
+
+    #[test]
+    fn spent_budget_still_yields_one_item() {
+        let mut queue = ChunkQueue::new(Vec3::ZERO, Vec3::X);
+        queue.extend([(IVec3::X * SIZE * 2, 1), (IVec3::X * SIZE, 0)]);
+        let drained = queue.drain_within(Duration::ZERO).collect::<Vec<_>>();
+        assert_eq!(drained, [(IVec3::X * SIZE, 0)]);
+        assert_eq!(queue.len(), 1);
+        assert_eq!(drain_all(&mut queue), [1]);
+        assert_eq!(queue.drain_within(Duration::ZERO).count(), 0);
+    }
+}
@@ -0,0 +1,358 @@
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    prelude::*,
+    utils::hashbrown::HashMap,
+};
+use nipahblocks::block_registry::Transparency;
+use nipahblocks::chunk::{world_to_block, ChunkMap, CHUNK_SIZE};
+use nipahblocks::generator::{generate_chunk, height_band_at, WorldSeed};
+use nipahblocks::persistence::{WorldSave, UNKNOWN_BLOCK};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::chunk_queue::ChunkQueue;
+use crate::culling::ChunkConnectivity;
+use crate::diagnostics::DebugInfo;
+use crate::meshing::chunk_layers;
+use crate::player::Player;
+use crate::{GameResources, GameState};
+
+pub const DRAW_DISTANCE: u32 = 3;
+/// Time per frame spent loading or generating chunks.
+const CHUNK_LOAD_BUDGET: Duration = Duration::from_millis(4);
+/// Time per frame spent building chunk meshes.
+const CHUNK_MESH_BUDGET: Duration = Duration::from_millis(6);
+/// Number of recent chunk mesh build times kept for percentiles.
+const MESH_TIME_HISTORY: usize = 256;
+
+pub const CHUNKS_LOADED: DiagnosticPath = DiagnosticPath::const_new("chunks/loaded");
+pub const CHUNKS_MESHED: DiagnosticPath = DiagnosticPath::const_new("chunks/meshed");
+pub const CHUNKS_PENDING_LOAD: DiagnosticPath = DiagnosticPath::const_new("chunks/pending_load");
+pub const CHUNKS_PENDING_MESH: DiagnosticPath = DiagnosticPath::const_new("chunks/pending_mesh");
+pub const CHUNK_TRIANGLES: DiagnosticPath = DiagnosticPath::const_new("chunks/triangles");
+pub const CHUNK_VERTICES: DiagnosticPath = DiagnosticPath::const_new("chunks/vertices");
+pub const MESH_TIME_P50: DiagnosticPath = DiagnosticPath::const_new("chunks/mesh_time_p50");
+pub const MESH_TIME_P95: DiagnosticPath = DiagnosticPath::const_new("chunks/mesh_time_p95");
+pub const MESH_TIME_P99: DiagnosticPath = DiagnosticPath::const_new("chunks/mesh_time_p99");
+
+pub struct ChunksPlugin;
+
+impl Plugin for ChunksPlugin {
+    fn build(&self, app: &mut App) {
+        for path in [
+            CHUNKS_LOADED,
+            CHUNKS_MESHED,
+            CHUNKS_PENDING_LOAD,
+            CHUNKS_PENDING_MESH,
+            CHUNK_TRIANGLES,
+            CHUNK_VERTICES,
+        ] {
+            app.register_diagnostic(Diagnostic::new(path).with_smoothing_factor(0.0));
+        }
+        for path in [MESH_TIME_P50, MESH_TIME_P95, MESH_TIME_P99] {
+            app.register_diagnostic(Diagnostic::new(path).with_suffix("ms"));
+        }
+        app.init_resource::<ChunkMap>()
+            .init_resource::<ChunkEntities>()
+            .init_resource::<ChunkQueueStats>()
+            .init_resource::<MeshTimes>()
+            .insert_resource(AmbientOcclusion(true))
+            .add_systems(
+                Update,
+                (
+                    toggle_ambient_occlusion,
+                    update_chunks.run_if(resource_exists::<WorldSave>),
+                    refresh_chunks.run_if(resource_exists_and_changed::<GameResources>),
+                    queue_remesh,
+                    remesh_chunks,
+                    chunk_diagnostics,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame).and(resource_exists::<WorldSeed>)),
+            );
+    }
+}
+
+fn player_pos_to_chunk_block(pos: Vec3) -> (IVec3, UVec3) {
+    let size = Vec3::ONE * CHUNK_SIZE as f32;
+    let pos = pos + size / 2.0;
+    let chunk_pos = pos.div_euclid(size);
+    let block_pos = pos.rem_euclid(size);
+    (
+        IVec3::new(chunk_pos.x as i32, chunk_pos.y as i32, chunk_pos.z as i32),
+        UVec3::new(block_pos.x as u32, block_pos.y as u32, block_pos.z as u32),
+    )
+}
+
+#[derive(Debug, Component)]
+pub struct ChunkEntity(pub IVec3);
+
+/// Spawned chunk entities by chunk position.
+#[derive(Debug, Default, Resource, Deref, DerefMut)]
+pub struct ChunkEntities(HashMap<IVec3, Entity>);
+
+#[derive(Debug, Component)]
+pub struct ChunkLayer(pub Transparency);
+
+/// Where a chunk's block data came from when it was loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub enum ChunkSource {
+    Generated,
+    Loaded,
+    /// Streamed from a server.
+    Received,
+}
+
+/// Marks a chunk entity whose meshes have to be rebuilt from its block data.
+#[derive(Debug, Component)]
+pub struct Remesh;
+
+/// Triangle and vertex count of a chunk's meshes, all layers combined.
+#[derive(Debug, Component)]
+pub struct ChunkMeshInfo {
+    pub triangles: usize,
+    pub vertices: usize,
+}
+
+/// Recent chunk mesh build times in milliseconds.
+#[derive(Debug, Default, Resource)]
+struct MeshTimes(VecDeque<f64>);
+
+/// Chunks still waiting to be loaded or meshed after this frame's budget.
+#[derive(Debug, Default, Resource)]
+pub struct ChunkQueueStats {
+    pub pending_load: usize,
+    pub pending_mesh: usize,
+}
+
+/// Whether chunk meshes are built with per-vertex ambient occlusion.
+#[derive(Debug, Resource, Deref, DerefMut)]
+pub struct AmbientOcclusion(pub bool);
+
+fn toggle_ambient_occlusion(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut ambient_occlusion: ResMut<AmbientOcclusion>,
+    mut chunks_map: ResMut<ChunkMap>,
+) {
+    if !keyboard.just_pressed(KeyCode::F7) {
+        return;
+    }
+    **ambient_occlusion = !**ambient_occlusion;
+    info!("Ambient occlusion: {}", **ambient_occlusion);
+    chunks_map.mark_all_changed();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_chunks(
+    mut chunks_map: ResMut<ChunkMap>,
+    mut chunk_entities: ResMut<ChunkEntities>,
+    mut commands: Commands,
+    game_resources: Res<GameResources>,
+    world_save: Res<WorldSave>,
+    mut queue_stats: ResMut<ChunkQueueStats>,
+    player_q: Query<&Transform, With<Player>>,
+) {
+    let Ok(transform) = player_q.get_single() else {
+        return;
+    };
+    let (chunk_pos, _) = player_pos_to_chunk_block(transform.translation);
+    let radius = DRAW_DISTANCE as i32;
+    let mut queue = ChunkQueue::new(transform.translation, *transform.forward());
+    queue.extend(
+        (-radius..=radius)
+            .flat_map(|x| (-radius..=radius).map(move |z| (x, z)))
+            .map(|(x, z)| (chunk_pos + x * IVec3::X + z * IVec3::Z) * CHUNK_SIZE as i32)
+            .filter(|chunk_pos| !chunks_map.contains_chunk(*chunk_pos))
+            .map(|chunk_pos| (chunk_pos, ())),
+    );
+    for (chunk_pos, ()) in queue.drain_within(CHUNK_LOAD_BUDGET) {
+        let chunk_pos_f32 = Vec3::new(chunk_pos.x as f32, chunk_pos.y as f32, chunk_pos.z as f32);
+        let loaded = world_save
+            .load_chunk(chunk_pos, game_resources.blocks.clone())
+            .unwrap_or_else(|e| {
+                error!("Failed to load chunk {chunk_pos}, regenerating: {e}");
+                None
+            });
+        let (chunk, source) = match loaded {
+            Some(chunk) => (chunk, ChunkSource::Loaded),
+            None => (
+                generate_chunk(
+                    chunk_pos_f32,
+                    world_save.seed(),
+                    game_resources.blocks_map.clone(),
+                    game_resources.blocks.clone(),
+                ),
+                ChunkSource::Generated,
+            ),
+        };
+        spawn_chunk_entity(&mut commands, &mut chunk_entities, chunk_pos, source);
+        chunks_map.insert_chunk(chunk_pos, chunk);
+    }
+    queue_stats.pending_load = queue.len();
+}
+
+/// Spawns the entity rendering the chunk at `chunk_pos`, unless it already
+/// has one.
+pub fn spawn_chunk_entity(
+    commands: &mut Commands,
+    chunk_entities: &mut ChunkEntities,
+    chunk_pos: IVec3,
+    source: ChunkSource,
+) {
+    if chunk_entities.contains_key(&chunk_pos) {
+        return;
+    }
+    let entity = commands
+        .spawn((
+            ChunkEntity(chunk_pos),
+            source,
+            Remesh,
+            Transform::from_translation(chunk_pos.as_vec3()),
+            Visibility::default(),
+        ))
+        .with_children(|parent| {
+            for transparency in Transparency::ALL {
+                parent.spawn((
+                    ChunkLayer(transparency),
+                    Transform::default(),
+                    Visibility::default(),
+                ));
+            }
+        })
+        .id();
+    chunk_entities.insert(chunk_pos, entity);
+}
+
+pub(crate) fn refresh_chunks(mut chunks_map: ResMut<ChunkMap>, game_resources: Res<GameResources>) {
+    chunks_map.set_registry(
+        game_resources.block_names.clone(),
+        game_resources.blocks.clone(),
+        UNKNOWN_BLOCK,
+    );
+}
+
+fn queue_remesh(
+    mut commands: Commands,
+    mut chunks_map: ResMut<ChunkMap>,
+    chunk_entities: Res<ChunkEntities>,
+) {
+    for chunk_pos in chunks_map.take_changed() {
+        if let Some(entity) = chunk_entities.get(&chunk_pos) {
+            commands.entity(*entity).insert(Remesh);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn remesh_chunks(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    chunks_map: Res<ChunkMap>,
+    game_resources: Res<GameResources>,
+    ambient_occlusion: Res<AmbientOcclusion>,
+    mut queue_stats: ResMut<ChunkQueueStats>,
+    mut mesh_times: ResMut<MeshTimes>,
+    player_q: Query<&Transform, With<Player>>,
+    chunks_q: Query<(Entity, &ChunkEntity, &Children), With<Remesh>>,
+    layers_q: Query<&ChunkLayer>,
+) {
+    let Ok(transform) = player_q.get_single() else {
+        return;
+    };
+    let mut queue = ChunkQueue::new(transform.translation, *transform.forward());
+    queue.extend(
+        chunks_q
+            .iter()
+            .map(|(entity, ChunkEntity(chunk_pos), children)| (*chunk_pos, (entity, children))),
+    );
+    for (chunk_pos, (entity, children)) in queue.drain_within(CHUNK_MESH_BUDGET) {
+        let chunk_pos = &chunk_pos;
+        commands.entity(entity).remove::<Remesh>();
+        let Some(chunk) = chunks_map.chunk(*chunk_pos) else {
+            continue;
+        };
+        let start = Instant::now();
+        let mut layer_meshes = match chunk.is_empty() {
+            true => HashMap::new(),
+            false => chunk_layers(chunk, Some((&chunks_map, *chunk_pos)), **ambient_occlusion)
+                .collect::<HashMap<_, _>>(),
+        };
+        if mesh_times.0.len() == MESH_TIME_HISTORY {
+            mesh_times.0.pop_front();
+        }
+        mesh_times
+            .0
+            .push_back(start.elapsed().as_secs_f64() * 1000.0);
+        commands.entity(entity).insert((
+            ChunkConnectivity(chunk.connectivity()),
+            ChunkMeshInfo {
+                triangles: layer_meshes
+                    .values()
+                    .filter_map(|mesh| mesh.indices())
+                    .map(|indices| indices.len() / 3)
+                    .sum(),
+                vertices: layer_meshes.values().map(Mesh::count_vertices).sum(),
+            },
+        ));
+        for &child in &**children {
+            let Ok(ChunkLayer(transparency)) = layers_q.get(child) else {
+                continue;
+            };
+            let mut layer = commands.entity(child);
+            match layer_meshes.remove(transparency) {
+                Some(mesh) => layer.insert((
+                    Mesh3d(meshes.add(mesh)),
+                    MeshMaterial3d(game_resources.materials[*transparency as usize].clone()),
+                )),
+                None => layer.remove::<Mesh3d>(),
+            };
+        }
+    }
+    queue_stats.pending_mesh = queue.len();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn chunk_diagnostics(
+    mut diagnostics: Diagnostics,
+    mut debug_info: ResMut<DebugInfo>,
+    chunks_map: Res<ChunkMap>,
+    queue_stats: Res<ChunkQueueStats>,
+    mesh_times: Res<MeshTimes>,
+    seed: Res<WorldSeed>,
+    player_q: Query<&Transform, With<Player>>,
+    meshed_q: Query<&ChunkMeshInfo>,
+) {
+    diagnostics.add_measurement(&CHUNKS_LOADED, || chunks_map.len() as f64);
+    diagnostics.add_measurement(&CHUNKS_MESHED, || meshed_q.iter().len() as f64);
+    diagnostics.add_measurement(&CHUNKS_PENDING_LOAD, || queue_stats.pending_load as f64);
+    diagnostics.add_measurement(&CHUNKS_PENDING_MESH, || queue_stats.pending_mesh as f64);
+    diagnostics.add_measurement(&CHUNK_TRIANGLES, || {
+        meshed_q.iter().map(|info| info.triangles).sum::<usize>() as f64
+    });
+    diagnostics.add_measurement(&CHUNK_VERTICES, || {
+        meshed_q.iter().map(|info| info.vertices).sum::<usize>() as f64
+    });
+    let mut times = mesh_times.0.iter().copied().collect::<Vec<_>>();
+    times.sort_by(f64::total_cmp);
+    let percentile = |p: f64| times[((times.len() - 1) as f64 * p).round() as usize];
+    if !times.is_empty() {
+        diagnostics.add_measurement(&MESH_TIME_P50, || percentile(0.5));
+        diagnostics.add_measurement(&MESH_TIME_P95, || percentile(0.95));
+        diagnostics.add_measurement(&MESH_TIME_P99, || percentile(0.99));
+    }
+
+    let Ok(transform) = player_q.get_single() else {
+        return;
+    };
+    let (chunk_pos, block_pos) = player_pos_to_chunk_block(transform.translation);
+    debug_info.set("Chunk", format!("{chunk_pos}"));
+    debug_info.set("Block in chunk", format!("{block_pos}"));
+    let block = world_to_block(transform.translation);
+    let noise = noise::Perlin::new(**seed);
+    debug_info.set(
+        "Height band",
+        format!("{:?}", height_band_at(&noise, block.x, block.z)),
+    );
+}
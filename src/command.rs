@@ -0,0 +1,362 @@
+use anyhow::anyhow;
+use bevy::prelude::*;
+
+use crate::world_edit::{Axis, EditCommand};
+
+/// Block name clearing a block with `/setblock`.
+pub const AIR: &str = "air";
+
+/// How the player interacts with the world.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Component)]
+pub enum GameMode {
+    /// Flies with collision and edits blocks.
+    #[default]
+    Creative,
+    /// Flies through blocks without editing them.
+    Spectator,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 2] = [GameMode::Creative, GameMode::Spectator];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Creative => "creative",
+            GameMode::Spectator => "spectator",
+        }
+    }
+}
+
+/// A coordinate, either absolute or relative to the player when written as
+/// `~` or `~offset`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Coord {
+    Absolute(f32),
+    Relative(f32),
+}
+
+impl Coord {
+    fn parse(arg: &str) -> anyhow::Result<Self> {
+        let number = |s: &str| {
+            s.parse::<f32>()
+                .map_err(|_| anyhow!("Expected a coordinate, got \"{arg}\""))
+        };
+        match arg.strip_prefix('~') {
+            Some("") => Ok(Coord::Relative(0.0)),
+            Some(offset) => Ok(Coord::Relative(number(offset)?)),
+            None => Ok(Coord::Absolute(number(arg)?)),
+        }
+    }
+
+    fn resolve(self, base: f32) -> f32 {
+        match self {
+            Coord::Absolute(value) => value,
+            Coord::Relative(offset) => base + offset,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position(pub [Coord; 3]);
+
+impl Position {
+    pub fn resolve(&self, base: Vec3) -> Vec3 {
+        Vec3::from_array(std::array::from_fn(|axis| self.0[axis].resolve(base[axis])))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeAction {
+    Query,
+    Set(f32),
+    Add(f32),
+    Pause(bool),
+}
+
+/// Named times accepted by `/time set`, in hours.
+const NAMED_TIMES: [(&str, f32); 5] = [
+    ("day", 8.0),
+    ("noon", 12.0),
+    ("sunset", 18.0),
+    ("night", 21.0),
+    ("midnight", 0.0),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Help,
+    Teleport(Position),
+    Seed,
+    Time(TimeAction),
+    /// `block` is a block name or [`AIR`].
+    SetBlock {
+        pos: Position,
+        block: String,
+    },
+    GameMode(GameMode),
+    Edit(EditCommand),
+    Undo,
+    Redo,
+}
+
+/// What an argument accepts, used for autocompletion.
+#[derive(Debug, Clone, Copy)]
+enum ArgKind {
+    Coord,
+    Block,
+    Choice(&'static [&'static str]),
+    /// Depends on the previous argument.
+    TimeValue,
+}
+
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub usage: &'static str,
+    args: &'static [ArgKind],
+}
+
+const GAME_MODES: &[&str] = &["creative", "spectator"];
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "help",
+        usage: "/help",
+        args: &[],
+    },
+    CommandSpec {
+        name: "tp",
+        usage: "/tp <x> <y> <z>",
+        args: &[ArgKind::Coord, ArgKind::Coord, ArgKind::Coord],
+    },
+    CommandSpec {
+        name: "seed",
+        usage: "/seed",
+        args: &[],
+    },
+    CommandSpec {
+        name: "time",
+        usage: "/time [set <hours|day|noon|sunset|night|midnight> | add <hours> | pause | resume]",
+        args: &[
+            ArgKind::Choice(&["set", "add", "pause", "resume"]),
+            ArgKind::TimeValue,
+        ],
+    },
+    CommandSpec {
+        name: "setblock",
+        usage: "/setblock <x> <y> <z> <block>",
+        args: &[
+            ArgKind::Coord,
+            ArgKind::Coord,
+            ArgKind::Coord,
+            ArgKind::Block,
+        ],
+    },
+    CommandSpec {
+        name: "gamemode",
+        usage: "/gamemode <creative|spectator>",
+        args: &[ArgKind::Choice(GAME_MODES)],
+    },
+    CommandSpec {
+        name: "schematic",
+        usage: "/schematic <save|load> <name>",
+        args: &[ArgKind::Choice(&["save", "load"])],
+    },
+    CommandSpec {
+        name: "undo",
+        usage: "/undo",
+        args: &[],
+    },
+    CommandSpec {
+        name: "redo",
+        usage: "/redo",
+        args: &[],
+    },
+    CommandSpec {
+        name: "pos1",
+        usage: "/pos1 [<x> <y> <z>]",
+        args: &[ArgKind::Coord, ArgKind::Coord, ArgKind::Coord],
+    },
+    CommandSpec {
+        name: "pos2",
+        usage: "/pos2 [<x> <y> <z>]",
+        args: &[ArgKind::Coord, ArgKind::Coord, ArgKind::Coord],
+    },
+    CommandSpec {
+        name: "fill",
+        usage: "/fill <block>",
+        args: &[ArgKind::Block],
+    },
+    CommandSpec {
+        name: "replace",
+        usage: "/replace <from> <to>",
+        args: &[ArgKind::Block, ArgKind::Block],
+    },
+    CommandSpec {
+        name: "hollow",
+        usage: "/hollow <block>",
+        args: &[ArgKind::Block],
+    },
+    CommandSpec {
+        name: "walls",
+        usage: "/walls <block>",
+        args: &[ArgKind::Block],
+    },
+    CommandSpec {
+        name: "copy",
+        usage: "/copy",
+        args: &[],
+    },
+    CommandSpec {
+        name: "paste",
+        usage: "/paste",
+        args: &[],
+    },
+    CommandSpec {
+        name: "rotate",
+        usage: "/rotate <90|180|270>",
+        args: &[ArgKind::Choice(&["90", "180", "270"])],
+    },
+    CommandSpec {
+        name: "flip",
+        usage: "/flip <x|y|z>",
+        args: &[ArgKind::Choice(&["x", "y", "z"])],
+    },
+];
+
+fn spec(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|spec| spec.name == name)
+}
+
+fn position(args: &[&str]) -> anyhow::Result<Position> {
+    Ok(Position([
+        Coord::parse(args[0])?,
+        Coord::parse(args[1])?,
+        Coord::parse(args[2])?,
+    ]))
+}
+
+fn corner(name: &str) -> usize {
+    match name {
+        "pos1" => 0,
+        _ => 1,
+    }
+}
+
+fn hours(arg: &str) -> anyhow::Result<f32> {
+    NAMED_TIMES
+        .iter()
+        .find(|(name, _)| *name == arg)
+        .map(|(_, hours)| Ok(*hours))
+        .unwrap_or_else(|| {
+            arg.parse::<f32>()
+                .map_err(|_| anyhow!("Expected hours or a time name, got \"{arg}\""))
+        })
+}
+
+/// Usage of every command, one per line.
+pub fn help() -> String {
+    COMMANDS
+        .iter()
+        .map(|spec| spec.usage)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl Command {
+    /// Parses a command line without the leading `/`.
+    pub fn parse(line: &str) -> anyhow::Result<Self> {
+        let mut words = line.split_whitespace();
+        let name = words.next().ok_or(anyhow!("Missing command"))?;
+        let args = words.collect::<Vec<_>>();
+        let spec = spec(name).ok_or(anyhow!("Unknown command \"{name}\", try /help"))?;
+        let usage = || anyhow!("Usage: {}", spec.usage);
+        let command = match (name, args.as_slice()) {
+            ("help", []) => Command::Help,
+            ("tp", [_, _, _]) => Command::Teleport(position(&args)?),
+            ("seed", []) => Command::Seed,
+            ("time", []) => Command::Time(TimeAction::Query),
+            ("time", ["set", value]) => Command::Time(TimeAction::Set(hours(value)?)),
+            ("time", ["add", value]) => Command::Time(TimeAction::Add(hours(value)?)),
+            ("time", ["pause"]) => Command::Time(TimeAction::Pause(true)),
+            ("time", ["resume"]) => Command::Time(TimeAction::Pause(false)),
+            ("setblock", [_, _, _, block]) => Command::SetBlock {
+                pos: position(&args)?,
+                block: block.to_string(),
+            },
+            ("gamemode", [mode]) => Command::GameMode(
+                GameMode::ALL
+                    .into_iter()
+                    .find(|m| m.name() == *mode)
+                    .ok_or_else(usage)?,
+            ),
+            ("pos1" | "pos2", []) => Command::Edit(EditCommand::SetCorner(corner(name), None)),
+            ("pos1" | "pos2", [_, _, _]) => {
+                Command::Edit(EditCommand::SetCorner(corner(name), Some(position(&args)?)))
+            }
+            ("fill", [block]) => Command::Edit(EditCommand::Fill(block.to_string())),
+            ("replace", [from, to]) => {
+                Command::Edit(EditCommand::Replace(from.to_string(), to.to_string()))
+            }
+            ("hollow", [block]) => Command::Edit(EditCommand::Hollow(block.to_string())),
+            ("walls", [block]) => Command::Edit(EditCommand::Walls(block.to_string())),
+            ("copy", []) => Command::Edit(EditCommand::Copy),
+            ("paste", []) => Command::Edit(EditCommand::Paste),
+            ("rotate", [degrees @ ("90" | "180" | "270")]) => {
+                Command::Edit(EditCommand::Rotate(degrees.parse()?))
+            }
+            ("flip", [axis]) => Command::Edit(EditCommand::Flip(match *axis {
+                "x" => Axis::X,
+                "y" => Axis::Y,
+                "z" => Axis::Z,
+                _ => return Err(usage()),
+            })),
+            ("schematic", [action @ ("save" | "load"), name]) => {
+                if !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                {
+                    return Err(anyhow!(
+                        "Schematic names may only contain letters, digits, '_' and '-'"
+                    ));
+                }
+                Command::Edit(match *action {
+                    "save" => EditCommand::SaveSchematic(name.to_string()),
+                    _ => EditCommand::LoadSchematic(name.to_string()),
+                })
+            }
+            ("undo", []) => Command::Undo,
+            ("redo", []) => Command::Redo,
+            _ => return Err(usage()),
+        };
+        Ok(command)
+    }
+}
+
+/// Completions for the last word of `line`, a command line without the
+/// leading `/`. Each completion is the whole line with that word replaced.
+pub fn complete(line: &str, block_names: &[String]) -> Vec<String> {
+    let (head, word) = match line.rfind(' ') {
+        Some(i) => line.split_at(i + 1),
+        None => ("", line),
+    };
+    let words = head.split_whitespace().collect::<Vec<_>>();
+    let candidates: Vec<&str> = match words.split_first() {
+        None => COMMANDS.iter().map(|spec| spec.name).collect(),
+        Some((name, args)) => match spec(name).and_then(|spec| spec.args.get(args.len())) {
+            Some(ArgKind::Choice(choices)) => choices.to_vec(),
+            Some(ArgKind::Block) => std::iter::once(AIR)
+                .chain(block_names.iter().map(String::as_str))
+                .collect(),
+            Some(ArgKind::TimeValue) if args.first() == Some(&"set") => {
+                NAMED_TIMES.iter().map(|(name, _)| *name).collect()
+            }
+            Some(ArgKind::Coord) => vec!["~"],
+            _ => Vec::new(),
+        },
+    };
+    candidates
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(word))
+        .map(|candidate| format!("{head}{candidate}"))
+        .collect()
+}
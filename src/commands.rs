@@ -0,0 +1,221 @@
+use anyhow::anyhow;
+use bevy::prelude::*;
+use nipahblocks::chunk::{world_to_block, ChunkMap};
+use nipahblocks::command::{help, Command, GameMode, TimeAction, AIR};
+use nipahblocks::generator::WorldSeed;
+use nipahblocks::history::EditHistory;
+use nipahblocks::net::{FromServer, ToServer};
+use nipahblocks::protocol::{ClientMessage, ServerMessage};
+use nipahblocks::world_edit::EditSession;
+use nipahblocks::world_time::WorldTime;
+
+use crate::console::{ConsoleMessage, ConsoleSubmit};
+use crate::daylight::TimeCommand;
+use crate::interaction::BlocksEdited;
+use crate::network::ConnectConfig;
+use crate::player::Player;
+use crate::{GameResources, GameState};
+
+/// Runs console commands locally, or sends them to the server when playing
+/// on one.
+pub struct CommandsPlugin;
+
+impl Plugin for CommandsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                history_keys,
+                (run_local_commands, record_edits).chain().run_if(
+                    not(resource_exists::<ConnectConfig>).and(resource_exists::<GameResources>),
+                ),
+                (send_chat, receive_server_commands).run_if(resource_exists::<ConnectConfig>),
+            )
+                .chain()
+                .run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_local_commands(
+    mut submits: EventReader<ConsoleSubmit>,
+    mut messages: EventWriter<ConsoleMessage>,
+    mut time_commands: EventWriter<TimeCommand>,
+    mut edits: EventWriter<BlocksEdited>,
+    mut chunk_map: ResMut<ChunkMap>,
+    game_resources: Res<GameResources>,
+    seed: Option<Res<WorldSeed>>,
+    world_time: Option<Res<WorldTime>>,
+    mut player_q: Query<
+        (
+            &mut Transform,
+            &mut GameMode,
+            &mut EditSession,
+            &mut EditHistory,
+        ),
+        With<Player>,
+    >,
+) {
+    for ConsoleSubmit(text) in submits.read() {
+        let Some(line) = text.strip_prefix('/') else {
+            messages.send(ConsoleMessage(text.clone()));
+            continue;
+        };
+        let Ok((mut transform, mut game_mode, mut session, mut history)) =
+            player_q.get_single_mut()
+        else {
+            continue;
+        };
+        let output = Command::parse(line).and_then(|command| {
+            Ok(match command {
+                Command::Help => help(),
+                Command::Teleport(pos) => {
+                    transform.translation = pos.resolve(transform.translation);
+                    format!("Teleported to {:.2}", transform.translation)
+                }
+                Command::Seed => match &seed {
+                    Some(seed) => format!("Seed: {}", seed.0),
+                    None => "The world isn't loaded yet".to_string(),
+                },
+                Command::Time(action) => match (action, &world_time) {
+                    (_, None) => "The world isn't loaded yet".to_string(),
+                    (TimeAction::Query, Some(world_time)) => {
+                        format!("Time is {}", world_time.clock())
+                    }
+                    (TimeAction::Set(hours), Some(_)) => {
+                        time_commands.send(TimeCommand::Set(hours));
+                        format!("Set the time to {}", WorldTime::new(hours).clock())
+                    }
+                    (TimeAction::Add(hours), Some(world_time)) => {
+                        time_commands.send(TimeCommand::Add(hours));
+                        let time = WorldTime::new(world_time.time_of_day + hours);
+                        format!("Set the time to {}", time.clock())
+                    }
+                    (TimeAction::Pause(paused), Some(_)) => {
+                        time_commands.send(TimeCommand::Pause(paused));
+                        match paused {
+                            true => "Paused the time".to_string(),
+                            false => "Resumed the time".to_string(),
+                        }
+                    }
+                },
+                Command::SetBlock { pos, block } => {
+                    let id = match block.as_str() {
+                        AIR => None,
+                        name => Some(
+                            *game_resources
+                                .blocks_map
+                                .get(name)
+                                .ok_or_else(|| anyhow!("Unknown block \"{name}\""))?,
+                        ),
+                    };
+                    let base = world_to_block(transform.translation).as_vec3();
+                    let pos = pos.resolve(base).floor().as_ivec3();
+                    if !chunk_map.contains_chunk(ChunkMap::chunk_pos(pos)) {
+                        return Err(anyhow!("{pos} isn't loaded"));
+                    }
+                    edits.send(BlocksEdited(chunk_map.set_blocks([(pos, id)])));
+                    format!("Set {pos} to {block}")
+                }
+                Command::GameMode(mode) => {
+                    *game_mode = mode;
+                    format!("Game mode set to {}", mode.name())
+                }
+                Command::Edit(command) => {
+                    let outcome = session.execute(
+                        command,
+                        &mut chunk_map,
+                        &game_resources.blocks_map,
+                        &transform,
+                    )?;
+                    edits.send(BlocksEdited(outcome.changes));
+                    outcome.message
+                }
+                Command::Undo => match history.undo(&mut chunk_map) {
+                    Some(reverted) => reverted.describe("Undid"),
+                    None => "Nothing to undo".to_string(),
+                },
+                Command::Redo => match history.redo(&mut chunk_map) {
+                    Some(reverted) => reverted.describe("Redid"),
+                    None => "Nothing to redo".to_string(),
+                },
+            })
+        });
+        messages.send(ConsoleMessage(output.unwrap_or_else(|e| e.to_string())));
+    }
+}
+
+/// Records the player's edits so they can be undone.
+fn record_edits(
+    mut edits: EventReader<BlocksEdited>,
+    mut history_q: Query<&mut EditHistory, With<Player>>,
+) {
+    let Ok(mut history) = history_q.get_single_mut() else {
+        return;
+    };
+    for BlocksEdited(changes) in edits.read() {
+        history.record(changes.clone());
+    }
+}
+
+/// Ctrl+Z undoes and Ctrl+Y redoes, through the console so they work the
+/// same on a server.
+fn history_keys(keyboard: Res<ButtonInput<KeyCode>>, mut submits: EventWriter<ConsoleSubmit>) {
+    if !keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::KeyZ) {
+        submits.send(ConsoleSubmit("/undo".to_string()));
+    }
+    if keyboard.just_pressed(KeyCode::KeyY) {
+        submits.send(ConsoleSubmit("/redo".to_string()));
+    }
+}
+
+fn send_chat(mut submits: EventReader<ConsoleSubmit>, mut to_server: EventWriter<ToServer>) {
+    for ConsoleSubmit(text) in submits.read() {
+        to_server.send(ToServer(ClientMessage::Chat { text: text.clone() }));
+    }
+}
+
+/// Applies the results of commands the server ran for this player.
+fn receive_server_commands(
+    mut from_server: EventReader<FromServer>,
+    mut messages: EventWriter<ConsoleMessage>,
+    mut world_time: Option<ResMut<WorldTime>>,
+    mut player_q: Query<(&mut Transform, &mut GameMode, &mut EditSession), With<Player>>,
+) {
+    for FromServer(message) in from_server.read() {
+        match message {
+            ServerMessage::Chat { text } => {
+                messages.send(ConsoleMessage(text.clone()));
+            }
+            ServerMessage::Teleport { translation } => {
+                for (mut transform, _, _) in &mut player_q {
+                    transform.translation = *translation;
+                }
+            }
+            ServerMessage::TimeOfDay {
+                time_of_day,
+                paused,
+            } => {
+                if let Some(world_time) = &mut world_time {
+                    world_time.time_of_day = *time_of_day;
+                    world_time.paused = *paused;
+                }
+            }
+            ServerMessage::SetGameMode { mode } => {
+                for (_, mut game_mode, _) in &mut player_q {
+                    *game_mode = *mode;
+                }
+            }
+            ServerMessage::Selection { corners } => {
+                for (_, _, mut session) in &mut player_q {
+                    session.corners = *corners;
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+use crate::block::Face;
+use crate::chunk::CHUNK_SIZE;
+
+/// Which pairs of chunk faces can see each other through non-opaque cells.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FaceConnectivity(u64);
+
+impl FaceConnectivity {
+    pub const ALL: FaceConnectivity = FaceConnectivity(u64::MAX);
+
+    fn bit(a: Face, b: Face) -> u64 {
+        1 << (a as usize * 6 + b as usize)
+    }
+
+    fn connect(&mut self, a: Face, b: Face) {
+        self.0 |= Self::bit(a, b) | Self::bit(b, a);
+    }
+
+    pub fn connected(&self, a: Face, b: Face) -> bool {
+        self.0 & Self::bit(a, b) != 0
+    }
+
+    /// Flood fills the non-opaque cells of a chunk and connects all faces every
+    /// region touches.
+    pub fn compute(is_opaque: impl Fn(IVec3) -> bool) -> Self {
+        let size = CHUNK_SIZE as i32;
+        let index = |pos: IVec3| (pos.x * size * size + pos.y * size + pos.z) as usize;
+        let mut visited = vec![false; (size * size * size) as usize];
+        let mut connectivity = FaceConnectivity::default();
+        let mut queue = VecDeque::new();
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    let start = IVec3::new(x, y, z);
+                    if visited[index(start)] || is_opaque(start) {
+                        continue;
+                    }
+                    visited[index(start)] = true;
+                    queue.push_back(start);
+                    let mut touched = Vec::new();
+                    while let Some(pos) = queue.pop_front() {
+                        for face in Face::ALL {
+                            let neighbor = pos + face.normal();
+                            let inside = neighbor.cmpge(IVec3::ZERO).all()
+                                && neighbor.cmplt(IVec3::splat(size)).all();
+                            if !inside {
+                                if !touched.contains(&face) {
+                                    touched.push(face);
+                                }
+                                continue;
+                            }
+                            if !visited[index(neighbor)] && !is_opaque(neighbor) {
+                                visited[index(neighbor)] = true;
+                                queue.push_back(neighbor);
+                            }
+                        }
+                    }
+                    for &a in &touched {
+                        for &b in &touched {
+                            connectivity.connect(a, b);
+                        }
+                    }
+                }
+            }
+        }
+        connectivity
+    }
+}
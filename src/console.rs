@@ -0,0 +1,247 @@
+use bevy::{
+    input::{
+        keyboard::{Key, KeyboardInput},
+        mouse::AccumulatedMouseScroll,
+        ButtonState, InputSystem,
+    },
+    prelude::*,
+};
+use nipahblocks::command::complete;
+use std::collections::VecDeque;
+
+use crate::GameResources;
+
+/// Messages kept in the console history.
+const MAX_MESSAGES: usize = 100;
+/// Messages shown while the console is open.
+const VISIBLE_MESSAGES: usize = 12;
+/// How long new messages stay visible while the console is closed.
+const MESSAGE_DURATION: f64 = 10.0;
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .add_event::<ConsoleMessage>()
+            .add_event::<ConsoleSubmit>()
+            .add_systems(Startup, spawn_console)
+            .add_systems(PreUpdate, capture_input.after(InputSystem))
+            .add_systems(
+                Update,
+                (edit_input, receive_messages, update_console_text).chain(),
+            );
+    }
+}
+
+/// A line to show in the console.
+#[derive(Debug, Event)]
+pub struct ConsoleMessage(pub String);
+
+/// Text the player entered, a command if it starts with `/`.
+#[derive(Debug, Event)]
+pub struct ConsoleSubmit(pub String);
+
+#[derive(Debug, Default, Resource)]
+struct Console {
+    open: bool,
+    input: String,
+    /// Messages with the time they were received.
+    messages: VecDeque<(f64, String)>,
+    /// Previously submitted lines, newest last.
+    submitted: Vec<String>,
+    /// Index into `submitted` while browsing it with the arrow keys.
+    browsing: Option<usize>,
+    /// Completions shown below the input line.
+    hint: String,
+}
+
+#[derive(Debug, Component)]
+struct ConsoleText;
+
+fn spawn_console(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        ConsoleText,
+        Text::default(),
+        TextColor(Color::WHITE),
+        TextFont {
+            font: asset_server.load("fonts/RobotoMono-Regular.ttf"),
+            font_size: 16.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(8.0),
+            bottom: Val::Px(8.0),
+            padding: UiRect::all(Val::Px(4.0)),
+            ..default()
+        },
+        BackgroundColor(Color::NONE),
+    ));
+}
+
+/// Hides key and button presses and scrolling from gameplay systems while the
+/// player is typing.
+fn capture_input(
+    console: Res<Console>,
+    mut keyboard: ResMut<ButtonInput<KeyCode>>,
+    mut mouse: ResMut<ButtonInput<MouseButton>>,
+    mut scroll: ResMut<AccumulatedMouseScroll>,
+) {
+    if console.open {
+        keyboard.reset_all();
+        mouse.reset_all();
+        scroll.delta = Vec2::ZERO;
+    }
+}
+
+fn edit_input(
+    mut console: ResMut<Console>,
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut submits: EventWriter<ConsoleSubmit>,
+    game_resources: Option<Res<GameResources>>,
+) {
+    for event in keyboard_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+        if !console.open {
+            match &event.logical_key {
+                Key::Character(c) if c.as_str() == "/" => console.input = "/".to_string(),
+                Key::Character(c) if c.eq_ignore_ascii_case("t") => console.input.clear(),
+                _ => continue,
+            }
+            console.open = true;
+            console.browsing = None;
+            console.hint.clear();
+            // The rest of this frame's keys were typed before opening.
+            break;
+        }
+        match &event.logical_key {
+            Key::Escape => console.open = false,
+            Key::Enter => {
+                let line = std::mem::take(&mut console.input).trim().to_string();
+                if !line.is_empty() {
+                    if console.submitted.last() != Some(&line) {
+                        console.submitted.push(line.clone());
+                    }
+                    submits.send(ConsoleSubmit(line));
+                }
+                console.open = false;
+            }
+            Key::Backspace => {
+                console.input.pop();
+            }
+            Key::ArrowUp | Key::ArrowDown => {
+                let len = console.submitted.len();
+                let browsing = match (event.logical_key == Key::ArrowUp, console.browsing) {
+                    (true, None) => len.checked_sub(1),
+                    (true, Some(i)) => Some(i.saturating_sub(1)),
+                    (false, Some(i)) if i + 1 < len => Some(i + 1),
+                    (false, _) => None,
+                };
+                console.browsing = browsing;
+                console.input = browsing
+                    .map(|i| console.submitted[i].clone())
+                    .unwrap_or_default();
+            }
+            Key::Tab => {
+                let Some(line) = console.input.strip_prefix('/') else {
+                    continue;
+                };
+                let block_names = game_resources
+                    .as_ref()
+                    .map(|resources| resources.block_names.as_slice())
+                    .unwrap_or_default();
+                let completions = complete(line, block_names);
+                // Complete as far as all candidates agree.
+                if let Some(first) = completions.first() {
+                    let common = completions.iter().fold(first.as_str(), |common, c| {
+                        let len = common
+                            .chars()
+                            .zip(c.chars())
+                            .take_while(|(a, b)| a == b)
+                            .map(|(a, _)| a.len_utf8())
+                            .sum();
+                        &common[..len]
+                    });
+                    console.input = format!("/{common}");
+                    if completions.len() == 1 {
+                        console.input.push(' ');
+                    }
+                }
+                console.hint = match completions.len() {
+                    0 | 1 => String::new(),
+                    _ => completions
+                        .iter()
+                        .map(|c| c.rsplit(' ').next().unwrap_or(c))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                };
+                continue;
+            }
+            Key::Space => console.input.push(' '),
+            Key::Character(c) => console.input.extend(c.chars().filter(|c| !c.is_control())),
+            _ => continue,
+        }
+        console.hint.clear();
+    }
+}
+
+fn receive_messages(
+    time: Res<Time>,
+    mut console: ResMut<Console>,
+    mut messages: EventReader<ConsoleMessage>,
+) {
+    for ConsoleMessage(message) in messages.read() {
+        info!("{message}");
+        for line in message.lines() {
+            if console.messages.len() == MAX_MESSAGES {
+                console.messages.pop_front();
+            }
+            console
+                .messages
+                .push_back((time.elapsed_secs_f64(), line.to_string()));
+        }
+    }
+}
+
+fn update_console_text(
+    time: Res<Time>,
+    console: Res<Console>,
+    mut text_q: Query<(&mut Text, &mut BackgroundColor), With<ConsoleText>>,
+) {
+    let Ok((mut text, mut background)) = text_q.get_single_mut() else {
+        return;
+    };
+    let now = time.elapsed_secs_f64();
+    let mut lines = console
+        .messages
+        .iter()
+        .rev()
+        .take(VISIBLE_MESSAGES)
+        .filter(|(received, _)| console.open || now - received < MESSAGE_DURATION)
+        .map(|(_, line)| line.as_str())
+        .collect::<Vec<_>>();
+    lines.reverse();
+    let mut s = lines.join("\n");
+    if console.open {
+        if !s.is_empty() {
+            s += "\n";
+        }
+        s += &format!("> {}_", console.input);
+        if !console.hint.is_empty() {
+            s += &format!("\n{}", console.hint);
+        }
+    }
+    if text.0 != s {
+        text.0 = s;
+    }
+    let color = match console.open {
+        true => Color::srgba(0.0, 0.0, 0.0, 0.5),
+        false => Color::NONE,
+    };
+    if background.0 != color {
+        background.0 = color;
+    }
+}
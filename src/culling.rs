@@ -0,0 +1,144 @@
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    math::Affine3A,
+    prelude::*,
+    render::{
+        primitives::{Aabb, Frustum},
+        view::VisibilitySystems,
+    },
+    utils::hashbrown::{HashMap, HashSet},
+};
+use std::collections::VecDeque;
+
+use nipahblocks::block::Face;
+use nipahblocks::chunk::{world_to_block, ChunkMap, CHUNK_SIZE};
+use nipahblocks::connectivity::FaceConnectivity;
+
+use crate::chunks::{ChunkEntity, DRAW_DISTANCE};
+use crate::GameState;
+
+pub const CHUNKS_VISIBLE: DiagnosticPath = DiagnosticPath::const_new("culling/visible");
+pub const CHUNKS_FRUSTUM_CULLED: DiagnosticPath =
+    DiagnosticPath::const_new("culling/frustum_culled");
+pub const CHUNKS_OCCLUDED: DiagnosticPath = DiagnosticPath::const_new("culling/occluded");
+
+pub struct CullingPlugin;
+
+impl Plugin for CullingPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_diagnostic(Diagnostic::new(CHUNKS_VISIBLE))
+            .register_diagnostic(Diagnostic::new(CHUNKS_FRUSTUM_CULLED))
+            .register_diagnostic(Diagnostic::new(CHUNKS_OCCLUDED))
+            .add_systems(
+                PostUpdate,
+                cull_chunks
+                    .before(VisibilitySystems::VisibilityPropagate)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Face connectivity of a chunk entity's blocks, updated when it's remeshed.
+#[derive(Debug, Clone, Copy, Component, Deref)]
+pub struct ChunkConnectivity(pub FaceConnectivity);
+
+#[derive(Debug, Default)]
+struct CullingStats {
+    visible: usize,
+    frustum_culled: usize,
+    occlusion_culled: usize,
+}
+
+fn chunk_aabb(chunk_pos: IVec3) -> Aabb {
+    let half = CHUNK_SIZE as f32 / 2.0;
+    let center = chunk_pos.as_vec3();
+    Aabb::from_min_max(center - half, center + half)
+}
+
+/// Walks the chunk grid outwards from the camera, entering a neighbor only
+/// through faces the current chunk connects to the face it was entered by and
+/// never stepping back towards the camera. Chunks not reached are hidden.
+fn cull_chunks(
+    mut diagnostics: Diagnostics,
+    camera_q: Query<(&GlobalTransform, &Frustum), With<Camera3d>>,
+    mut chunks_q: Query<(&ChunkEntity, Option<&ChunkConnectivity>, &mut Visibility)>,
+) {
+    let Ok((camera_transform, frustum)) = camera_q.get_single() else {
+        return;
+    };
+    let connectivity = chunks_q
+        .iter()
+        .map(|(ChunkEntity(chunk_pos), connectivity, _)| {
+            (
+                *chunk_pos,
+                connectivity.map_or(FaceConnectivity::ALL, |connectivity| **connectivity),
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    let in_frustum = |chunk_pos: IVec3| {
+        frustum.intersects_obb(&chunk_aabb(chunk_pos), &Affine3A::IDENTITY, true, false)
+    };
+
+    let size = CHUNK_SIZE as i32;
+    let start = ChunkMap::chunk_pos(world_to_block(camera_transform.translation()));
+    let max_steps = DRAW_DISTANCE as i32 + 1;
+    let mut reached = HashSet::from([start]);
+    // Chunk position, face it was entered through and directions taken so far.
+    let mut queue = VecDeque::from([(start, None::<Face>, 0u8)]);
+    while let Some((chunk_pos, entered, directions)) = queue.pop_front() {
+        // Unloaded chunks are treated as open air.
+        let connectivity = connectivity
+            .get(&chunk_pos)
+            .copied()
+            .unwrap_or(FaceConnectivity::ALL);
+        for face in Face::ALL {
+            if directions & (1 << face.opposite() as u8) != 0 {
+                continue;
+            }
+            if entered.is_some_and(|entered| !connectivity.connected(entered, face)) {
+                continue;
+            }
+            let neighbor = chunk_pos + face.normal() * size;
+            if ((neighbor - start) / size).abs().max_element() > max_steps
+                || reached.contains(&neighbor)
+                || !in_frustum(neighbor)
+            {
+                continue;
+            }
+            reached.insert(neighbor);
+            queue.push_back((
+                neighbor,
+                Some(face.opposite()),
+                directions | 1 << face as u8,
+            ));
+        }
+    }
+
+    let mut stats = CullingStats::default();
+    for (ChunkEntity(chunk_pos), _, mut visibility) in &mut chunks_q {
+        let visible = match reached.contains(chunk_pos) {
+            true => true,
+            false if !in_frustum(*chunk_pos) => {
+                stats.frustum_culled += 1;
+                false
+            }
+            false => {
+                stats.occlusion_culled += 1;
+                false
+            }
+        };
+        if visible {
+            stats.visible += 1;
+        }
+        let target = match visible {
+            true => Visibility::Inherited,
+            false => Visibility::Hidden,
+        };
+        if *visibility != target {
+            *visibility = target;
+        }
+    }
+    diagnostics.add_measurement(&CHUNKS_VISIBLE, || stats.visible as f64);
+    diagnostics.add_measurement(&CHUNKS_FRUSTUM_CULLED, || stats.frustum_culled as f64);
+    diagnostics.add_measurement(&CHUNKS_OCCLUDED, || stats.occlusion_culled as f64);
+}
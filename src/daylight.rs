@@ -0,0 +1,156 @@
+use bevy::{pbr::FogFalloff, prelude::*};
+use nipahblocks::chunk::CHUNK_SIZE;
+use nipahblocks::world_time::{WorldTime, HOURS_PER_DAY, MIN_DAYLIGHT};
+
+use crate::lod::LOD_DISTANCE;
+use crate::material::BlockMaterial;
+use crate::{GameResources, GameState};
+
+const SUN_ILLUMINANCE: f32 = 10_000.0;
+const DAY_SKY: Color = Color::srgb(0.55, 0.75, 1.0);
+const NIGHT_SKY: Color = Color::srgb(0.01, 0.01, 0.04);
+const SUNSET_SKY: Color = Color::srgb(0.95, 0.5, 0.3);
+
+pub struct DaylightPlugin;
+
+impl Plugin for DaylightPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TimeCommand>()
+            .add_systems(OnEnter(GameState::InGame), spawn_sun)
+            .add_systems(
+                Update,
+                (
+                    time_keys,
+                    apply_time_commands,
+                    advance_time,
+                    (update_daylight, update_fog),
+                )
+                    .chain()
+                    .run_if(resource_exists::<WorldTime>),
+            );
+    }
+}
+
+fn sky_color(world_time: &WorldTime) -> Color {
+    let height = world_time.sun_direction().y;
+    let sunset = (1.0 - height.abs() * 4.0).clamp(0.0, 1.0);
+    NIGHT_SKY
+        .mix(
+            &DAY_SKY,
+            (world_time.daylight() - MIN_DAYLIGHT) / (1.0 - MIN_DAYLIGHT),
+        )
+        .mix(&SUNSET_SKY, sunset * 0.5)
+}
+
+#[derive(Debug, Event, Clone, Copy)]
+pub enum TimeCommand {
+    Set(f32),
+    Add(f32),
+    Pause(bool),
+}
+
+#[derive(Debug, Component)]
+struct Sun;
+
+fn spawn_sun(mut commands: Commands) {
+    commands.spawn((
+        Sun,
+        DirectionalLight {
+            shadows_enabled: false,
+            ..default()
+        },
+        Transform::default(),
+    ));
+}
+
+fn time_keys(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    world_time: Res<WorldTime>,
+    mut commands: EventWriter<TimeCommand>,
+) {
+    if keyboard.just_pressed(KeyCode::BracketRight) {
+        commands.send(TimeCommand::Add(1.0));
+    }
+    if keyboard.just_pressed(KeyCode::BracketLeft) {
+        commands.send(TimeCommand::Add(-1.0));
+    }
+    if keyboard.just_pressed(KeyCode::Backslash) {
+        commands.send(TimeCommand::Pause(!world_time.paused));
+    }
+}
+
+fn apply_time_commands(mut world_time: ResMut<WorldTime>, mut commands: EventReader<TimeCommand>) {
+    for command in commands.read() {
+        match *command {
+            TimeCommand::Set(time) => world_time.time_of_day = time,
+            TimeCommand::Add(hours) => world_time.time_of_day += hours,
+            TimeCommand::Pause(paused) => world_time.paused = paused,
+        }
+        world_time.time_of_day = world_time.time_of_day.rem_euclid(HOURS_PER_DAY);
+        info!(
+            "Time of day set to {:.2}{}",
+            world_time.time_of_day,
+            if world_time.paused { " (paused)" } else { "" }
+        );
+    }
+}
+
+fn advance_time(time: Res<Time>, mut world_time: ResMut<WorldTime>) {
+    world_time.advance(time.delta_secs());
+}
+
+fn update_daylight(
+    world_time: Res<WorldTime>,
+    game_resources: Option<Res<GameResources>>,
+    mut materials: ResMut<Assets<BlockMaterial>>,
+    mut clear_color: ResMut<ClearColor>,
+    mut ambient_light: ResMut<AmbientLight>,
+    mut sun_q: Query<(&mut Transform, &mut DirectionalLight), With<Sun>>,
+) {
+    let daylight = world_time.daylight();
+    let sun_direction = world_time.sun_direction();
+    if let Ok((mut transform, mut light)) = sun_q.get_single_mut() {
+        *transform = Transform::default().looking_to(-sun_direction, Vec3::Y);
+        light.illuminance = SUN_ILLUMINANCE * sun_direction.y.max(0.0);
+    }
+    clear_color.0 = sky_color(&world_time);
+    ambient_light.brightness = 80.0 * daylight;
+    let Some(game_resources) = game_resources else {
+        return;
+    };
+    for handle in &game_resources.materials {
+        let outdated = materials
+            .get(handle)
+            .is_some_and(|material| (material.daylight - daylight).abs() > 0.001);
+        if outdated {
+            if let Some(material) = materials.get_mut(handle) {
+                material.daylight = daylight;
+            }
+        }
+    }
+}
+
+fn update_fog(
+    mut commands: Commands,
+    world_time: Res<WorldTime>,
+    mut fog_q: Query<(Entity, Option<&mut DistanceFog>), With<Camera3d>>,
+) {
+    let sky_color = sky_color(&world_time);
+    // Fade out terrain towards the edge of the LOD area to hide pop-in.
+    let radius = (LOD_DISTANCE * CHUNK_SIZE) as f32;
+    for (camera, fog) in &mut fog_q {
+        match fog {
+            Some(mut fog) => fog.color = sky_color,
+            None => {
+                commands.entity(camera).insert(DistanceFog {
+                    color: sky_color,
+                    falloff: FogFalloff::Linear {
+                        start: radius * 0.6,
+                        end: radius,
+                    },
+                    ..default()
+                });
+            }
+        }
+    }
+}
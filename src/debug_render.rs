@@ -0,0 +1,207 @@
+use bevy::{pbr::wireframe::WireframeConfig, prelude::*, utils::hashbrown::HashMap};
+use nipahblocks::block_registry::Transparency;
+use nipahblocks::chunk::{ChunkMap, CHUNK_SIZE};
+
+use crate::chunks::{ChunkEntity, ChunkLayer, ChunkSource, Remesh};
+use crate::material::BlockMaterial;
+use crate::player::Player;
+use crate::{GameResources, GameState};
+
+/// Radius around the player within which face normals are drawn.
+const NORMALS_RADIUS: f32 = 24.0;
+const NORMAL_LENGTH: f32 = 0.4;
+
+pub struct DebugRenderPlugin;
+
+impl Plugin for DebugRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugRenderModes>()
+            .init_resource::<TintMaterials>()
+            .add_systems(
+                Update,
+                (
+                    toggle_debug_modes,
+                    draw_chunk_borders.run_if(|modes: Res<DebugRenderModes>| modes.chunk_borders),
+                    draw_face_normals.run_if(|modes: Res<DebugRenderModes>| modes.face_normals),
+                    tint_chunks,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame).and(resource_exists::<GameResources>)),
+            );
+    }
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct DebugRenderModes {
+    pub chunk_borders: bool,
+    pub generation_tint: bool,
+    pub face_normals: bool,
+}
+
+/// Generation state a chunk is tinted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ChunkState {
+    Generated,
+    Loaded,
+    Received,
+    Unsaved,
+    PendingMesh,
+}
+
+impl ChunkState {
+    fn color(self) -> LinearRgba {
+        match self {
+            ChunkState::Generated => LinearRgba::rgb(0.6, 1.0, 0.6),
+            ChunkState::Loaded => LinearRgba::rgb(0.6, 0.7, 1.0),
+            ChunkState::Received => LinearRgba::rgb(0.9, 0.6, 1.0),
+            ChunkState::Unsaved => LinearRgba::rgb(1.0, 0.7, 0.3),
+            ChunkState::PendingMesh => LinearRgba::rgb(1.0, 0.4, 0.4),
+        }
+    }
+}
+
+/// Tinted copies of the block materials, created on demand.
+#[derive(Debug, Default, Resource)]
+struct TintMaterials(HashMap<(ChunkState, Transparency), Handle<BlockMaterial>>);
+
+fn toggle_debug_modes(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut modes: ResMut<DebugRenderModes>,
+    mut wireframe_config: ResMut<WireframeConfig>,
+) {
+    if keyboard.just_pressed(KeyCode::F4) {
+        wireframe_config.global = !wireframe_config.global;
+        info!("Wireframe: {}", wireframe_config.global);
+    }
+    if keyboard.just_pressed(KeyCode::F5) {
+        modes.chunk_borders = !modes.chunk_borders;
+        info!("Chunk borders: {}", modes.chunk_borders);
+    }
+    if keyboard.just_pressed(KeyCode::F6) {
+        modes.generation_tint = !modes.generation_tint;
+        info!("Chunk generation tint: {}", modes.generation_tint);
+    }
+    if keyboard.just_pressed(KeyCode::F8) {
+        modes.face_normals = !modes.face_normals;
+        info!("Face normals: {}", modes.face_normals);
+    }
+}
+
+fn draw_chunk_borders(mut gizmos: Gizmos, chunk_map: Res<ChunkMap>) {
+    for (chunk_pos, chunk) in chunk_map.chunks() {
+        let color = match chunk.is_empty() {
+            true => Color::srgba(1.0, 1.0, 1.0, 0.2),
+            false => Color::srgb(1.0, 1.0, 0.0),
+        };
+        gizmos.cuboid(
+            Transform::from_translation(chunk_pos.as_vec3())
+                .with_scale(Vec3::splat(CHUNK_SIZE as f32)),
+            color,
+        );
+    }
+}
+
+fn draw_face_normals(
+    mut gizmos: Gizmos,
+    meshes: Res<Assets<Mesh>>,
+    player_q: Query<&Transform, With<Player>>,
+    layers_q: Query<(&Mesh3d, &GlobalTransform), With<ChunkLayer>>,
+) {
+    let Ok(player) = player_q.get_single() else {
+        return;
+    };
+    for (Mesh3d(handle), transform) in &layers_q {
+        let Some(mesh) = meshes.get(handle) else {
+            continue;
+        };
+        let (Some(positions), Some(normals)) = (
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+                .and_then(|positions| positions.as_float3()),
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+                .and_then(|normals| normals.as_float3()),
+        ) else {
+            continue;
+        };
+        // Chunk meshes are made of quads, four vertices each.
+        for (quad, normal) in positions.chunks_exact(4).zip(normals.iter().step_by(4)) {
+            let center = quad.iter().map(|p| Vec3::from_array(*p)).sum::<Vec3>() / 4.0;
+            let center = transform.transform_point(center);
+            if center.distance(player.translation) > NORMALS_RADIUS {
+                continue;
+            }
+            let normal = Vec3::from_array(*normal);
+            gizmos.arrow(
+                center,
+                center + normal * NORMAL_LENGTH,
+                Color::srgb(normal.x.abs(), normal.y.abs(), normal.z.abs()),
+            );
+        }
+    }
+}
+
+fn tint_chunks(
+    modes: Res<DebugRenderModes>,
+    game_resources: Res<GameResources>,
+    chunk_map: Res<ChunkMap>,
+    mut materials: ResMut<Assets<BlockMaterial>>,
+    mut tint_materials: ResMut<TintMaterials>,
+    chunks_q: Query<(&ChunkEntity, &ChunkSource, Has<Remesh>, &Children)>,
+    mut layers_q: Query<(&ChunkLayer, &mut MeshMaterial3d<BlockMaterial>)>,
+) {
+    if game_resources.is_changed() {
+        tint_materials.0.clear();
+    }
+    if !modes.generation_tint && !modes.is_changed() {
+        return;
+    }
+    let dirty = chunk_map
+        .chunks()
+        .filter(|(_, chunk)| chunk.is_dirty())
+        .map(|(chunk_pos, _)| *chunk_pos)
+        .collect::<Vec<_>>();
+    for (ChunkEntity(chunk_pos), source, pending, children) in &chunks_q {
+        let state = match (pending, dirty.contains(chunk_pos), source) {
+            (true, _, _) => ChunkState::PendingMesh,
+            (false, true, _) => ChunkState::Unsaved,
+            (false, false, ChunkSource::Generated) => ChunkState::Generated,
+            (false, false, ChunkSource::Loaded) => ChunkState::Loaded,
+            (false, false, ChunkSource::Received) => ChunkState::Received,
+        };
+        for &child in &**children {
+            let Ok((ChunkLayer(transparency), mut material)) = layers_q.get_mut(child) else {
+                continue;
+            };
+            let base = &game_resources.materials[*transparency as usize];
+            let handle = match modes.generation_tint {
+                false => base.clone(),
+                true => {
+                    let Some(base_material) = materials.get(base).cloned() else {
+                        continue;
+                    };
+                    let handle = tint_materials
+                        .0
+                        .entry((state, *transparency))
+                        .or_insert_with(|| {
+                            materials.add(BlockMaterial {
+                                tint: state.color(),
+                                ..base_material.clone()
+                            })
+                        })
+                        .clone();
+                    // Keep the copy in sync with the day/night cycle.
+                    if let Some(tinted) = materials.get(&handle) {
+                        if tinted.daylight != base_material.daylight {
+                            if let Some(tinted) = materials.get_mut(&handle) {
+                                tinted.daylight = base_material.daylight;
+                            }
+                        }
+                    }
+                    handle
+                }
+            };
+            if material.0 != handle {
+                material.0 = handle;
+            }
+        }
+    }
+}
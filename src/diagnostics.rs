@@ -3,18 +3,38 @@ use bevy::{
     prelude::*,
 };
 
-use crate::player::Player;
-
 #[derive(Component)]
 struct DiagnosticsText;
 
+/// Whether the extended debug screen is shown.
+#[derive(Debug, Default, Resource, Deref, DerefMut)]
+pub struct DebugScreen(bool);
+
+/// Text lines subsystems contribute to the debug screen, in the order they
+/// were first set.
+#[derive(Debug, Default, Resource)]
+pub struct DebugInfo {
+    lines: Vec<(&'static str, String)>,
+}
+
+impl DebugInfo {
+    pub fn set(&mut self, label: &'static str, value: String) {
+        match self.lines.iter_mut().find(|(l, _)| *l == label) {
+            Some((_, line)) => *line = value,
+            None => self.lines.push((label, value)),
+        }
+    }
+}
+
 pub struct DiagnosticsPlugin;
 
 impl Plugin for DiagnosticsPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(FrameTimeDiagnosticsPlugin)
+            .init_resource::<DebugScreen>()
+            .init_resource::<DebugInfo>()
             .add_systems(Startup, setup_diagnostics)
-            .add_systems(Update, update_diagnostics);
+            .add_systems(Update, (toggle_debug_screen, update_diagnostics).chain());
     }
 }
 
@@ -33,19 +53,44 @@ fn setup_diagnostics(mut commands: Commands, asset_server: Res<AssetServer>) {
     info!("Finished diagnostics setup");
 }
 
+fn toggle_debug_screen(keyboard: Res<ButtonInput<KeyCode>>, mut debug_screen: ResMut<DebugScreen>) {
+    if keyboard.just_pressed(KeyCode::F3) {
+        **debug_screen = !**debug_screen;
+    }
+}
+
 fn update_diagnostics(
     diagnostics: Res<DiagnosticsStore>,
-    player_q: Query<&Transform, With<Player>>,
+    debug_screen: Res<DebugScreen>,
+    debug_info: Res<DebugInfo>,
     mut text_q: Query<&mut Text, With<DiagnosticsText>>,
 ) {
     let mut s = String::new();
-    if let Ok(transform) = player_q.get_single() {
-        s += format!("Position - {}, ", transform.translation).as_str();
-    };
     if let Some(fps) = diagnostics.get(&FrameTimeDiagnosticsPlugin::FPS) {
         if let Some(value) = fps.smoothed() {
-            s += format!("FPS: - {value:.3}, ").as_str();
+            s += format!("FPS: - {value:.3}").as_str();
+        }
+    }
+    if **debug_screen {
+        s += "\n";
+        for (label, value) in &debug_info.lines {
+            s += format!("{label}: {value}\n").as_str();
+        }
+        let mut measured = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.is_enabled)
+            .filter_map(|diagnostic| {
+                diagnostic
+                    .smoothed()
+                    .map(|value| (diagnostic.path().as_str(), value, &diagnostic.suffix))
+            })
+            .collect::<Vec<_>>();
+        measured.sort_by_key(|(path, _, _)| *path);
+        for (path, value, suffix) in measured {
+            s += format!("{path}: {value:.2}{suffix}\n").as_str();
         }
+    } else {
+        s += " (F3 for debug screen)";
     }
     if let Ok(mut text) = text_q.get_single_mut() {
         **text = s;
@@ -0,0 +1,66 @@
+use bevy::{prelude::*, utils::hashbrown::HashMap};
+use noise::NoiseFn;
+use std::sync::Arc;
+
+use crate::block::Block;
+use crate::chunk::Chunk;
+
+/// Seed of the world being played, whether it is stored locally or hosted by
+/// a server.
+#[derive(Debug, Clone, Copy, Resource, Deref)]
+pub struct WorldSeed(pub u32);
+
+/// Grid height of the surface block of the generated column at `x`, `z`.
+pub fn terrain_height(noise: &noise::Perlin, x: i32, z: i32) -> i32 {
+    let scale = 0.015;
+    (noise.get([x as f64 * scale, z as f64 * scale]) * 64.0).round() as i32
+}
+
+/// Height range a generated column falls into. The generator has no biomes,
+/// so this is what the debug screen and map overlays show instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeightBand {
+    Lowlands,
+    Plains,
+    Hills,
+    Mountains,
+}
+
+/// Height band of the generated column at `x`, `z`.
+pub fn height_band_at(noise: &noise::Perlin, x: i32, z: i32) -> HeightBand {
+    match terrain_height(noise, x, z) {
+        ..-16 => HeightBand::Lowlands,
+        -16..8 => HeightBand::Plains,
+        8..32 => HeightBand::Hills,
+        _ => HeightBand::Mountains,
+    }
+}
+
+pub fn generate_chunk(
+    pos: Vec3,
+    seed: u32,
+    block_map: Arc<HashMap<String, usize>>,
+    blocks: Arc<Vec<Block>>,
+) -> Chunk {
+    info!("generate_chunk_pos: {pos}");
+    let noise = noise::Perlin::new(seed);
+    let mut chunk = Chunk::new(blocks);
+    for x in 0..16 {
+        for z in 0..16 {
+            let n_y = terrain_height(&noise, x as i32 + pos.x as i32, z as i32 + pos.z as i32);
+            for y in 0..16 {
+                let d = n_y - (y as i32 + pos.y.round() as i32);
+                let block_id = match d {
+                    0 => Some(block_map["grass"]),
+                    1..3 => Some(block_map["dirt"]),
+                    3.. => Some(block_map["stone"]),
+                    _ => None,
+                };
+                chunk.set_at(UVec3::new(x, y, z), block_id);
+            }
+        }
+    }
+    // Generated terrain can be rebuilt from the seed, only edits need saving.
+    chunk.take_dirty();
+    chunk
+}
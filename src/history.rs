@@ -0,0 +1,126 @@
+use bevy::{prelude::*, utils::hashbrown::HashMap};
+use std::collections::VecDeque;
+
+use crate::chunk::{BlockChange, ChunkMap};
+
+/// Change sets kept for undoing.
+const MAX_CHANGE_SETS: usize = 100;
+/// Block changes kept over all change sets. The newest change set is kept
+/// even if it is larger on its own.
+const MAX_CHANGED_BLOCKS: usize = 1 << 20;
+
+/// Undo and redo stacks of a player's block edits, each entry being the
+/// changes of one edit or operation. Kept for as long as the player's session
+/// lasts.
+#[derive(Debug, Default, Component)]
+pub struct EditHistory {
+    undo: VecDeque<Vec<BlockChange>>,
+    redo: Vec<Vec<BlockChange>>,
+    /// Block changes in `undo`.
+    changed_blocks: usize,
+}
+
+impl EditHistory {
+    /// Records changes already applied to the world, forgetting what could
+    /// be redone.
+    pub fn record(&mut self, changes: Vec<BlockChange>) {
+        if changes.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        self.changed_blocks += changes.len();
+        self.undo.push_back(changes);
+        while self.undo.len() > MAX_CHANGE_SETS
+            || (self.changed_blocks > MAX_CHANGED_BLOCKS && self.undo.len() > 1)
+        {
+            if let Some(oldest) = self.undo.pop_front() {
+                self.changed_blocks -= oldest.len();
+            }
+        }
+    }
+
+    /// Reverts the newest change set, or `None` if there is nothing to undo.
+    /// Blocks changed again since, e.g. by other players, are left alone.
+    pub fn undo(&mut self, chunk_map: &mut ChunkMap) -> Option<Reverted> {
+        let changes = self.undo.pop_back()?;
+        self.changed_blocks -= changes.len();
+        let (mut undone, reverted) = apply_unchanged(
+            chunk_map,
+            changes
+                .iter()
+                .rev()
+                .map(|change| (*change, change.new, change.old)),
+        );
+        undone.reverse();
+        if !undone.is_empty() {
+            self.redo.push(undone);
+        }
+        Some(reverted)
+    }
+
+    /// Reapplies the newest undone change set, or `None` if there is nothing
+    /// to redo. Blocks changed again since undoing are left alone.
+    pub fn redo(&mut self, chunk_map: &mut ChunkMap) -> Option<Reverted> {
+        let changes = self.redo.pop()?;
+        let (redone, reverted) = apply_unchanged(
+            chunk_map,
+            changes
+                .iter()
+                .map(|change| (*change, change.old, change.new)),
+        );
+        self.changed_blocks += redone.len();
+        if !redone.is_empty() {
+            self.undo.push_back(redone);
+        }
+        Some(reverted)
+    }
+}
+
+/// Blocks changed by undoing or redoing a change set.
+#[derive(Debug)]
+pub struct Reverted {
+    pub changes: Vec<BlockChange>,
+    /// Blocks left alone because they changed since.
+    pub skipped: usize,
+}
+
+impl Reverted {
+    /// Console reply, e.g. "Undid 3 block changes".
+    pub fn describe(&self, verb: &str) -> String {
+        let mut message = format!("{verb} {} block changes", self.changes.len());
+        if self.skipped > 0 {
+            message += &format!(", skipped {} changed since", self.skipped);
+        }
+        message
+    }
+}
+
+/// Sets each block from the expected to the target block, skipping blocks
+/// that aren't the expected one. Returns the recorded changes that were
+/// applied along with the result.
+fn apply_unchanged(
+    chunk_map: &mut ChunkMap,
+    changes: impl Iterator<Item = (BlockChange, Option<usize>, Option<usize>)>,
+) -> (Vec<BlockChange>, Reverted) {
+    // Blocks as they will be once the edits are applied, so a position
+    // changed more than once in a set is checked against its earlier change.
+    let mut pending = HashMap::new();
+    let mut applied = Vec::new();
+    let mut edits = Vec::new();
+    let mut skipped = 0;
+    for (change, expected, target) in changes {
+        let current = pending
+            .get(&change.pos)
+            .copied()
+            .unwrap_or_else(|| chunk_map.block_at(change.pos));
+        if current != expected {
+            skipped += 1;
+            continue;
+        }
+        pending.insert(change.pos, target);
+        applied.push(change);
+        edits.push((change.pos, target));
+    }
+    let changes = chunk_map.set_blocks(edits);
+    (applied, Reverted { changes, skipped })
+}
@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+use nipahblocks::chunk::{BlockChange, ChunkMap};
+use nipahblocks::command::GameMode;
+use nipahblocks::persistence::UNKNOWN_BLOCK;
+use nipahblocks::raycast::raycast;
+
+use crate::diagnostics::DebugInfo;
+use crate::inventory::{Inventory, SelectedBlock};
+use crate::player::{collides_with_block, Player};
+use crate::{GameResources, GameState};
+
+const REACH_DISTANCE: f32 = 6.0;
+
+pub struct InteractionPlugin;
+
+impl Plugin for InteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BlocksEdited>().add_systems(
+            Update,
+            (edit_blocks, target_debug_info)
+                .run_if(in_state(GameState::InGame).and(resource_exists::<GameResources>)),
+        );
+    }
+}
+
+/// Blocks the player changed with one edit, already applied to the local
+/// [`ChunkMap`].
+#[derive(Debug, Event, Deref)]
+pub struct BlocksEdited(pub Vec<BlockChange>);
+
+/// Breaks the targeted block into the player's inventory, or places the
+/// selected block against it.
+fn edit_blocks(
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut chunk_map: ResMut<ChunkMap>,
+    game_resources: Res<GameResources>,
+    selected_block: Res<SelectedBlock>,
+    mut edits: EventWriter<BlocksEdited>,
+    mut player_q: Query<(&Transform, &GameMode, &mut Inventory), With<Player>>,
+) {
+    let breaking = mouse.just_pressed(MouseButton::Left);
+    let placing = mouse.just_pressed(MouseButton::Right);
+    if !breaking && !placing {
+        return;
+    }
+    let Ok((transform, game_mode, mut inventory)) = player_q.get_single_mut() else {
+        return;
+    };
+    if *game_mode == GameMode::Spectator {
+        return;
+    }
+    let Some(hit) = raycast(
+        &chunk_map,
+        transform.translation,
+        *transform.forward(),
+        REACH_DISTANCE,
+    ) else {
+        return;
+    };
+    if breaking {
+        let changes = chunk_map.set_blocks([(hit.block_pos, None)]);
+        for change in &changes {
+            let name = change.old.and_then(|id| game_resources.block_names.get(id));
+            // Blocks that don't fit into the inventory are lost.
+            if let Some(name) = name.filter(|name| name.as_str() != UNKNOWN_BLOCK) {
+                inventory.add(name);
+            }
+        }
+        edits.send(BlocksEdited(changes));
+        return;
+    }
+    let pos = hit.block_pos + hit.normal;
+    let Some(block) = **selected_block else {
+        return;
+    };
+    if hit.normal == IVec3::ZERO || chunk_map.block_at(pos).is_some() {
+        return;
+    }
+    // Don't place blocks inside the player.
+    let Some(block_info) = game_resources.blocks.get(block) else {
+        return;
+    };
+    if collides_with_block(transform.translation, pos, block_info) {
+        return;
+    }
+    let changes = chunk_map.set_blocks([(pos, Some(block))]);
+    inventory.remove_selected();
+    edits.send(BlocksEdited(changes));
+}
+
+fn target_debug_info(
+    mut debug_info: ResMut<DebugInfo>,
+    chunk_map: Res<ChunkMap>,
+    game_resources: Res<GameResources>,
+    player_q: Query<&Transform, With<Player>>,
+) {
+    let Ok(transform) = player_q.get_single() else {
+        return;
+    };
+    let target = raycast(
+        &chunk_map,
+        transform.translation,
+        *transform.forward(),
+        REACH_DISTANCE,
+    )
+    .and_then(|hit| {
+        let id = chunk_map.block_at(hit.block_pos)?;
+        let name = game_resources.block_names.get(id)?;
+        Some(format!("{name} at {}", hit.block_pos))
+    });
+    debug_info.set("Target", target.unwrap_or_else(|| "none".to_string()));
+}
@@ -0,0 +1,360 @@
+use bevy::{input::mouse::AccumulatedMouseScroll, prelude::*};
+use nipahblocks::persistence::UNKNOWN_BLOCK;
+
+use crate::player::Player;
+use crate::{GameResources, GameState};
+
+pub const HOTBAR_SLOTS: usize = 9;
+/// Slots including the hotbar, which comes first.
+pub const INVENTORY_SLOTS: usize = HOTBAR_SLOTS * 4;
+pub const MAX_STACK: u32 = 64;
+
+const SLOT_SIZE: f32 = 48.0;
+const SLOT_COLOR: Color = Color::srgba(0.0, 0.0, 0.0, 0.5);
+const BORDER_COLOR: Color = Color::srgb(0.4, 0.4, 0.4);
+const SELECTED_BORDER_COLOR: Color = Color::WHITE;
+const DIGIT_KEYS: [KeyCode; HOTBAR_SLOTS] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SelectedBlock>()
+            .add_systems(Startup, spawn_hotbar)
+            .add_systems(
+                Update,
+                (
+                    stock_inventory.run_if(resource_added::<GameResources>),
+                    select_slot,
+                    update_selected_block,
+                    update_hotbar,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame).and(resource_exists::<GameResources>)),
+            );
+    }
+}
+
+/// Blocks of one kind in an inventory slot. Blocks are kept by name so
+/// stacks survive the block registry being reloaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemStack {
+    pub block: String,
+    pub count: u32,
+}
+
+#[derive(Debug, Component)]
+pub struct Inventory {
+    pub slots: [Option<ItemStack>; INVENTORY_SLOTS],
+    /// Index of the selected hotbar slot.
+    pub selected: usize,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self {
+            slots: std::array::from_fn(|_| None),
+            selected: 0,
+        }
+    }
+}
+
+impl Inventory {
+    /// Adds one `block` to the first stack of it with room, or else the first
+    /// empty slot. Returns `false` if the inventory is full.
+    pub fn add(&mut self, block: &str) -> bool {
+        let stack = self
+            .slots
+            .iter_mut()
+            .flatten()
+            .find(|stack| stack.block == block && stack.count < MAX_STACK);
+        if let Some(stack) = stack {
+            stack.count += 1;
+            return true;
+        }
+        let Some(slot) = self.slots.iter_mut().find(|slot| slot.is_none()) else {
+            return false;
+        };
+        *slot = Some(ItemStack {
+            block: block.to_string(),
+            count: 1,
+        });
+        true
+    }
+
+    pub fn selected_stack(&self) -> Option<&ItemStack> {
+        self.slots[self.selected].as_ref()
+    }
+
+    /// Takes one block from the selected stack, emptying the slot once none
+    /// are left.
+    pub fn remove_selected(&mut self) {
+        let slot = &mut self.slots[self.selected];
+        if let Some(stack) = slot {
+            stack.count -= 1;
+            if stack.count == 0 {
+                *slot = None;
+            }
+        }
+    }
+}
+
+/// Registry index of the block in the player's selected hotbar slot, `None`
+/// if it is empty.
+#[derive(Debug, Default, Resource, Deref, PartialEq, Eq)]
+pub struct SelectedBlock(pub Option<usize>);
+
+#[derive(Debug, Component)]
+struct HotbarSlot(usize);
+
+#[derive(Debug, Component)]
+struct HotbarIcon(usize);
+
+#[derive(Debug, Component)]
+struct HotbarCount(usize);
+
+fn spawn_hotbar(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/RobotoMono-Regular.ttf");
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(8.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            column_gap: Val::Px(4.0),
+            ..default()
+        })
+        .with_children(|parent| {
+            for i in 0..HOTBAR_SLOTS {
+                parent
+                    .spawn((
+                        HotbarSlot(i),
+                        Node {
+                            width: Val::Px(SLOT_SIZE),
+                            height: Val::Px(SLOT_SIZE),
+                            border: UiRect::all(Val::Px(2.0)),
+                            padding: UiRect::all(Val::Px(4.0)),
+                            ..default()
+                        },
+                        BorderColor(BORDER_COLOR),
+                        BackgroundColor(SLOT_COLOR),
+                    ))
+                    .with_children(|slot| {
+                        slot.spawn((
+                            HotbarIcon(i),
+                            ImageNode::default(),
+                            Node {
+                                width: Val::Percent(100.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            Visibility::Hidden,
+                        ));
+                        slot.spawn((
+                            HotbarCount(i),
+                            Text::default(),
+                            TextColor(Color::WHITE),
+                            TextFont {
+                                font: font.clone(),
+                                font_size: 14.0,
+                                ..default()
+                            },
+                            Node {
+                                position_type: PositionType::Absolute,
+                                right: Val::Px(2.0),
+                                bottom: Val::Px(0.0),
+                                ..default()
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+/// Gives the player a full stack of every block once the registry is loaded.
+fn stock_inventory(
+    game_resources: Res<GameResources>,
+    mut player_q: Query<&mut Inventory, With<Player>>,
+) {
+    let Ok(mut inventory) = player_q.get_single_mut() else {
+        return;
+    };
+    let blocks = game_resources
+        .block_names
+        .iter()
+        .filter(|name| name.as_str() != UNKNOWN_BLOCK);
+    for (slot, block) in inventory.slots.iter_mut().zip(blocks) {
+        *slot = Some(ItemStack {
+            block: block.clone(),
+            count: MAX_STACK,
+        });
+    }
+}
+
+fn select_slot(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    scroll: Res<AccumulatedMouseScroll>,
+    mut player_q: Query<&mut Inventory, With<Player>>,
+) {
+    let Ok(mut inventory) = player_q.get_single_mut() else {
+        return;
+    };
+    if let Some(slot) = DIGIT_KEYS
+        .iter()
+        .position(|&key| keyboard.just_pressed(key))
+    {
+        inventory.selected = slot;
+    }
+    // Scrolling down selects the next slot to the right.
+    let step = match scroll.delta.y {
+        y if y < 0.0 => 1,
+        y if y > 0.0 => HOTBAR_SLOTS - 1,
+        _ => return,
+    };
+    inventory.selected = (inventory.selected + step) % HOTBAR_SLOTS;
+}
+
+fn update_selected_block(
+    game_resources: Res<GameResources>,
+    mut selected_block: ResMut<SelectedBlock>,
+    player_q: Query<&Inventory, With<Player>>,
+) {
+    let Ok(inventory) = player_q.get_single() else {
+        return;
+    };
+    let block = inventory
+        .selected_stack()
+        .and_then(|stack| game_resources.blocks_map.get(&stack.block).copied());
+    selected_block.set_if_neq(SelectedBlock(block));
+}
+
+fn update_hotbar(
+    game_resources: Res<GameResources>,
+    player_q: Query<Ref<Inventory>, With<Player>>,
+    mut slot_q: Query<(&HotbarSlot, &mut BorderColor)>,
+    mut icon_q: Query<(&HotbarIcon, &mut ImageNode, &mut Visibility)>,
+    mut count_q: Query<(&HotbarCount, &mut Text)>,
+) {
+    let Ok(inventory) = player_q.get_single() else {
+        return;
+    };
+    if !inventory.is_changed() && !game_resources.is_changed() {
+        return;
+    }
+    for (HotbarSlot(i), mut border) in &mut slot_q {
+        border.0 = match *i == inventory.selected {
+            true => SELECTED_BORDER_COLOR,
+            false => BORDER_COLOR,
+        };
+    }
+    for (HotbarIcon(i), mut image, mut visibility) in &mut icon_q {
+        let icon = inventory.slots[*i]
+            .as_ref()
+            .and_then(|stack| game_resources.blocks_map.get(&stack.block))
+            .map(|&id| game_resources.icons[id].clone());
+        *visibility = match icon {
+            Some(icon) => {
+                image.image = icon;
+                Visibility::Inherited
+            }
+            None => Visibility::Hidden,
+        };
+    }
+    for (HotbarCount(i), mut text) in &mut count_q {
+        text.0 = match &inventory.slots[*i] {
+            Some(stack) if stack.count > 1 => stack.count.to_string(),
+            _ => String::new(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_merge_into_stacks_with_room() {
+        let mut inventory = Inventory::default();
+        inventory.slots[2] = Some(ItemStack {
+            block: "stone".to_string(),
+            count: 10,
+        });
+        assert!(inventory.add("stone"));
+        assert_eq!(inventory.slots[2].as_ref().unwrap().count, 11);
+        assert!(inventory.slots[0].is_none());
+
+        assert!(inventory.add("dirt"));
+        assert_eq!(
+            inventory.slots[0],
+            Some(ItemStack {
+                block: "dirt".to_string(),
+                count: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn full_stacks_spill_into_the_first_empty_slot() {
+        let mut inventory = Inventory::default();
+        inventory.slots[1] = Some(ItemStack {
+            block: "stone".to_string(),
+            count: MAX_STACK,
+        });
+        assert!(inventory.add("stone"));
+        assert_eq!(inventory.slots[1].as_ref().unwrap().count, MAX_STACK);
+        assert_eq!(
+            inventory.slots[0],
+            Some(ItemStack {
+                block: "stone".to_string(),
+                count: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn full_inventory_rejects_blocks() {
+        let mut inventory = Inventory::default();
+        for slot in &mut inventory.slots {
+            *slot = Some(ItemStack {
+                block: "stone".to_string(),
+                count: MAX_STACK,
+            });
+        }
+        assert!(!inventory.add("stone"));
+        assert!(!inventory.add("dirt"));
+        assert_eq!(inventory.slots.len(), INVENTORY_SLOTS);
+        assert!(inventory
+            .slots
+            .iter()
+            .all(|slot| slot.as_ref().unwrap().count == MAX_STACK));
+    }
+
+    #[test]
+    fn removing_the_last_block_empties_the_slot() {
+        let mut inventory = Inventory {
+            selected: 3,
+            ..default()
+        };
+        inventory.slots[3] = Some(ItemStack {
+            block: "glass".to_string(),
+            count: 2,
+        });
+        inventory.remove_selected();
+        assert_eq!(inventory.selected_stack().unwrap().count, 1);
+        inventory.remove_selected();
+        assert!(inventory.selected_stack().is_none());
+        // Nothing left to take.
+        inventory.remove_selected();
+        assert!(inventory.slots[3].is_none());
+    }
+}
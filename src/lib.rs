@@ -0,0 +1,20 @@
+//! World model shared by the game and headless tools: block registry, chunk
+//! storage, terrain generation, lighting, raycasts and world persistence.
+//! Nothing in here needs a window or a GPU.
+
+pub mod block;
+pub mod block_registry;
+pub mod chunk;
+pub mod command;
+pub mod connectivity;
+pub mod generator;
+pub mod history;
+pub mod light;
+pub mod net;
+pub mod persistence;
+pub mod protocol;
+pub mod raycast;
+pub mod schematic;
+pub mod vox;
+pub mod world_edit;
+pub mod world_time;
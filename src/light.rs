@@ -0,0 +1,203 @@
+use bevy::{prelude::*, utils::hashbrown::HashSet};
+use std::collections::VecDeque;
+
+use crate::chunk::{ChunkMap, CHUNK_SIZE};
+
+pub const MAX_LIGHT: u8 = 15;
+
+const NEIGHBORS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightChannel {
+    Sky,
+    Block,
+}
+
+impl LightChannel {
+    pub const ALL: [LightChannel; 2] = [LightChannel::Sky, LightChannel::Block];
+}
+
+/// Level `from` passes on to the neighbor in direction `dir`. Full skylight
+/// travels straight down without falling off.
+fn spread_level(channel: LightChannel, dir: IVec3, from: u8) -> u8 {
+    match channel {
+        LightChannel::Sky if dir == IVec3::NEG_Y && from == MAX_LIGHT => MAX_LIGHT,
+        _ => from.saturating_sub(1),
+    }
+}
+
+/// Light a cell emits by itself, regardless of its neighbors.
+fn source_level(chunk_map: &ChunkMap, channel: LightChannel, pos: IVec3) -> u8 {
+    match channel {
+        LightChannel::Block => chunk_map.light_emission_at(pos),
+        // Cells below an unloaded chunk are assumed to see the open sky.
+        LightChannel::Sky => match chunk_map.transmits_light(pos) == Some(true)
+            && chunk_map.transmits_light(pos + IVec3::Y).is_none()
+        {
+            true => MAX_LIGHT,
+            false => 0,
+        },
+    }
+}
+
+fn spread(
+    chunk_map: &mut ChunkMap,
+    channel: LightChannel,
+    mut queue: VecDeque<IVec3>,
+    changed: &mut HashSet<IVec3>,
+) {
+    while let Some(pos) = queue.pop_front() {
+        let Some(level) = chunk_map.light_at(pos, channel) else {
+            continue;
+        };
+        for dir in NEIGHBORS {
+            let neighbor = pos + dir;
+            if chunk_map.transmits_light(neighbor) != Some(true) {
+                continue;
+            }
+            let target = spread_level(channel, dir, level);
+            if chunk_map.light_at(neighbor, channel).unwrap_or(MAX_LIGHT) < target {
+                chunk_map.set_light_at(neighbor, channel, target);
+                changed.insert(ChunkMap::chunk_pos(neighbor));
+                queue.push_back(neighbor);
+            }
+        }
+    }
+}
+
+/// Clears light that depended on `start` cells and relights the cleared area
+/// from the remaining sources and the untouched light around it.
+fn relight(
+    chunk_map: &mut ChunkMap,
+    channel: LightChannel,
+    start: impl IntoIterator<Item = IVec3>,
+    changed: &mut HashSet<IVec3>,
+) {
+    let mut removal = VecDeque::new();
+    let mut cleared = Vec::new();
+    for pos in start {
+        if let Some(level) = chunk_map.light_at(pos, channel) {
+            chunk_map.set_light_at(pos, channel, 0);
+            changed.insert(ChunkMap::chunk_pos(pos));
+            removal.push_back((pos, level));
+            cleared.push(pos);
+        }
+    }
+    let mut queue = VecDeque::new();
+    while let Some((pos, level)) = removal.pop_front() {
+        for dir in NEIGHBORS {
+            let neighbor = pos + dir;
+            let Some(neighbor_level) = chunk_map.light_at(neighbor, channel) else {
+                continue;
+            };
+            if neighbor_level == 0 {
+                continue;
+            }
+            if neighbor_level <= spread_level(channel, dir, level) {
+                chunk_map.set_light_at(neighbor, channel, 0);
+                changed.insert(ChunkMap::chunk_pos(neighbor));
+                removal.push_back((neighbor, neighbor_level));
+                cleared.push(neighbor);
+            } else {
+                queue.push_back(neighbor);
+            }
+        }
+    }
+    for pos in cleared {
+        let level = source_level(chunk_map, channel, pos);
+        if level > 0 {
+            chunk_map.set_light_at(pos, channel, level);
+            queue.push_back(pos);
+        }
+    }
+    spread(chunk_map, channel, queue, changed);
+}
+
+/// Computes light for a freshly inserted chunk and spreads it across loaded
+/// neighbors. Returns positions of chunks whose light changed.
+pub fn light_chunk(chunk_map: &mut ChunkMap, chunk_pos: IVec3) -> HashSet<IVec3> {
+    let size = CHUNK_SIZE as i32;
+    let mut changed = HashSet::from([chunk_pos]);
+    let mut sky_queue = VecDeque::new();
+    let mut block_queue = VecDeque::new();
+    let mut shadowed = Vec::new();
+    for x in 0..size {
+        for z in 0..size {
+            let top = chunk_pos + IVec3::new(x, size, z);
+            let mut level = match chunk_map.light_at(top, LightChannel::Sky) {
+                Some(MAX_LIGHT) | None => MAX_LIGHT,
+                Some(_) => 0,
+            };
+            for y in (0..size).rev() {
+                let pos = chunk_pos + IVec3::new(x, y, z);
+                if chunk_map.transmits_light(pos) != Some(true) {
+                    level = 0;
+                }
+                if level == MAX_LIGHT {
+                    chunk_map.set_light_at(pos, LightChannel::Sky, MAX_LIGHT);
+                    sky_queue.push_back(pos);
+                }
+                let emission = chunk_map.light_emission_at(pos);
+                if emission > 0 {
+                    chunk_map.set_light_at(pos, LightChannel::Block, emission);
+                    block_queue.push_back(pos);
+                }
+            }
+            // A chunk below that assumed open sky is now shadowed by this one.
+            let below = chunk_pos + IVec3::new(x, -1, z);
+            if level < MAX_LIGHT && chunk_map.light_at(below, LightChannel::Sky) == Some(MAX_LIGHT)
+            {
+                shadowed.push(below);
+            }
+        }
+    }
+    // Let light already present in loaded neighbors flow into the new chunk.
+    for dir in NEIGHBORS {
+        for a in 0..size {
+            for b in 0..size {
+                let local = match dir {
+                    IVec3::X => IVec3::new(size, a, b),
+                    IVec3::NEG_X => IVec3::new(-1, a, b),
+                    IVec3::Y => IVec3::new(a, size, b),
+                    IVec3::NEG_Y => IVec3::new(a, -1, b),
+                    IVec3::Z => IVec3::new(a, b, size),
+                    _ => IVec3::new(a, b, -1),
+                };
+                let pos = chunk_pos + local;
+                if chunk_map.light_at(pos, LightChannel::Sky).unwrap_or(0) > 0 {
+                    sky_queue.push_back(pos);
+                }
+                if chunk_map.light_at(pos, LightChannel::Block).unwrap_or(0) > 0 {
+                    block_queue.push_back(pos);
+                }
+            }
+        }
+    }
+    spread(chunk_map, LightChannel::Sky, sky_queue, &mut changed);
+    spread(chunk_map, LightChannel::Block, block_queue, &mut changed);
+    relight(chunk_map, LightChannel::Sky, shadowed, &mut changed);
+    changed
+}
+
+/// Updates light around a block that was just placed or removed. Returns
+/// positions of chunks whose light changed.
+pub fn relight_block(chunk_map: &mut ChunkMap, pos: IVec3) -> HashSet<IVec3> {
+    relight_blocks(chunk_map, &[pos])
+}
+
+/// Like [`relight_block`] for many blocks, relighting the area around all of
+/// them in one pass.
+pub fn relight_blocks(chunk_map: &mut ChunkMap, positions: &[IVec3]) -> HashSet<IVec3> {
+    let mut changed = HashSet::new();
+    for channel in LightChannel::ALL {
+        relight(chunk_map, channel, positions.iter().copied(), &mut changed);
+    }
+    changed
+}
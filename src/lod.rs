@@ -0,0 +1,271 @@
+use bevy::{prelude::*, utils::hashbrown::HashMap};
+use nipahblocks::block::{Block, Face, MeshData};
+use nipahblocks::block_registry::Transparency;
+use nipahblocks::chunk::{world_to_block, Chunk, ChunkMap, CHUNK_SIZE};
+use nipahblocks::generator::{terrain_height, WorldSeed};
+use nipahblocks::persistence::WorldSave;
+use std::sync::Arc;
+
+use crate::chunks::DRAW_DISTANCE;
+use crate::meshing::into_mesh;
+use crate::player::Player;
+use crate::{GameResources, GameState};
+
+/// Radius in chunks up to which terrain is drawn as LOD tiles.
+pub const LOD_DISTANCE: u32 = 16;
+const LOD_TILES_PER_FRAME: usize = 16;
+const SURFACE_BLOCK: &str = "grass";
+const SIDE_SHADE: f32 = 0.8;
+/// Highest and lowest chunk layer searched for the surface of saved terrain.
+const MAX_LAYER: i32 = 7;
+const MIN_LAYER: i32 = -8;
+
+pub struct LodPlugin;
+
+impl Plugin for LodPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LodTiles>().add_systems(
+            Update,
+            (
+                clear_lod_tiles.run_if(resource_exists_and_changed::<GameResources>),
+                update_lod_tiles,
+            )
+                .chain()
+                .run_if(in_state(GameState::InGame).and(resource_exists::<WorldSeed>)),
+        );
+    }
+}
+
+/// Spawned tiles by chunk column, with their LOD level.
+#[derive(Debug, Default, Resource)]
+struct LodTiles(HashMap<IVec3, (u32, Entity)>);
+
+#[derive(Debug, Component)]
+struct LodTile;
+
+/// LOD level of a column `ring` chunks away, each level doubling the cell size.
+/// Columns within the draw distance are full-detail chunks.
+fn lod_level(ring: u32) -> Option<u32> {
+    match ring {
+        r if r <= DRAW_DISTANCE => None,
+        r if r <= DRAW_DISTANCE * 2 => Some(1),
+        r if r <= DRAW_DISTANCE * 4 => Some(2),
+        r if r <= LOD_DISTANCE => Some(3),
+        _ => None,
+    }
+}
+
+fn clear_lod_tiles(mut commands: Commands, mut lod_tiles: ResMut<LodTiles>) {
+    for (_, entity) in lod_tiles.0.drain().map(|(_, tile)| tile) {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Finds surface heights in loaded or saved chunks, falling back to the
+/// terrain generator where chunks were never generated.
+struct SurfaceSampler<'a> {
+    chunk_map: &'a ChunkMap,
+    world_save: Option<&'a WorldSave>,
+    blocks: Arc<Vec<Block>>,
+    noise: noise::Perlin,
+    /// Chunks read from the save, `None` if it doesn't have them.
+    saved: HashMap<IVec3, Option<Chunk>>,
+}
+
+impl SurfaceSampler<'_> {
+    fn chunk(&mut self, chunk_pos: IVec3) -> Option<&Chunk> {
+        if let Some(chunk) = self.chunk_map.chunk(chunk_pos) {
+            return Some(chunk);
+        }
+        let Self {
+            world_save,
+            blocks,
+            saved,
+            ..
+        } = self;
+        saved
+            .entry(chunk_pos)
+            .or_insert_with(|| {
+                (*world_save)?
+                    .load_chunk(chunk_pos, blocks.clone())
+                    .unwrap_or_else(|e| {
+                        error!("Failed to load chunk {chunk_pos} for LOD: {e}");
+                        None
+                    })
+            })
+            .as_ref()
+    }
+
+    /// Grid height of the topmost block of the column at `x`, `z`.
+    fn height(&mut self, x: i32, z: i32) -> i32 {
+        let generated = terrain_height(&self.noise, x, z);
+        let size = CHUNK_SIZE as i32;
+        for layer in (MIN_LAYER..=MAX_LAYER).rev() {
+            let chunk_pos = ChunkMap::chunk_pos(IVec3::new(x, layer * size, z));
+            let local = IVec3::new(x, 0, z) - chunk_pos * IVec3::new(1, 0, 1);
+            let Some(chunk) = self.chunk(chunk_pos) else {
+                if (chunk_pos.y..chunk_pos.y + size).contains(&generated) {
+                    return generated;
+                }
+                continue;
+            };
+            let top = (0..CHUNK_SIZE).rev().find(|&y| {
+                chunk
+                    .at(UVec3::new(local.x as u32, y, local.z as u32))
+                    .is_some()
+            });
+            if let Some(y) = top {
+                return chunk_pos.y + y as i32;
+            }
+        }
+        generated
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_lod_tiles(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut lod_tiles: ResMut<LodTiles>,
+    game_resources: Res<GameResources>,
+    chunk_map: Res<ChunkMap>,
+    world_save: Option<Res<WorldSave>>,
+    seed: Res<WorldSeed>,
+    player_q: Query<&Transform, With<Player>>,
+) {
+    let Ok(transform) = player_q.get_single() else {
+        return;
+    };
+    let size = CHUNK_SIZE as i32;
+    let center = ChunkMap::chunk_pos(world_to_block(transform.translation)) * IVec3::new(1, 0, 1);
+    let wanted = |column: IVec3| lod_level(((column - center) / size).abs().max_element() as u32);
+    let LodTiles(tiles) = lod_tiles.as_mut();
+    tiles.retain(|column, (level, entity)| {
+        let keep = wanted(*column) == Some(*level);
+        if !keep {
+            commands.entity(*entity).despawn();
+        }
+        keep
+    });
+
+    let Some(surface) = game_resources
+        .blocks_map
+        .get(SURFACE_BLOCK)
+        .and_then(|&id| game_resources.blocks.get(id))
+    else {
+        return;
+    };
+    let mut sampler = SurfaceSampler {
+        chunk_map: &chunk_map,
+        world_save: world_save.as_deref(),
+        blocks: game_resources.blocks.clone(),
+        noise: noise::Perlin::new(**seed),
+        saved: HashMap::new(),
+    };
+    let radius = LOD_DISTANCE as i32;
+    let mut missing = (-radius..=radius)
+        .flat_map(|x| (-radius..=radius).map(move |z| center + IVec3::new(x, 0, z) * size))
+        .filter(|column| !tiles.contains_key(column))
+        .filter_map(|column| wanted(column).map(|level| (column, level)))
+        .collect::<Vec<_>>();
+    missing.sort_by_key(|(column, _)| (*column - center).abs().max_element());
+    for (column, level) in missing.into_iter().take(LOD_TILES_PER_FRAME) {
+        // Cells of the tile and the ones bordering it.
+        let step = 1 << level;
+        let cells = CHUNK_SIZE as i32 / step;
+        let heights = (-1..=cells)
+            .flat_map(|i| (-1..=cells).map(move |j| IVec2::new(i, j)))
+            .map(|cell| {
+                let pos = column.xz() + cell * step;
+                (pos, sampler.height(pos.x, pos.y))
+            })
+            .collect::<HashMap<_, _>>();
+        let heights = |x: i32, z: i32| heights[&IVec2::new(x, z)];
+        let mesh = build_tile(
+            column,
+            step,
+            heights,
+            surface.texture(Face::Top),
+            surface.texture(Face::Front),
+        );
+        let entity = commands
+            .spawn((
+                LodTile,
+                Mesh3d(meshes.add(into_mesh(mesh))),
+                MeshMaterial3d(game_resources.materials[Transparency::Opaque as usize].clone()),
+                Transform::default(),
+            ))
+            .id();
+        tiles.insert(column, (level, entity));
+    }
+}
+
+/// Builds a heightmap impostor of the chunk column at `column` from cells of
+/// `step` blocks. Every wall is extended one cell below its lower neighbor so
+/// the cracks between tiles of different levels stay covered.
+fn build_tile(
+    column: IVec3,
+    step: i32,
+    heights: impl Fn(i32, i32) -> i32,
+    top_texture: u32,
+    side_texture: u32,
+) -> MeshData {
+    let mut mesh = MeshData::default();
+    let cells = CHUNK_SIZE as i32 / step;
+    // Grid block `g` spans world `g - 8..g - 7`, so its top face lies at `g - 7`.
+    let offset = CHUNK_SIZE as f32 / 2.0;
+    let cell_height = |i: i32, j: i32| heights(column.x + i * step, column.z + j * step);
+    let top_uvs = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+    let side_uvs = [[1.0, 1.0], [1.0, 0.0], [0.0, 0.0], [0.0, 1.0]];
+    let half = step as f32 / 2.0;
+    for i in 0..cells {
+        for j in 0..cells {
+            let height = cell_height(i, j);
+            let top = (height + 1) as f32 - offset;
+            let min = Vec3::new(
+                (column.x + i * step) as f32 - offset,
+                top,
+                (column.z + j * step) as f32 - offset,
+            );
+            let step_f = step as f32;
+            mesh.push_quad(
+                [
+                    min + Vec3::Z * step_f,
+                    min + Vec3::new(step_f, 0.0, step_f),
+                    min + Vec3::X * step_f,
+                    min,
+                ],
+                Vec3::Y,
+                top_uvs,
+                top_texture,
+                [[1.0, 0.0, 1.0, 1.0]; 4],
+            );
+            let center = min + Vec3::new(half, 0.0, half);
+            for face in [Face::Front, Face::Back, Face::Right, Face::Left] {
+                let dir = face.normal();
+                let neighbor = IVec2::new(i + dir.x, j + dir.z);
+                let inside =
+                    neighbor.cmpge(IVec2::ZERO).all() && neighbor.cmplt(IVec2::splat(cells)).all();
+                let neighbor_height = cell_height(neighbor.x, neighbor.y);
+                let bottom = match inside {
+                    true if neighbor_height >= height => continue,
+                    true => (neighbor_height + 1) as f32 - offset,
+                    false => (neighbor_height.min(height) + 1 - step) as f32 - offset,
+                };
+                let normal = dir.as_vec3();
+                let tangent = normal.cross(Vec3::Y);
+                let origin = Vec3::new(center.x, bottom, center.z) + normal * half - tangent * half;
+                let up = Vec3::Y * (top - bottom);
+                let across = tangent * step_f;
+                mesh.push_quad(
+                    [origin, origin + up, origin + up + across, origin + across],
+                    normal,
+                    side_uvs,
+                    side_texture,
+                    [[1.0, 0.0, SIDE_SHADE, 1.0]; 4],
+                );
+            }
+        }
+    }
+    mesh
+}
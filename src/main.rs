@@ -11,22 +11,49 @@ use bevy::{
     utils::hashbrown::HashMap,
     window::PresentMode,
 };
-use std::{fs, sync::Arc};
+use clap::{value_parser, Arg, Command};
+use nipahblocks::block::Block;
+use nipahblocks::block_registry::{BlockInfoRegistry, BlockInfoRegistryLoader, Transparency};
+use std::{net::SocketAddr, path::Path, sync::Arc};
 
-mod block;
-mod block_registry;
-mod chunk;
+mod chunk_queue;
+mod chunks;
+mod commands;
+mod console;
+mod culling;
+mod daylight;
+mod debug_render;
 mod diagnostics;
+mod interaction;
+mod inventory;
+mod lod;
+mod material;
+mod meshing;
+mod network;
 mod player;
+mod remote_player;
+mod save;
+mod selection;
 
-use block::Block;
-use block_registry::BlockInfoRegistry;
-use chunk::ChunksPlugin;
+use chunks::ChunksPlugin;
+use commands::CommandsPlugin;
+use console::ConsolePlugin;
+use culling::CullingPlugin;
+use daylight::DaylightPlugin;
+use debug_render::DebugRenderPlugin;
 use diagnostics::DiagnosticsPlugin;
+use interaction::InteractionPlugin;
+use inventory::InventoryPlugin;
+use lod::LodPlugin;
+use material::{build_texture_array, BlockMaterial};
+use network::{ConnectConfig, NetworkPlugin};
 use player::PlayerPlugin;
+use save::{SaveConfig, SavePlugin};
+use selection::SelectionPlugin;
 
-const BLOCK_INFO_REGISTRY: &str = "assets/block_registry.json";
-const BLOCK_TEXTURES_DIR: &str = "../assets/textures/blocks";
+const BLOCK_INFO_REGISTRY: &str = "block_registry.json";
+const BLOCK_TEXTURES_DIR: &str = "textures/blocks";
+const SAVES_DIR: &str = "saves";
 
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, States)]
 enum GameState {
@@ -38,185 +65,284 @@ enum GameState {
 #[derive(Debug, Resource)]
 struct GameAssets {
     block_textures: Handle<LoadedFolder>,
-    block_registry_json: String,
+    block_registry: Handle<BlockInfoRegistry>,
 }
 
 #[derive(Debug, Resource)]
 pub struct GameResources {
-    material: Handle<StandardMaterial>,
+    materials: [Handle<BlockMaterial>; 3],
     blocks_map: Arc<HashMap<String, usize>>,
+    block_names: Arc<Vec<String>>,
     blocks: Arc<Vec<Block>>,
+    /// Front face texture of each block, shown in the hotbar.
+    icons: Arc<Vec<Handle<Image>>>,
 }
 
-fn main() {
-    App::new()
-        .add_plugins((
-            DefaultPlugins
-                .set(RenderPlugin {
-                    render_creation: RenderCreation::Automatic(WgpuSettings {
-                        features: WgpuFeatures::POLYGON_MODE_LINE,
-                        ..default()
-                    }),
+fn main() -> anyhow::Result<()> {
+    let matches = Command::new("nipahblocks")
+        .arg(
+            Arg::new("world")
+                .long("world")
+                .default_value("world")
+                .help("Name of the world to load or create"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_parser(value_parser!(u32))
+                .help("Seed used when creating a new world"),
+        )
+        .arg(
+            Arg::new("time")
+                .long("time")
+                .value_parser(value_parser!(f32))
+                .help("Time of day in hours to start at"),
+        )
+        .arg(
+            Arg::new("connect")
+                .long("connect")
+                .value_parser(value_parser!(SocketAddr))
+                .help("Address of a server to play on instead of a local world"),
+        )
+        .arg(
+            Arg::new("name")
+                .long("name")
+                .default_value("player")
+                .help("Player name shown to others on a server"),
+        )
+        .get_matches();
+    let save_config = SaveConfig {
+        dir: Path::new(SAVES_DIR).join(matches.get_one::<String>("world").unwrap()),
+        seed: matches.get_one::<u32>("seed").copied(),
+        time: matches.get_one::<f32>("time").copied(),
+    };
+
+    let mut app = App::new();
+    app.add_plugins((
+        DefaultPlugins
+            .set(RenderPlugin {
+                render_creation: RenderCreation::Automatic(WgpuSettings {
+                    features: WgpuFeatures::POLYGON_MODE_LINE,
                     ..default()
-                })
-                .set(WindowPlugin {
-                    primary_window: Some(Window {
-                        present_mode: PresentMode::AutoNoVsync,
-                        ..default()
-                    }),
+                }),
+                ..default()
+            })
+            .set(WindowPlugin {
+                primary_window: Some(Window {
+                    present_mode: PresentMode::AutoNoVsync,
                     ..default()
                 }),
-            DiagnosticsPlugin,
-            PlayerPlugin,
-            ChunksPlugin,
-            WireframePlugin,
-        ))
-        .init_state::<GameState>()
-        .add_systems(OnEnter(GameState::LoadingAssets), load_assets)
-        .add_systems(
-            Update,
-            loading_assets.run_if(in_state(GameState::LoadingAssets)),
-        )
-        .add_systems(OnExit(GameState::LoadingAssets), setup_resources)
-        .add_systems(OnEnter(GameState::InGame), setup)
-        .insert_resource(WireframeConfig {
-            global: false,
-            default_color: Color::WHITE,
-        })
-        .run();
+                ..default()
+            }),
+        DiagnosticsPlugin,
+        ConsolePlugin,
+        CommandsPlugin,
+        PlayerPlugin,
+        InteractionPlugin,
+        InventoryPlugin,
+        ChunksPlugin,
+        CullingPlugin,
+        LodPlugin,
+        DebugRenderPlugin,
+        SelectionPlugin,
+        DaylightPlugin,
+        WireframePlugin,
+        MaterialPlugin::<BlockMaterial>::default(),
+    ))
+    .init_asset::<BlockInfoRegistry>()
+    .init_asset_loader::<BlockInfoRegistryLoader>()
+    .init_state::<GameState>()
+    .add_systems(OnEnter(GameState::LoadingAssets), load_assets)
+    .add_systems(
+        Update,
+        loading_assets.run_if(in_state(GameState::LoadingAssets)),
+    )
+    .add_systems(OnExit(GameState::LoadingAssets), setup_resources)
+    .add_systems(Update, reload_resources.run_if(in_state(GameState::InGame)))
+    .insert_resource(WireframeConfig {
+        global: false,
+        default_color: Color::WHITE,
+    });
+    match matches.get_one::<SocketAddr>("connect") {
+        Some(&addr) => app
+            .add_plugins(NetworkPlugin)
+            .insert_resource(ConnectConfig {
+                addr,
+                name: matches.get_one::<String>("name").unwrap().clone(),
+            }),
+        None => app.add_plugins(SavePlugin).insert_resource(save_config),
+    };
+    app.run();
+    Ok(())
 }
 
-fn load_assets(mut commands: Commands, asset_server: Res<AssetServer>) -> Result {
-    let block_textures = asset_server.load_folder(BLOCK_TEXTURES_DIR);
-    let block_registry_json = fs::read_to_string(BLOCK_INFO_REGISTRY)?;
+fn load_assets(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands.insert_resource(GameAssets {
-        block_textures,
-        block_registry_json,
+        block_textures: asset_server.load_folder(BLOCK_TEXTURES_DIR),
+        block_registry: asset_server.load(BLOCK_INFO_REGISTRY),
     });
-    Ok(())
 }
 
 fn loading_assets(
     mut next_state: ResMut<NextState<GameState>>,
     game_assets: Res<GameAssets>,
-    mut events: EventReader<AssetEvent<LoadedFolder>>,
+    asset_server: Res<AssetServer>,
 ) {
-    for event in events.read() {
-        if event.is_loaded_with_dependencies(&game_assets.block_textures) {
-            next_state.set(GameState::InGame);
-        }
+    if asset_server.is_loaded_with_dependencies(&game_assets.block_textures)
+        && asset_server.is_loaded_with_dependencies(&game_assets.block_registry)
+    {
+        next_state.set(GameState::InGame);
     }
 }
 
-fn setup_resources(
-    mut commands: Commands,
-    mut images: ResMut<Assets<Image>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    game_assets: Res<GameAssets>,
-    loaded_folders: Res<Assets<LoadedFolder>>,
-) -> Result {
-    let (texture_map, mut layout_builder) = loaded_folders
-        .get(&game_assets.block_textures)
-        .ok_or(anyhow!("Couldn't load block textures folder"))?
-        .handles
-        .iter()
-        .try_fold(
-            (
-                HashMap::<String, AssetId<Image>>::new(),
-                TextureAtlasBuilder::default(),
-            ),
-            |(mut map, mut builder), handle| {
-                let id = handle.id().try_typed::<Image>()?;
-                let path = handle
-                    .path()
-                    .and_then(|p| p.path().file_name())
-                    .map(|n| n.to_string_lossy())
-                    .ok_or(anyhow!("Failed to retrieve image's file name"))?;
-
-                let texture = images
-                    .get(id)
-                    .ok_or(anyhow!("Failed to retrieve image: {path}"))?;
-
-                builder.add_texture(Some(id), texture);
-                if map.contains_key(path.as_ref()) {
-                    return Err(anyhow!("Duplicate image: {path}"));
-                }
-                map.insert(path.to_string(), id);
-
-                info!("Loaded texture {path} into atlas at {id}");
-                anyhow::Ok((map, builder))
+fn build_game_resources(
+    images: &mut Assets<Image>,
+    materials: &mut Assets<BlockMaterial>,
+    block_textures: &LoadedFolder,
+    block_info_registry: &BlockInfoRegistry,
+) -> anyhow::Result<GameResources> {
+    let (texture_map, textures) = block_textures.handles.iter().try_fold(
+        (HashMap::<String, u32>::new(), Vec::<Image>::new()),
+        |(mut map, mut textures), handle| {
+            let id = handle.id().try_typed::<Image>()?;
+            let path = handle
+                .path()
+                .and_then(|p| p.path().file_name())
+                .map(|n| n.to_string_lossy())
+                .ok_or(anyhow!("Failed to retrieve image's file name"))?;
+
+            let texture = images
+                .get(id)
+                .ok_or(anyhow!("Failed to retrieve image: {path}"))?;
+
+            if map.contains_key(path.as_ref()) {
+                return Err(anyhow!("Duplicate image: {path}"));
+            }
+            let layer = textures.len() as u32;
+            map.insert(path.to_string(), layer);
+            textures.push(texture.clone());
+
+            info!("Loaded texture {path} into texture array at layer {layer}");
+            anyhow::Ok((map, textures))
+        },
+    )?;
+    let texture_array = images.add(build_texture_array(&textures)?);
+    let materials = Transparency::ALL.map(|transparency| {
+        materials.add(BlockMaterial {
+            textures: texture_array.clone(),
+            alpha_cutoff: match transparency {
+                Transparency::Cutout => 0.5,
+                Transparency::Opaque | Transparency::Translucent => 0.0,
+            },
+            daylight: 1.0,
+            tint: LinearRgba::WHITE,
+            alpha_mode: match transparency {
+                Transparency::Opaque => AlphaMode::Opaque,
+                Transparency::Cutout => AlphaMode::Mask(0.5),
+                Transparency::Translucent => AlphaMode::Blend,
             },
-        )?;
-    let (layout, sources, mut image) = layout_builder.build()?;
-    let texture_map = texture_map
-        .into_iter()
-        .fold(HashMap::new(), |mut map, (key, id)| {
-            let urect = sources.texture_rect(&layout, id).unwrap();
-            let size = layout.size;
-            let rect = Rect::new(
-                urect.min.x as f32 / size.x as f32,
-                urect.min.y as f32 / size.y as f32,
-                urect.max.x as f32 / size.x as f32,
-                urect.max.y as f32 / size.y as f32,
-            );
-            map.insert(key, rect);
-            map
-        });
-    image.sampler = ImageSampler::nearest();
-    let texture_atlas = images.add(image);
-    let material = materials.add(StandardMaterial {
-        base_color: Color::WHITE,
-        base_color_texture: Some(texture_atlas.clone()),
-        perceptual_roughness: 0.97,
-        unlit: true,
-        reflectance: 0.1,
-        ..default()
+        })
     });
-    let block_info_registry =
-        serde_json::from_str::<BlockInfoRegistry>(&game_assets.block_registry_json)?;
-    let blocks = block_info_registry
+    let blocks = block_info_registry.build_blocks(|name| {
+        texture_map
+            .get(name)
+            .copied()
+            .ok_or(anyhow!("Unknown block texture: {name}"))
+    })?;
+    // Copies with nearest filtering keep the pixel art sharp when scaled up.
+    let icons = block_info_registry
         .blocks
-        .into_iter()
-        .enumerate()
-        .map(|(i, block_info)| {
-            (
-                i,
-                block_info.name,
-                Block::new(
-                    texture_map[&block_info.front],
-                    texture_map[&block_info.back],
-                    texture_map[&block_info.left],
-                    texture_map[&block_info.right],
-                    texture_map[&block_info.top],
-                    texture_map[&block_info.bottom],
-                ),
-            )
+        .iter()
+        .map(|info| {
+            let layer = texture_map
+                .get(&info.front)
+                .ok_or(anyhow!("Unknown block texture: {}", info.front))?;
+            let mut icon = textures[*layer as usize].clone();
+            icon.sampler = ImageSampler::nearest();
+            Ok(images.add(icon))
         })
-        .collect::<Vec<_>>();
-    let block_map = blocks
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let block_names = block_info_registry.block_names();
+    let block_map = block_names
         .iter()
-        .map(|(i, name, _)| (name.clone(), *i))
+        .enumerate()
+        .map(|(i, name)| (name.clone(), i))
         .collect();
-    let blocks = blocks
-        .iter()
-        .map(|(_, _, block)| block.clone())
-        .collect::<Vec<_>>();
-    commands.insert_resource(GameResources {
-        material,
+    Ok(GameResources {
+        materials,
         blocks_map: Arc::new(block_map),
+        block_names: Arc::new(block_names),
         blocks: Arc::new(blocks),
-    });
+        icons: Arc::new(icons),
+    })
+}
+
+fn setup_resources(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<BlockMaterial>>,
+    game_assets: Res<GameAssets>,
+    loaded_folders: Res<Assets<LoadedFolder>>,
+    block_registries: Res<Assets<BlockInfoRegistry>>,
+) -> Result {
+    let game_resources = build_game_resources(
+        &mut images,
+        &mut materials,
+        loaded_folders
+            .get(&game_assets.block_textures)
+            .ok_or(anyhow!("Couldn't load block textures folder"))?,
+        block_registries
+            .get(&game_assets.block_registry)
+            .ok_or(anyhow!("Couldn't load block registry"))?,
+    )?;
+    commands.insert_resource(game_resources);
     Ok(())
 }
 
-/// set up a simple 3D scene
-fn setup(mut commands: Commands) {
-    // light
-    commands.spawn((
-        PointLight {
-            shadows_enabled: false,
-            ..default()
-        },
-        Transform::from_xyz(4.0, 8.0, 4.0),
-    ));
+#[allow(clippy::too_many_arguments)]
+fn reload_resources(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<BlockMaterial>>,
+    game_assets: Res<GameAssets>,
+    loaded_folders: Res<Assets<LoadedFolder>>,
+    block_registries: Res<Assets<BlockInfoRegistry>>,
+    mut image_events: EventReader<AssetEvent<Image>>,
+    mut registry_events: EventReader<AssetEvent<BlockInfoRegistry>>,
+) -> Result {
+    let Some(block_textures) = loaded_folders.get(&game_assets.block_textures) else {
+        return Ok(());
+    };
+    // Every event is read, so the ones after the first change don't trigger
+    // another rebuild next frame.
+    let textures_modified = image_events
+        .read()
+        .filter(|event| {
+            block_textures
+                .handles
+                .iter()
+                .filter_map(|handle| handle.id().try_typed::<Image>().ok())
+                .any(|id| event.is_modified(id))
+        })
+        .count()
+        > 0;
+    let registry_modified = registry_events
+        .read()
+        .filter(|event| event.is_modified(&game_assets.block_registry))
+        .count()
+        > 0;
+    if !textures_modified && !registry_modified {
+        return Ok(());
+    }
+    info!("Block assets changed, rebuilding game resources");
+    let game_resources = build_game_resources(
+        &mut images,
+        &mut materials,
+        block_textures,
+        block_registries
+            .get(&game_assets.block_registry)
+            .ok_or(anyhow!("Couldn't load block registry"))?,
+    )?;
+    commands.insert_resource(game_resources);
+    Ok(())
 }
@@ -0,0 +1,129 @@
+use anyhow::anyhow;
+use bevy::{
+    asset::RenderAssetUsages,
+    image::{ImageFilterMode, ImageSampler, ImageSamplerDescriptor},
+    prelude::*,
+    render::render_resource::{
+        AsBindGroup, Extent3d, ShaderRef, TextureDimension, TextureFormat, TextureViewDescriptor,
+        TextureViewDimension,
+    },
+};
+
+const BLOCK_SHADER: &str = "shaders/block.wgsl";
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct BlockMaterial {
+    #[texture(0, dimension = "2d_array")]
+    #[sampler(1)]
+    pub textures: Handle<Image>,
+    #[uniform(2)]
+    pub alpha_cutoff: f32,
+    /// Scales sky light, from night to full daylight.
+    #[uniform(3)]
+    pub daylight: f32,
+    /// Multiplies the final color, used by debug views.
+    #[uniform(4)]
+    pub tint: LinearRgba,
+    pub alpha_mode: AlphaMode,
+}
+
+impl Material for BlockMaterial {
+    fn fragment_shader() -> ShaderRef {
+        BLOCK_SHADER.into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+}
+
+fn downsample(data: &[u8], size: u32) -> Vec<u8> {
+    let half = size / 2;
+    let mut out = Vec::with_capacity((half * half * 4) as usize);
+    for y in 0..half {
+        for x in 0..half {
+            let texels = [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| {
+                let i = (((y * 2 + dy) * size + x * 2 + dx) * 4) as usize;
+                &data[i..i + 4]
+            });
+            // Weight colors by alpha so transparent texels don't darken cutout edges.
+            let alpha = texels.iter().map(|t| t[3] as u32).sum::<u32>();
+            for channel in 0..3 {
+                let value = match alpha {
+                    0 => texels.iter().map(|t| t[channel] as u32).sum::<u32>() / 4,
+                    _ => {
+                        texels
+                            .iter()
+                            .map(|t| t[channel] as u32 * t[3] as u32)
+                            .sum::<u32>()
+                            / alpha
+                    }
+                };
+                out.push(value as u8);
+            }
+            out.push((alpha / 4) as u8);
+        }
+    }
+    out
+}
+
+/// Stacks square RGBA textures into a 2D array image, one layer per texture,
+/// with a full mip chain generated for every layer.
+pub fn build_texture_array(textures: &[Image]) -> anyhow::Result<Image> {
+    let size = textures
+        .first()
+        .ok_or(anyhow!("No block textures to build texture array from"))?
+        .width();
+    if !size.is_power_of_two() {
+        return Err(anyhow!("Block texture size {size} is not a power of two"));
+    }
+    let mip_level_count = size.ilog2() + 1;
+    let mut data = Vec::new();
+    for texture in textures {
+        if texture.size() != UVec2::splat(size) {
+            return Err(anyhow!(
+                "Block texture size {} doesn't match {size}x{size}",
+                texture.size()
+            ));
+        }
+        let texture = texture
+            .convert(TextureFormat::Rgba8UnormSrgb)
+            .ok_or(anyhow!("Failed to convert block texture to RGBA"))?;
+        let mut level = texture
+            .data
+            .ok_or(anyhow!("Block texture has no pixel data"))?;
+        let mut level_size = size;
+        loop {
+            data.extend_from_slice(&level);
+            if level_size == 1 {
+                break;
+            }
+            level = downsample(&level, level_size);
+            level_size /= 2;
+        }
+    }
+
+    let mut image = Image::new_uninit(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: textures.len() as u32,
+        },
+        TextureDimension::D2,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.data = Some(data);
+    image.texture_descriptor.mip_level_count = mip_level_count;
+    image.texture_view_descriptor = Some(TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::D2Array),
+        ..default()
+    });
+    image.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+        mag_filter: ImageFilterMode::Nearest,
+        min_filter: ImageFilterMode::Nearest,
+        mipmap_filter: ImageFilterMode::Linear,
+        ..default()
+    });
+    Ok(image)
+}
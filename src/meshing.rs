@@ -0,0 +1,34 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology},
+};
+use nipahblocks::block::MeshData;
+use nipahblocks::block_registry::Transparency;
+use nipahblocks::chunk::{Chunk, ChunkMap};
+
+pub fn into_mesh(data: MeshData) -> Mesh {
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, data.positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, data.normals)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, data.uvs)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_1, data.layers)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, data.colors)
+    .with_inserted_indices(Indices::U32(data.indices))
+}
+
+/// Meshes of the non-empty transparency layers of a chunk.
+pub fn chunk_layers(
+    chunk: &Chunk,
+    neighbors: Option<(&ChunkMap, IVec3)>,
+    ambient_occlusion: bool,
+) -> impl Iterator<Item = (Transparency, Mesh)> {
+    Transparency::ALL
+        .into_iter()
+        .zip(chunk.build_mesh_data(neighbors, ambient_occlusion))
+        .filter(|(_, mesh)| !mesh.is_empty())
+        .map(|(transparency, mesh)| (transparency, into_mesh(mesh)))
+}
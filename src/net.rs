@@ -0,0 +1,481 @@
+use bevy::{prelude::*, utils::hashbrown::HashMap};
+use std::{
+    io,
+    net::{SocketAddr, TcpListener, TcpStream, UdpSocket},
+};
+
+use crate::protocol::{
+    decode_client_datagram, decode_server_datagram, encode_client_datagram, encode_server_datagram,
+    ClientMessage, Connection, ServerMessage, PROTOCOL_VERSION,
+};
+
+/// Largest UDP packet read, transforms are far smaller.
+const MAX_DATAGRAM_LEN: usize = 1500;
+
+/// Client ID assigned by the server during the handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClientId(pub u32);
+
+/// Receives client messages before `Update` and sends queued server messages
+/// after it. Runs while a [`NetServer`] resource exists.
+pub struct NetServerPlugin;
+
+impl Plugin for NetServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ServerEvent>()
+            .add_event::<FromClient>()
+            .add_event::<ToClients>()
+            .add_systems(
+                PreUpdate,
+                (accept_clients, receive_from_clients)
+                    .chain()
+                    .run_if(resource_exists::<NetServer>),
+            )
+            .add_systems(
+                PostUpdate,
+                send_to_clients.run_if(resource_exists::<NetServer>),
+            )
+            .add_systems(
+                Last,
+                disconnect_clients.run_if(resource_exists::<NetServer>.and(on_event::<AppExit>)),
+            );
+    }
+}
+
+#[derive(Debug, Event)]
+pub enum ServerEvent {
+    /// A client completed the handshake.
+    Connected {
+        client: ClientId,
+        name: String,
+    },
+    Disconnected {
+        client: ClientId,
+    },
+}
+
+#[derive(Debug, Event)]
+pub struct FromClient {
+    pub client: ClientId,
+    pub message: ClientMessage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    All,
+    Client(ClientId),
+    AllExcept(ClientId),
+}
+
+impl Target {
+    fn includes(self, client: ClientId) -> bool {
+        match self {
+            Target::All => true,
+            Target::Client(target) => target == client,
+            Target::AllExcept(excluded) => excluded != client,
+        }
+    }
+}
+
+#[derive(Debug, Event)]
+pub struct ToClients {
+    pub target: Target,
+    pub message: ServerMessage,
+}
+
+#[derive(Debug)]
+struct RemoteClient {
+    connection: Connection,
+    token: u64,
+    /// Set once the handshake completed.
+    name: Option<String>,
+    /// Where UDP packets come from, learned from the first one received.
+    udp_addr: Option<SocketAddr>,
+    last_tick: u32,
+}
+
+#[derive(Debug, Resource)]
+pub struct NetServer {
+    listener: TcpListener,
+    socket: UdpSocket,
+    clients: HashMap<ClientId, RemoteClient>,
+    next_id: u32,
+}
+
+impl NetServer {
+    /// Listens for TCP connections and UDP packets on the same port.
+    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let socket = UdpSocket::bind(listener.local_addr()?)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            socket,
+            clients: HashMap::new(),
+            next_id: 1,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn client_name(&self, client: ClientId) -> Option<&str> {
+        self.clients.get(&client)?.name.as_deref()
+    }
+}
+
+fn accept_clients(mut server: ResMut<NetServer>) {
+    loop {
+        let stream = match server.listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+            Err(e) => {
+                warn!("Failed to accept connection: {e}");
+                break;
+            }
+        };
+        let connection = match Connection::new(stream) {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("Failed to set up connection: {e}");
+                continue;
+            }
+        };
+        let client = ClientId(server.next_id);
+        server.next_id += 1;
+        info!(
+            "Client {} connecting from {}",
+            client.0,
+            connection
+                .peer_addr()
+                .map_or("unknown address".to_string(), |addr| addr.to_string())
+        );
+        server.clients.insert(
+            client,
+            RemoteClient {
+                connection,
+                token: rand::random(),
+                name: None,
+                udp_addr: None,
+                last_tick: 0,
+            },
+        );
+    }
+}
+
+fn receive_from_clients(
+    mut server: ResMut<NetServer>,
+    mut server_events: EventWriter<ServerEvent>,
+    mut from_client: EventWriter<FromClient>,
+) {
+    let NetServer {
+        socket, clients, ..
+    } = server.as_mut();
+    let mut disconnected = Vec::new();
+    for (&client, remote) in clients.iter_mut() {
+        let frames = remote.connection.receive().unwrap_or_else(|e| {
+            warn!("Failed to read from client {}: {e}", client.0);
+            Vec::new()
+        });
+        for frame in frames {
+            let message = match ClientMessage::decode(&frame) {
+                Ok(message) => message,
+                Err(e) => {
+                    warn!("Client {} sent an invalid message: {e}", client.0);
+                    disconnected.push(client);
+                    break;
+                }
+            };
+            match (remote.name.is_some(), message) {
+                (false, ClientMessage::Hello { version, name }) => {
+                    if version != PROTOCOL_VERSION {
+                        let reason = format!(
+                            "Protocol version {version} is not supported, server uses {PROTOCOL_VERSION}"
+                        );
+                        remote
+                            .connection
+                            .send(&ServerMessage::Rejected { reason }.encode());
+                        disconnected.push(client);
+                        break;
+                    }
+                    let welcome = ServerMessage::Welcome {
+                        client_id: client.0,
+                        token: remote.token,
+                    };
+                    remote.connection.send(&welcome.encode());
+                    remote.name = Some(name.clone());
+                    info!("{name} joined as client {}", client.0);
+                    server_events.send(ServerEvent::Connected { client, name });
+                }
+                (false, _) => {
+                    warn!("Client {} skipped the handshake", client.0);
+                    disconnected.push(client);
+                    break;
+                }
+                (true, ClientMessage::Disconnect) => {
+                    disconnected.push(client);
+                    break;
+                }
+                (true, ClientMessage::Hello { .. }) => {
+                    warn!("Client {} repeated the handshake", client.0);
+                }
+                (true, message) => {
+                    from_client.send(FromClient { client, message });
+                }
+            }
+        }
+        if remote.connection.is_closed() {
+            disconnected.push(client);
+        }
+    }
+    for client in disconnected {
+        let Some(mut remote) = clients.remove(&client) else {
+            continue;
+        };
+        // Best effort, e.g. to deliver a rejection.
+        let _ = remote.connection.flush();
+        info!("Client {} disconnected", client.0);
+        if remote.name.is_some() {
+            server_events.send(ServerEvent::Disconnected { client });
+        }
+    }
+
+    let mut buf = [0; MAX_DATAGRAM_LEN];
+    loop {
+        let (len, addr) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+            Err(e) => {
+                warn!("Failed to receive datagram: {e}");
+                break;
+            }
+        };
+        let Ok((client_id, token, message)) = decode_client_datagram(&buf[..len]) else {
+            continue;
+        };
+        let client = ClientId(client_id);
+        let Some(remote) = clients.get_mut(&client) else {
+            continue;
+        };
+        if remote.token != token || remote.name.is_none() {
+            continue;
+        }
+        remote.udp_addr = Some(addr);
+        if let ClientMessage::PlayerTransform { tick, .. } = message {
+            if tick <= remote.last_tick {
+                continue;
+            }
+            remote.last_tick = tick;
+        }
+        from_client.send(FromClient { client, message });
+    }
+}
+
+fn send_to_clients(mut server: ResMut<NetServer>, mut to_clients: EventReader<ToClients>) {
+    let NetServer {
+        socket, clients, ..
+    } = server.as_mut();
+    for ToClients { target, message } in to_clients.read() {
+        let payload = message.encode();
+        let datagram = message
+            .is_unreliable()
+            .then(|| encode_server_datagram(message));
+        for (&client, remote) in clients.iter_mut() {
+            if remote.name.is_none() || !target.includes(client) {
+                continue;
+            }
+            match (&datagram, remote.udp_addr) {
+                (Some(datagram), Some(addr)) => {
+                    if let Err(e) = socket.send_to(datagram, addr) {
+                        warn!("Failed to send datagram to client {}: {e}", client.0);
+                    }
+                }
+                // Fall back to the stream until the client's address is known.
+                _ => remote.connection.send(&payload),
+            }
+        }
+    }
+    for (client, remote) in clients.iter_mut() {
+        if let Err(e) = remote.connection.flush() {
+            warn!("Failed to send to client {}: {e}", client.0);
+        }
+    }
+}
+
+fn disconnect_clients(mut server: ResMut<NetServer>) {
+    let message = ServerMessage::Disconnect {
+        reason: "Server is shutting down".to_string(),
+    }
+    .encode();
+    for (_, mut remote) in server.clients.drain() {
+        remote.connection.send(&message);
+        let _ = remote.connection.flush();
+    }
+}
+
+/// Receives server messages before `Update` and sends queued client messages
+/// after it. Runs while a [`NetClient`] resource exists, which is removed
+/// again when the connection ends.
+pub struct NetClientPlugin;
+
+impl Plugin for NetClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FromServer>()
+            .add_event::<ToServer>()
+            .add_systems(
+                PreUpdate,
+                receive_from_server.run_if(resource_exists::<NetClient>),
+            )
+            .add_systems(
+                PostUpdate,
+                send_to_server.run_if(resource_exists::<NetClient>),
+            )
+            .add_systems(
+                Last,
+                disconnect_from_server
+                    .run_if(resource_exists::<NetClient>.and(on_event::<AppExit>)),
+            );
+    }
+}
+
+#[derive(Debug, Event)]
+pub struct FromServer(pub ServerMessage);
+
+#[derive(Debug, Event)]
+pub struct ToServer(pub ClientMessage);
+
+#[derive(Debug, Resource)]
+pub struct NetClient {
+    connection: Connection,
+    socket: UdpSocket,
+    /// Client ID and UDP token, once the server accepted the handshake.
+    session: Option<(ClientId, u64)>,
+    /// Last transform tick received per remote player.
+    last_ticks: HashMap<u32, u32>,
+}
+
+impl NetClient {
+    /// Connects to the server at `addr` and starts the handshake.
+    pub fn connect(addr: SocketAddr, name: &str) -> io::Result<Self> {
+        let mut connection = Connection::new(TcpStream::connect(addr)?)?;
+        connection.send(
+            &ClientMessage::Hello {
+                version: PROTOCOL_VERSION,
+                name: name.to_string(),
+            }
+            .encode(),
+        );
+        let local_addr = match addr {
+            SocketAddr::V4(_) => "0.0.0.0:0",
+            SocketAddr::V6(_) => "[::]:0",
+        };
+        let socket = UdpSocket::bind(local_addr)?;
+        socket.connect(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            connection,
+            socket,
+            session: None,
+            last_ticks: HashMap::new(),
+        })
+    }
+
+    pub fn client_id(&self) -> Option<ClientId> {
+        self.session.map(|(client, _)| client)
+    }
+}
+
+fn receive_from_server(
+    mut commands: Commands,
+    mut client: ResMut<NetClient>,
+    mut from_server: EventWriter<FromServer>,
+) {
+    let frames = client.connection.receive().unwrap_or_else(|e| {
+        warn!("Failed to read from server: {e}");
+        Vec::new()
+    });
+    let mut lost = client.connection.is_closed();
+    let mut messages = Vec::new();
+    for frame in frames {
+        match ServerMessage::decode(&frame) {
+            Ok(message) => messages.push(message),
+            Err(e) => {
+                warn!("Server sent an invalid message: {e}");
+                lost = true;
+                break;
+            }
+        }
+    }
+    let mut buf = [0; MAX_DATAGRAM_LEN];
+    loop {
+        let len = match client.socket.recv(&mut buf) {
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+            // Reported for earlier packets nobody received, keep reading.
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => continue,
+            Err(e) => {
+                warn!("Failed to receive datagram: {e}");
+                break;
+            }
+        };
+        if let Ok(message) = decode_server_datagram(&buf[..len]) {
+            messages.push(message);
+        }
+    }
+    let mut ended = false;
+    for message in messages {
+        match &message {
+            ServerMessage::Welcome { client_id, token } => {
+                client.session = Some((ClientId(*client_id), *token));
+            }
+            ServerMessage::PlayerTransform {
+                client_id, tick, ..
+            } => {
+                let last_tick = client.last_ticks.entry(*client_id).or_default();
+                if *tick <= *last_tick {
+                    continue;
+                }
+                *last_tick = *tick;
+            }
+            ServerMessage::PlayerLeft { client_id } => {
+                client.last_ticks.remove(client_id);
+            }
+            ServerMessage::Rejected { .. } | ServerMessage::Disconnect { .. } => ended = true,
+            _ => {}
+        }
+        from_server.send(FromServer(message));
+    }
+    if lost && !ended {
+        from_server.send(FromServer(ServerMessage::Disconnect {
+            reason: "Lost connection to the server".to_string(),
+        }));
+    }
+    if lost || ended {
+        commands.remove_resource::<NetClient>();
+    }
+}
+
+fn send_to_server(mut client: ResMut<NetClient>, mut to_server: EventReader<ToServer>) {
+    let client = client.as_mut();
+    for ToServer(message) in to_server.read() {
+        match (message.is_unreliable(), client.session) {
+            (true, Some((ClientId(client_id), token))) => {
+                let datagram = encode_client_datagram(client_id, token, message);
+                if let Err(e) = client.socket.send(&datagram) {
+                    warn!("Failed to send datagram: {e}");
+                }
+            }
+            _ => client.connection.send(&message.encode()),
+        }
+    }
+    if let Err(e) = client.connection.flush() {
+        warn!("Failed to send to server: {e}");
+    }
+}
+
+fn disconnect_from_server(mut client: ResMut<NetClient>) {
+    client.connection.send(&ClientMessage::Disconnect.encode());
+    let _ = client.connection.flush();
+}
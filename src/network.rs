@@ -0,0 +1,234 @@
+use bevy::prelude::*;
+use nipahblocks::chunk::ChunkMap;
+use nipahblocks::generator::WorldSeed;
+use nipahblocks::net::{FromServer, NetClient, NetClientPlugin, ToServer};
+use nipahblocks::persistence::UNKNOWN_BLOCK;
+use nipahblocks::protocol::{decode_chunk, ClientMessage, ServerMessage};
+use nipahblocks::world_time::WorldTime;
+use std::net::SocketAddr;
+
+use crate::chunks::{spawn_chunk_entity, ChunkEntities, ChunkSource};
+use crate::interaction::BlocksEdited;
+use crate::player::Player;
+use crate::remote_player::RemotePlayerPlugin;
+use crate::{GameResources, GameState};
+
+/// Player transforms sent to the server per second.
+pub const TRANSFORM_RATE: f32 = 20.0;
+
+/// Plays on a server instead of a local world.
+pub struct NetworkPlugin;
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((NetClientPlugin, RemotePlayerPlugin))
+            .init_resource::<ServerPalette>()
+            .insert_resource(TransformTimer(Timer::from_seconds(
+                1.0 / TRANSFORM_RATE,
+                TimerMode::Repeating,
+            )))
+            .add_systems(OnEnter(GameState::InGame), connect)
+            .add_systems(
+                Update,
+                (
+                    update_palette.run_if(resource_exists_and_changed::<GameResources>),
+                    handle_server_messages,
+                    send_block_edits,
+                    send_transform,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+#[derive(Debug, Resource)]
+pub struct ConnectConfig {
+    pub addr: SocketAddr,
+    pub name: String,
+}
+
+/// Maps between the server's block IDs and the local registry, empty until
+/// the server sent its world info.
+#[derive(Debug, Default, Resource)]
+struct ServerPalette {
+    block_names: Vec<String>,
+    to_local: Vec<usize>,
+    to_server: Vec<Option<u16>>,
+    unknown: usize,
+}
+
+impl ServerPalette {
+    fn new(block_names: Vec<String>, game_resources: &GameResources) -> Self {
+        let unknown = game_resources
+            .blocks_map
+            .get(UNKNOWN_BLOCK)
+            .copied()
+            .unwrap_or(0);
+        let to_local = block_names
+            .iter()
+            .map(|name| {
+                game_resources
+                    .blocks_map
+                    .get(name)
+                    .copied()
+                    .unwrap_or_else(|| {
+                        warn!("Server block {name} is missing from the registry");
+                        unknown
+                    })
+            })
+            .collect();
+        let to_server = game_resources
+            .block_names
+            .iter()
+            .map(|name| {
+                block_names
+                    .iter()
+                    .position(|server_name| server_name == name)
+                    .map(|id| id as u16)
+            })
+            .collect();
+        Self {
+            block_names,
+            to_local,
+            to_server,
+            unknown,
+        }
+    }
+
+    /// Local block for a server block ID, [`UNKNOWN_BLOCK`] for IDs missing
+    /// from the server's world info.
+    fn local_block(&self, id: u16) -> usize {
+        self.to_local
+            .get(id as usize)
+            .copied()
+            .unwrap_or(self.unknown)
+    }
+}
+
+#[derive(Debug, Resource, Deref, DerefMut)]
+struct TransformTimer(Timer);
+
+fn connect(mut commands: Commands, config: Res<ConnectConfig>) -> Result {
+    info!("Connecting to {} as {}", config.addr, config.name);
+    commands.insert_resource(NetClient::connect(config.addr, &config.name)?);
+    Ok(())
+}
+
+fn update_palette(mut palette: ResMut<ServerPalette>, game_resources: Res<GameResources>) {
+    *palette = ServerPalette::new(std::mem::take(&mut palette.block_names), &game_resources);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_server_messages(
+    mut commands: Commands,
+    mut from_server: EventReader<FromServer>,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut chunk_entities: ResMut<ChunkEntities>,
+    mut palette: ResMut<ServerPalette>,
+    game_resources: Res<GameResources>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for FromServer(message) in from_server.read() {
+        match message {
+            ServerMessage::Welcome { client_id, .. } => {
+                info!("Joined the server as client {client_id}");
+            }
+            ServerMessage::Rejected { reason } | ServerMessage::Disconnect { reason } => {
+                error!("Disconnected from the server: {reason}");
+                exit.send(AppExit::error());
+            }
+            ServerMessage::WorldInfo {
+                seed,
+                time_of_day,
+                block_names,
+            } => {
+                info!("Server world has seed {seed}");
+                commands.insert_resource(WorldSeed(*seed));
+                commands.insert_resource(WorldTime::new(*time_of_day));
+                *palette = ServerPalette::new(block_names.clone(), &game_resources);
+            }
+            ServerMessage::Chunk { chunk_pos, data } => {
+                let chunk =
+                    match decode_chunk(data, &palette.to_local, game_resources.blocks.clone()) {
+                        Ok(chunk) => chunk,
+                        Err(e) => {
+                            error!("Failed to decode chunk {chunk_pos} from the server: {e}");
+                            continue;
+                        }
+                    };
+                spawn_chunk_entity(
+                    &mut commands,
+                    &mut chunk_entities,
+                    *chunk_pos,
+                    ChunkSource::Received,
+                );
+                chunk_map.insert_chunk(*chunk_pos, chunk);
+            }
+            ServerMessage::BlockChanged { pos, block } => {
+                chunk_map.set_block(*pos, block.map(|id| palette.local_block(id)));
+            }
+            ServerMessage::BlocksChanged { changes } => {
+                chunk_map.set_blocks(
+                    changes
+                        .iter()
+                        .map(|(pos, block)| (*pos, block.map(|id| palette.local_block(id)))),
+                );
+            }
+            // Handled by the remote player systems.
+            ServerMessage::PlayerJoined { .. }
+            | ServerMessage::PlayerLeft { .. }
+            | ServerMessage::PlayerTransform { .. } => {}
+            // Handled by the command systems.
+            ServerMessage::Chat { .. }
+            | ServerMessage::Teleport { .. }
+            | ServerMessage::TimeOfDay { .. }
+            | ServerMessage::SetGameMode { .. }
+            | ServerMessage::Selection { .. } => {}
+        }
+    }
+}
+
+fn send_block_edits(
+    mut edits: EventReader<BlocksEdited>,
+    palette: Res<ServerPalette>,
+    mut to_server: EventWriter<ToServer>,
+) {
+    for edit in edits.read().flat_map(|edits| edits.iter()) {
+        let block = match edit.new {
+            Some(id) => match palette.to_server.get(id).copied().flatten() {
+                Some(id) => Some(id),
+                None => {
+                    warn!("The server doesn't know block {id}, not sending edit");
+                    continue;
+                }
+            },
+            None => None,
+        };
+        to_server.send(ToServer(ClientMessage::SetBlock {
+            pos: edit.pos,
+            block,
+        }));
+    }
+}
+
+fn send_transform(
+    time: Res<Time>,
+    mut timer: ResMut<TransformTimer>,
+    mut tick: Local<u32>,
+    mut to_server: EventWriter<ToServer>,
+    player_q: Query<&Transform, With<Player>>,
+) {
+    if !timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Ok(transform) = player_q.get_single() else {
+        return;
+    };
+    *tick += 1;
+    to_server.send(ToServer(ClientMessage::PlayerTransform {
+        tick: *tick,
+        translation: transform.translation,
+        rotation: transform.rotation,
+    }));
+}
@@ -0,0 +1,208 @@
+use anyhow::anyhow;
+use bevy::{prelude::*, utils::hashbrown::HashMap};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::block::Block;
+use crate::chunk::{Chunk, ChunkMap, CHUNK_LEN};
+
+pub const DEFAULT_SEED: u32 = 123456;
+pub const UNKNOWN_BLOCK: &str = "unknown";
+/// Time of day in hours new worlds start at.
+pub const DEFAULT_TIME_OF_DAY: f32 = 8.0;
+
+const LEVEL_FILE: &str = "level.json";
+const CHUNKS_DIR: &str = "chunks";
+const CHUNK_FORMAT_VERSION: u32 = 1;
+/// Persisted per-world metadata. Block IDs stored in chunk files refer to
+/// `block_ids`, so they survive reordering or extending the block registry.
+#[derive(Debug, Serialize, Deserialize)]
+struct LevelData {
+    seed: u32,
+    #[serde(default)]
+    block_ids: BTreeMap<String, u16>,
+    #[serde(default = "default_time_of_day")]
+    time_of_day: f32,
+}
+
+fn default_time_of_day() -> f32 {
+    DEFAULT_TIME_OF_DAY
+}
+
+#[derive(Debug, Resource)]
+pub struct WorldSave {
+    dir: PathBuf,
+    level: LevelData,
+    /// World block ID to registry index, air (`0`) included.
+    to_runtime: Vec<Option<usize>>,
+    /// Registry index to world block ID.
+    to_world: Vec<u16>,
+}
+
+impl WorldSave {
+    /// Opens the world at `dir`, creating it with `seed` if it doesn't exist.
+    pub fn open(dir: &Path, seed: Option<u32>, block_names: &[String]) -> anyhow::Result<Self> {
+        let level_path = dir.join(LEVEL_FILE);
+        let level = match level_path.exists() {
+            true => serde_json::from_str::<LevelData>(&fs::read_to_string(&level_path)?)?,
+            false => LevelData {
+                seed: seed.unwrap_or(DEFAULT_SEED),
+                block_ids: BTreeMap::new(),
+                time_of_day: DEFAULT_TIME_OF_DAY,
+            },
+        };
+        fs::create_dir_all(dir.join(CHUNKS_DIR))?;
+        let mut world_save = Self {
+            dir: dir.to_path_buf(),
+            level,
+            to_runtime: Vec::new(),
+            to_world: Vec::new(),
+        };
+        world_save.map_block_ids(block_names)?;
+        Ok(world_save)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.level.seed
+    }
+
+    pub fn time_of_day(&self) -> f32 {
+        self.level.time_of_day
+    }
+
+    /// Assigns IDs to blocks new to this world and rebuilds the remapping
+    /// tables. Blocks the registry no longer has load as [`UNKNOWN_BLOCK`].
+    pub fn map_block_ids(&mut self, block_names: &[String]) -> anyhow::Result<()> {
+        let unknown = block_names
+            .iter()
+            .position(|name| name == UNKNOWN_BLOCK)
+            .ok_or(anyhow!("Block registry has no \"{UNKNOWN_BLOCK}\" block"))?;
+        let mut next_id = self.level.block_ids.values().max().copied().unwrap_or(0);
+        self.to_world = block_names
+            .iter()
+            .map(|name| {
+                *self.level.block_ids.entry(name.clone()).or_insert_with(|| {
+                    next_id += 1;
+                    next_id
+                })
+            })
+            .collect();
+        let runtime_ids = block_names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.as_str(), i))
+            .collect::<HashMap<_, _>>();
+        self.to_runtime = vec![None; next_id as usize + 1];
+        for (name, id) in &self.level.block_ids {
+            let runtime_id = runtime_ids.get(name.as_str()).copied();
+            if runtime_id.is_none() {
+                warn!("Block {name} is missing from the registry, loading it as {UNKNOWN_BLOCK}");
+            }
+            self.to_runtime[*id as usize] = Some(runtime_id.unwrap_or(unknown));
+        }
+        self.save_level()
+    }
+
+    fn save_level(&self) -> anyhow::Result<()> {
+        fs::write(
+            self.dir.join(LEVEL_FILE),
+            serde_json::to_string_pretty(&self.level)?,
+        )?;
+        Ok(())
+    }
+
+    fn chunk_path(&self, chunk_pos: IVec3) -> PathBuf {
+        self.dir.join(CHUNKS_DIR).join(format!(
+            "{}_{}_{}.bin",
+            chunk_pos.x, chunk_pos.y, chunk_pos.z
+        ))
+    }
+
+    pub fn load_chunk(
+        &self,
+        chunk_pos: IVec3,
+        blocks_info: Arc<Vec<Block>>,
+    ) -> anyhow::Result<Option<Chunk>> {
+        let path = self.chunk_path(chunk_pos);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let mut data = Vec::new();
+        ZlibDecoder::new(fs::File::open(&path)?).read_to_end(&mut data)?;
+        let (version, ids) = data
+            .split_first_chunk::<4>()
+            .ok_or(anyhow!("Chunk file {} is truncated", path.display()))?;
+        if u32::from_le_bytes(*version) != CHUNK_FORMAT_VERSION
+            || ids.len() != CHUNK_LEN as usize * 2
+        {
+            return Err(anyhow!(
+                "Chunk file {} has unsupported format",
+                path.display()
+            ));
+        }
+        let blocks = ids
+            .chunks_exact(2)
+            .map(|id| {
+                let id = u16::from_le_bytes([id[0], id[1]]) as usize;
+                match id {
+                    0 => Ok(None),
+                    _ => self
+                        .to_runtime
+                        .get(id)
+                        .copied()
+                        .flatten()
+                        .map(Some)
+                        .ok_or(anyhow!(
+                            "Chunk file {} has unknown block ID {id}",
+                            path.display()
+                        )),
+                }
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Some(Chunk::from_blocks(&blocks, blocks_info)))
+    }
+
+    pub fn save_chunk(&self, chunk_pos: IVec3, chunk: &Chunk) -> anyhow::Result<()> {
+        let mut data = Vec::with_capacity(4 + CHUNK_LEN as usize * 2);
+        data.extend_from_slice(&CHUNK_FORMAT_VERSION.to_le_bytes());
+        for block in chunk.blocks() {
+            let id = block.map_or(0, |id| self.to_world[id]);
+            data.extend_from_slice(&id.to_le_bytes());
+        }
+        let mut encoder = ZlibEncoder::new(
+            fs::File::create(self.chunk_path(chunk_pos))?,
+            Compression::default(),
+        );
+        encoder.write_all(&data)?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    /// Stores the time of day along with the rest of the level data.
+    pub fn save_time(&mut self, time_of_day: f32) -> anyhow::Result<()> {
+        self.level.time_of_day = time_of_day;
+        self.save_level()
+    }
+
+    pub fn save_dirty_chunks(&self, chunk_map: &mut ChunkMap) -> anyhow::Result<usize> {
+        let mut saved = 0;
+        for (chunk_pos, chunk) in chunk_map.chunks_mut() {
+            if chunk.take_dirty() {
+                self.save_chunk(*chunk_pos, chunk)?;
+                saved += 1;
+            }
+        }
+        Ok(saved)
+    }
+}
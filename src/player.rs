@@ -1,6 +1,18 @@
 use bevy::{input::mouse::AccumulatedMouseMotion, prelude::*};
+use nipahblocks::block::Block;
+use nipahblocks::chunk::{block_min_corner, world_to_block, ChunkMap};
+use nipahblocks::command::GameMode;
+use nipahblocks::history::EditHistory;
+use nipahblocks::world_edit::EditSession;
 use std::f32::consts::FRAC_PI_2;
 
+use crate::diagnostics::DebugInfo;
+use crate::inventory::Inventory;
+
+const PLAYER_HALF_WIDTH: f32 = 0.3;
+const PLAYER_EYE_HEIGHT: f32 = 1.6;
+const PLAYER_HEAD_HEIGHT: f32 = 0.2;
+
 pub struct PlayerPlugin;
 
 #[derive(Debug, Component)]
@@ -28,7 +40,7 @@ impl Default for CameraSensitivity {
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, spawn_player)
-            .add_systems(Update, move_player);
+            .add_systems(Update, (move_player, player_debug_info).chain());
     }
 }
 
@@ -36,6 +48,10 @@ fn spawn_player(mut commands: Commands) {
     commands
         .spawn((
             Player::default(),
+            GameMode::default(),
+            EditSession::default(),
+            EditHistory::default(),
+            Inventory::default(),
             CameraSensitivity::default(),
             Transform::from_xyz(2.0, 0.5, 2.0),
             Visibility::default(),
@@ -51,13 +67,46 @@ fn spawn_player(mut commands: Commands) {
         });
 }
 
+fn player_bounds(pos: Vec3) -> (Vec3, Vec3) {
+    (
+        pos - Vec3::new(PLAYER_HALF_WIDTH, PLAYER_EYE_HEIGHT, PLAYER_HALF_WIDTH),
+        pos + Vec3::new(PLAYER_HALF_WIDTH, PLAYER_HEAD_HEIGHT, PLAYER_HALF_WIDTH),
+    )
+}
+
+pub fn collides(chunk_map: &ChunkMap, pos: Vec3) -> bool {
+    let (min, max) = player_bounds(pos);
+    let (from, to) = (world_to_block(min), world_to_block(max));
+    (from.x..=to.x)
+        .flat_map(|x| (from.y..=to.y).flat_map(move |y| (from.z..=to.z).map(move |z| (x, y, z))))
+        .map(|(x, y, z)| IVec3::new(x, y, z))
+        .filter_map(|block_pos| {
+            chunk_map
+                .block_info_at(block_pos)
+                .map(|(_, block)| (block_pos, block))
+        })
+        .any(|(block_pos, block)| collides_with_block(pos, block_pos, block))
+}
+
+/// Whether a player at `pos` would be inside `block` at `block_pos`.
+pub fn collides_with_block(pos: Vec3, block_pos: IVec3, block: &Block) -> bool {
+    let (min, max) = player_bounds(pos);
+    let corner = block_min_corner(block_pos);
+    block.collision_boxes().iter().any(|b| {
+        let (b_min, b_max) = (corner + b.min, corner + b.max);
+        min.cmplt(b_max).all() && max.cmpgt(b_min).all()
+    })
+}
+
 fn move_player(
     time: Res<Time>,
+    chunk_map: Res<ChunkMap>,
     accumulated_mouse_motion: Res<AccumulatedMouseMotion>,
     keyboard: Res<ButtonInput<KeyCode>>,
-    mut player_q: Query<(&mut Transform, &Player, &CameraSensitivity), With<Player>>,
+    mut player_q: Query<(&mut Transform, &Player, &GameMode, &CameraSensitivity)>,
 ) {
-    let Ok((mut transform, player, camera_sensitivity)) = player_q.get_single_mut() else {
+    let Ok((mut transform, player, game_mode, camera_sensitivity)) = player_q.get_single_mut()
+    else {
         return;
     };
     let delta = accumulated_mouse_motion.delta;
@@ -94,5 +143,37 @@ fn move_player(
         direction += *transform.down();
     }
     let movement = direction.normalize_or_zero() * player.movement_speed * time.delta_secs();
-    transform.translation += movement;
+    for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+        let target = transform.translation + movement * axis;
+        if *game_mode == GameMode::Spectator || !collides(&chunk_map, target) {
+            transform.translation = target;
+        }
+    }
+}
+
+fn player_debug_info(mut debug_info: ResMut<DebugInfo>, player_q: Query<&Transform, With<Player>>) {
+    let Ok(transform) = player_q.get_single() else {
+        return;
+    };
+    let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+    let forward = transform.forward();
+    let facing = match (
+        forward.x.abs() > forward.z.abs(),
+        forward.x > 0.0,
+        forward.z > 0.0,
+    ) {
+        (true, true, _) => "east (+X)",
+        (true, false, _) => "west (-X)",
+        (false, _, true) => "south (+Z)",
+        (false, _, false) => "north (-Z)",
+    };
+    debug_info.set("Position", format!("{:.2}", transform.translation));
+    debug_info.set(
+        "Facing",
+        format!(
+            "{facing}, yaw {:.1}, pitch {:.1}",
+            yaw.to_degrees(),
+            pitch.to_degrees()
+        ),
+    );
 }
@@ -0,0 +1,673 @@
+use anyhow::anyhow;
+use bevy::prelude::*;
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, SocketAddr, TcpStream},
+    sync::Arc,
+};
+
+use crate::block::Block;
+use crate::chunk::{Chunk, CHUNK_LEN};
+use crate::command::GameMode;
+
+/// Bumped whenever the encoding of a message changes. Clients speaking a
+/// different version are rejected during the handshake.
+pub const PROTOCOL_VERSION: u16 = 2;
+/// Frames longer than this are treated as a corrupt stream.
+const MAX_FRAME_LEN: usize = 4 << 20;
+/// Most bytes queued for a peer that isn't reading them before the
+/// connection is dropped.
+pub const MAX_QUEUED_LEN: usize = 4 * MAX_FRAME_LEN;
+/// Most changes sent in one [`ServerMessage::BlocksChanged`], keeping it well
+/// below the frame limit.
+pub const MAX_BLOCKS_CHANGED: usize = 1 << 16;
+/// Most block IDs a registry can have to be sent over the protocol, as block
+/// IDs are sent as `u16`s with `0` taken by air.
+pub const MAX_BLOCK_IDS: usize = u16::MAX as usize;
+/// Longest string in bytes a message can carry, longer ones are truncated.
+pub const MAX_STRING_LEN: usize = u16::MAX as usize;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+    /// First message on a new connection.
+    Hello {
+        version: u16,
+        name: String,
+    },
+    /// Sent over UDP, packets with an older `tick` than the last one are dropped.
+    PlayerTransform {
+        tick: u32,
+        translation: Vec3,
+        rotation: Quat,
+    },
+    /// Block change, using the server's block IDs.
+    SetBlock {
+        pos: IVec3,
+        block: Option<u16>,
+    },
+    /// Chat line, or a command if it starts with `/`.
+    Chat {
+        text: String,
+    },
+    Disconnect,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+    /// Accepts the handshake. UDP packets from the client have to carry `token`.
+    Welcome {
+        client_id: u32,
+        token: u64,
+    },
+    Rejected {
+        reason: String,
+    },
+    /// `block_names` is indexed by the server's block IDs, clients use it to
+    /// map them to their own registry.
+    WorldInfo {
+        seed: u32,
+        time_of_day: f32,
+        block_names: Vec<String>,
+    },
+    /// Compressed chunk blocks, see [`encode_chunk`].
+    Chunk {
+        chunk_pos: IVec3,
+        data: Vec<u8>,
+    },
+    BlockChanged {
+        pos: IVec3,
+        block: Option<u16>,
+    },
+    PlayerJoined {
+        client_id: u32,
+        name: String,
+    },
+    PlayerLeft {
+        client_id: u32,
+    },
+    /// Sent over UDP once the client's address is known.
+    PlayerTransform {
+        client_id: u32,
+        tick: u32,
+        translation: Vec3,
+        rotation: Quat,
+    },
+    Disconnect {
+        reason: String,
+    },
+    /// Chat or command output shown in the console.
+    Chat {
+        text: String,
+    },
+    /// Moves the receiving player.
+    Teleport {
+        translation: Vec3,
+    },
+    TimeOfDay {
+        time_of_day: f32,
+        paused: bool,
+    },
+    SetGameMode {
+        mode: GameMode,
+    },
+    /// World-edit selection corners of the receiving player.
+    Selection {
+        corners: [Option<IVec3>; 2],
+    },
+    /// Many block changes applied together, e.g. by a world-edit operation.
+    BlocksChanged {
+        changes: Vec<(IVec3, Option<u16>)>,
+    },
+}
+
+#[derive(Default)]
+struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn u8(&mut self, value: u8) -> &mut Self {
+        self.0.push(value);
+        self
+    }
+
+    fn u16(&mut self, value: u16) -> &mut Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn u32(&mut self, value: u32) -> &mut Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn u64(&mut self, value: u64) -> &mut Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn f32(&mut self, value: f32) -> &mut Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn ivec3(&mut self, value: IVec3) -> &mut Self {
+        for v in value.to_array() {
+            self.0.extend_from_slice(&v.to_le_bytes());
+        }
+        self
+    }
+
+    fn vec3(&mut self, value: Vec3) -> &mut Self {
+        value.to_array().iter().fold(self, |e, v| e.f32(*v))
+    }
+
+    fn quat(&mut self, value: Quat) -> &mut Self {
+        value.to_array().iter().fold(self, |e, v| e.f32(*v))
+    }
+
+    /// `0` for air, the block ID plus one otherwise. IDs past
+    /// [`MAX_BLOCK_IDS`] can't be represented and are sent as air.
+    fn block(&mut self, block: Option<u16>) -> &mut Self {
+        self.u16(block.and_then(|id| id.checked_add(1)).unwrap_or(0))
+    }
+
+    fn corner(&mut self, corner: Option<IVec3>) -> &mut Self {
+        match corner {
+            Some(pos) => self.u8(1).ivec3(pos),
+            None => self.u8(0),
+        }
+    }
+
+    /// Writes at most [`MAX_STRING_LEN`] bytes of `value`, cut at a character
+    /// boundary.
+    fn string(&mut self, value: &str) -> &mut Self {
+        let mut len = value.len().min(MAX_STRING_LEN);
+        while !value.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.u16(len as u16);
+        self.0.extend_from_slice(&value.as_bytes()[..len]);
+        self
+    }
+
+    fn bytes(&mut self, value: &[u8]) -> &mut Self {
+        self.u32(value.len() as u32);
+        self.0.extend_from_slice(value);
+        self
+    }
+}
+
+struct Decoder<'a>(&'a [u8]);
+
+impl Decoder<'_> {
+    fn take<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        let (head, rest) = self
+            .0
+            .split_first_chunk::<N>()
+            .ok_or(anyhow!("Message is truncated"))?;
+        self.0 = rest;
+        Ok(*head)
+    }
+
+    fn slice(&mut self, len: usize) -> anyhow::Result<&[u8]> {
+        if self.0.len() < len {
+            return Err(anyhow!("Message is truncated"));
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    fn u64(&mut self) -> anyhow::Result<u64> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    fn f32(&mut self) -> anyhow::Result<f32> {
+        Ok(f32::from_le_bytes(self.take()?))
+    }
+
+    fn ivec3(&mut self) -> anyhow::Result<IVec3> {
+        Ok(IVec3::new(
+            i32::from_le_bytes(self.take()?),
+            i32::from_le_bytes(self.take()?),
+            i32::from_le_bytes(self.take()?),
+        ))
+    }
+
+    fn vec3(&mut self) -> anyhow::Result<Vec3> {
+        let value = Vec3::new(self.f32()?, self.f32()?, self.f32()?);
+        match value.is_finite() {
+            true => Ok(value),
+            false => Err(anyhow!("Vector {value} isn't finite")),
+        }
+    }
+
+    fn quat(&mut self) -> anyhow::Result<Quat> {
+        let value = Quat::from_xyzw(self.f32()?, self.f32()?, self.f32()?, self.f32()?);
+        let length = value.length();
+        if !length.is_finite() || length < f32::EPSILON {
+            return Err(anyhow!("Rotation {value} isn't a valid quaternion"));
+        }
+        Ok(value / length)
+    }
+
+    fn block(&mut self) -> anyhow::Result<Option<u16>> {
+        Ok(self.u16()?.checked_sub(1))
+    }
+
+    fn corner(&mut self) -> anyhow::Result<Option<IVec3>> {
+        match self.u8()? {
+            0 => Ok(None),
+            _ => Ok(Some(self.ivec3()?)),
+        }
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let len = self.u16()? as usize;
+        Ok(String::from_utf8(self.slice(len)?.to_vec())?)
+    }
+
+    fn bytes(&mut self) -> anyhow::Result<Vec<u8>> {
+        let len = self.u32()? as usize;
+        Ok(self.slice(len)?.to_vec())
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        match self.0.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!("Message has {} trailing bytes", self.0.len())),
+        }
+    }
+}
+
+impl ClientMessage {
+    fn encode_into(&self, e: &mut Encoder) {
+        match self {
+            ClientMessage::Hello { version, name } => e.u8(0).u16(*version).string(name),
+            ClientMessage::PlayerTransform {
+                tick,
+                translation,
+                rotation,
+            } => e.u8(1).u32(*tick).vec3(*translation).quat(*rotation),
+            ClientMessage::SetBlock { pos, block } => e.u8(2).ivec3(*pos).block(*block),
+            ClientMessage::Disconnect => e.u8(3),
+            ClientMessage::Chat { text } => e.u8(4).string(text),
+        };
+    }
+
+    fn decode_from(d: &mut Decoder) -> anyhow::Result<Self> {
+        Ok(match d.u8()? {
+            0 => ClientMessage::Hello {
+                version: d.u16()?,
+                name: d.string()?,
+            },
+            1 => ClientMessage::PlayerTransform {
+                tick: d.u32()?,
+                translation: d.vec3()?,
+                rotation: d.quat()?,
+            },
+            2 => ClientMessage::SetBlock {
+                pos: d.ivec3()?,
+                block: d.block()?,
+            },
+            3 => ClientMessage::Disconnect,
+            4 => ClientMessage::Chat { text: d.string()? },
+            tag => return Err(anyhow!("Unknown client message {tag}")),
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut e = Encoder::default();
+        self.encode_into(&mut e);
+        e.0
+    }
+
+    pub fn decode(data: &[u8]) -> anyhow::Result<Self> {
+        let mut d = Decoder(data);
+        let message = Self::decode_from(&mut d)?;
+        d.finish()?;
+        Ok(message)
+    }
+
+    /// Whether the message is sent over UDP rather than the TCP stream.
+    pub fn is_unreliable(&self) -> bool {
+        matches!(self, ClientMessage::PlayerTransform { .. })
+    }
+}
+
+impl ServerMessage {
+    fn encode_into(&self, e: &mut Encoder) {
+        match self {
+            ServerMessage::Welcome { client_id, token } => e.u8(0).u32(*client_id).u64(*token),
+            ServerMessage::Rejected { reason } => e.u8(1).string(reason),
+            ServerMessage::WorldInfo {
+                seed,
+                time_of_day,
+                block_names,
+            } => block_names.iter().fold(
+                e.u8(2)
+                    .u32(*seed)
+                    .f32(*time_of_day)
+                    .u16(block_names.len() as u16),
+                |e, name| e.string(name),
+            ),
+            ServerMessage::Chunk { chunk_pos, data } => e.u8(3).ivec3(*chunk_pos).bytes(data),
+            ServerMessage::BlockChanged { pos, block } => e.u8(4).ivec3(*pos).block(*block),
+            ServerMessage::PlayerJoined { client_id, name } => e.u8(5).u32(*client_id).string(name),
+            ServerMessage::PlayerLeft { client_id } => e.u8(6).u32(*client_id),
+            ServerMessage::PlayerTransform {
+                client_id,
+                tick,
+                translation,
+                rotation,
+            } => e
+                .u8(7)
+                .u32(*client_id)
+                .u32(*tick)
+                .vec3(*translation)
+                .quat(*rotation),
+            ServerMessage::Disconnect { reason } => e.u8(8).string(reason),
+            ServerMessage::Chat { text } => e.u8(9).string(text),
+            ServerMessage::Teleport { translation } => e.u8(10).vec3(*translation),
+            ServerMessage::TimeOfDay {
+                time_of_day,
+                paused,
+            } => e.u8(11).f32(*time_of_day).u8(*paused as u8),
+            ServerMessage::SetGameMode { mode } => e.u8(12).u8(*mode as u8),
+            ServerMessage::Selection { corners } => e.u8(13).corner(corners[0]).corner(corners[1]),
+            ServerMessage::BlocksChanged { changes } => changes
+                .iter()
+                .fold(e.u8(14).u32(changes.len() as u32), |e, (pos, block)| {
+                    e.ivec3(*pos).block(*block)
+                }),
+        };
+    }
+
+    fn decode_from(d: &mut Decoder) -> anyhow::Result<Self> {
+        Ok(match d.u8()? {
+            0 => ServerMessage::Welcome {
+                client_id: d.u32()?,
+                token: d.u64()?,
+            },
+            1 => ServerMessage::Rejected {
+                reason: d.string()?,
+            },
+            2 => ServerMessage::WorldInfo {
+                seed: d.u32()?,
+                time_of_day: d.f32()?,
+                block_names: (0..d.u16()?)
+                    .map(|_| d.string())
+                    .collect::<anyhow::Result<_>>()?,
+            },
+            3 => ServerMessage::Chunk {
+                chunk_pos: d.ivec3()?,
+                data: d.bytes()?,
+            },
+            4 => ServerMessage::BlockChanged {
+                pos: d.ivec3()?,
+                block: d.block()?,
+            },
+            5 => ServerMessage::PlayerJoined {
+                client_id: d.u32()?,
+                name: d.string()?,
+            },
+            6 => ServerMessage::PlayerLeft {
+                client_id: d.u32()?,
+            },
+            7 => ServerMessage::PlayerTransform {
+                client_id: d.u32()?,
+                tick: d.u32()?,
+                translation: d.vec3()?,
+                rotation: d.quat()?,
+            },
+            8 => ServerMessage::Disconnect {
+                reason: d.string()?,
+            },
+            9 => ServerMessage::Chat { text: d.string()? },
+            10 => ServerMessage::Teleport {
+                translation: d.vec3()?,
+            },
+            11 => ServerMessage::TimeOfDay {
+                time_of_day: d.f32()?,
+                paused: d.u8()? != 0,
+            },
+            12 => ServerMessage::SetGameMode {
+                mode: match GameMode::ALL.get(d.u8()? as usize) {
+                    Some(mode) => *mode,
+                    None => return Err(anyhow!("Unknown game mode")),
+                },
+            },
+            13 => ServerMessage::Selection {
+                corners: [d.corner()?, d.corner()?],
+            },
+            14 => ServerMessage::BlocksChanged {
+                changes: (0..d.u32()?)
+                    .map(|_| Ok((d.ivec3()?, d.block()?)))
+                    .collect::<anyhow::Result<_>>()?,
+            },
+            tag => return Err(anyhow!("Unknown server message {tag}")),
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut e = Encoder::default();
+        self.encode_into(&mut e);
+        e.0
+    }
+
+    pub fn decode(data: &[u8]) -> anyhow::Result<Self> {
+        let mut d = Decoder(data);
+        let message = Self::decode_from(&mut d)?;
+        d.finish()?;
+        Ok(message)
+    }
+
+    /// Whether the message is sent over UDP rather than the TCP stream.
+    pub fn is_unreliable(&self) -> bool {
+        matches!(self, ServerMessage::PlayerTransform { .. })
+    }
+}
+
+/// UDP packets from clients start with the protocol version and the client's
+/// ID and token from [`ServerMessage::Welcome`].
+pub fn encode_client_datagram(client_id: u32, token: u64, message: &ClientMessage) -> Vec<u8> {
+    let mut e = Encoder::default();
+    e.u16(PROTOCOL_VERSION).u32(client_id).u64(token);
+    message.encode_into(&mut e);
+    e.0
+}
+
+pub fn decode_client_datagram(data: &[u8]) -> anyhow::Result<(u32, u64, ClientMessage)> {
+    let mut d = Decoder(data);
+    let version = d.u16()?;
+    if version != PROTOCOL_VERSION {
+        return Err(anyhow!("Datagram has protocol version {version}"));
+    }
+    let (client_id, token) = (d.u32()?, d.u64()?);
+    let message = ClientMessage::decode_from(&mut d)?;
+    d.finish()?;
+    Ok((client_id, token, message))
+}
+
+/// UDP packets from the server start with the protocol version.
+pub fn encode_server_datagram(message: &ServerMessage) -> Vec<u8> {
+    let mut e = Encoder::default();
+    e.u16(PROTOCOL_VERSION);
+    message.encode_into(&mut e);
+    e.0
+}
+
+pub fn decode_server_datagram(data: &[u8]) -> anyhow::Result<ServerMessage> {
+    let mut d = Decoder(data);
+    let version = d.u16()?;
+    if version != PROTOCOL_VERSION {
+        return Err(anyhow!("Datagram has protocol version {version}"));
+    }
+    let message = ServerMessage::decode_from(&mut d)?;
+    d.finish()?;
+    Ok(message)
+}
+
+/// Compresses a chunk's blocks as little endian `u16`s, `0` for air and the
+/// block ID plus one otherwise.
+pub fn encode_chunk(chunk: &Chunk) -> anyhow::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+    for block in chunk.blocks() {
+        let id = match *block {
+            None => 0,
+            Some(id) if id < MAX_BLOCK_IDS => id as u16 + 1,
+            Some(id) => return Err(anyhow!("Block ID {id} can't be sent")),
+        };
+        encoder.write_all(&id.to_le_bytes())?;
+    }
+    Ok(encoder.finish()?)
+}
+
+/// Decompresses a chunk from [`encode_chunk`], mapping the server's block IDs
+/// to local ones with `remap`.
+pub fn decode_chunk(
+    data: &[u8],
+    remap: &[usize],
+    blocks_info: Arc<Vec<Block>>,
+) -> anyhow::Result<Chunk> {
+    let mut ids = Vec::with_capacity(CHUNK_LEN as usize * 2);
+    ZlibDecoder::new(data)
+        .take(CHUNK_LEN as u64 * 2 + 1)
+        .read_to_end(&mut ids)?;
+    if ids.len() != CHUNK_LEN as usize * 2 {
+        return Err(anyhow!("Chunk payload has {} bytes", ids.len()));
+    }
+    let blocks = ids
+        .chunks_exact(2)
+        .map(|id| match u16::from_le_bytes([id[0], id[1]]) {
+            0 => Ok(None),
+            id => remap
+                .get(id as usize - 1)
+                .copied()
+                .map(Some)
+                .ok_or(anyhow!("Chunk has unknown block ID {}", id - 1)),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(Chunk::from_blocks(&blocks, blocks_info))
+}
+
+/// Non-blocking TCP stream carrying length prefixed frames.
+#[derive(Debug)]
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    closed: bool,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+            closed: false,
+        })
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    /// Whether the peer closed the connection, writing to it failed or it
+    /// fell more than [`MAX_QUEUED_LEN`] bytes behind.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Queues a frame, sent by the next [`Connection::flush`]. Drops the
+    /// connection instead if the queue would grow past [`MAX_QUEUED_LEN`].
+    pub fn send(&mut self, payload: &[u8]) {
+        if self.closed {
+            return;
+        }
+        if self.outgoing.len() + 4 + payload.len() > MAX_QUEUED_LEN {
+            warn!(
+                "Dropping connection with {} unsent bytes queued",
+                self.outgoing.len()
+            );
+            self.closed = true;
+            self.outgoing = Vec::new();
+            let _ = self.stream.shutdown(Shutdown::Both);
+            return;
+        }
+        self.outgoing
+            .extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        self.outgoing.extend_from_slice(payload);
+    }
+
+    /// Writes as much of the queued data as the socket accepts.
+    pub fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => {
+                    self.closed = true;
+                    return Err(io::ErrorKind::WriteZero.into());
+                }
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    self.closed = true;
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads all complete frames that arrived so far.
+    pub fn receive(&mut self) -> io::Result<Vec<Vec<u8>>> {
+        let mut buf = [0; 16 * 1024];
+        while !self.closed {
+            match self.stream.read(&mut buf) {
+                Ok(0) => self.closed = true,
+                Ok(read) => self.incoming.extend_from_slice(&buf[..read]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    self.closed = true;
+                    return Err(e);
+                }
+            }
+        }
+        let mut frames = Vec::new();
+        let mut start = 0;
+        while let Some(len) = self.incoming[start..].first_chunk::<4>() {
+            let len = u32::from_le_bytes(*len) as usize;
+            if len > MAX_FRAME_LEN {
+                self.closed = true;
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Frame of {len} bytes is too long"),
+                ));
+            }
+            if self.incoming.len() < start + 4 + len {
+                break;
+            }
+            frames.push(self.incoming[start + 4..start + 4 + len].to_vec());
+            start += 4 + len;
+        }
+        self.incoming.drain(..start);
+        Ok(frames)
+    }
+}
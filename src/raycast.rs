@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+
+use crate::block::{BlockBox, BlockShape};
+use crate::chunk::{block_min_corner, world_to_block, ChunkMap};
+
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit {
+    pub block_pos: IVec3,
+    /// Normal of the hit face, zero if the ray starts inside the block.
+    pub normal: IVec3,
+}
+
+/// Distance along the ray to `min..max` and the normal of the entered face.
+fn ray_box(origin: Vec3, dir: Vec3, min: Vec3, max: Vec3) -> Option<(f32, IVec3)> {
+    let mut t_near = f32::NEG_INFINITY;
+    let mut t_far = f32::INFINITY;
+    let mut normal = IVec3::ZERO;
+    for axis in 0..3 {
+        if dir[axis] == 0.0 {
+            if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let t1 = (min[axis] - origin[axis]) / dir[axis];
+        let t2 = (max[axis] - origin[axis]) / dir[axis];
+        let (t1, t2) = (t1.min(t2), t1.max(t2));
+        if t1 > t_near {
+            t_near = t1;
+            normal = IVec3::ZERO;
+            normal[axis] = -dir[axis].signum() as i32;
+        }
+        t_far = t_far.min(t2);
+    }
+    match t_near <= t_far && t_far >= 0.0 {
+        true if t_near < 0.0 => Some((0.0, IVec3::ZERO)),
+        true => Some((t_near, normal)),
+        false => None,
+    }
+}
+
+/// Walks the block grid along the ray and returns the first block whose shape
+/// it hits within `max_distance`.
+pub fn raycast(chunk_map: &ChunkMap, origin: Vec3, dir: Vec3, max_distance: f32) -> Option<RayHit> {
+    let dir = dir.normalize_or_zero();
+    if dir == Vec3::ZERO {
+        return None;
+    }
+    let mut block_pos = world_to_block(origin);
+    let step = dir.signum().as_ivec3();
+    let next_boundary = |pos: IVec3, axis: usize| {
+        let corner = block_min_corner(pos)[axis];
+        match step[axis] > 0 {
+            true => corner + 1.0,
+            false => corner,
+        }
+    };
+    let mut t_max = Vec3::from_array(std::array::from_fn(|axis| match dir[axis] {
+        0.0 => f32::INFINITY,
+        d => (next_boundary(block_pos, axis) - origin[axis]) / d,
+    }));
+    let t_delta = dir.recip().abs();
+    loop {
+        if let Some((_, block)) = chunk_map.block_info_at(block_pos) {
+            let corner = block_min_corner(block_pos);
+            let boxes = match block.shape() {
+                BlockShape::Cross => vec![BlockBox::FULL],
+                shape => shape.boxes().to_vec(),
+            };
+            let hit = boxes
+                .iter()
+                .filter_map(|b| ray_box(origin, dir, corner + b.min, corner + b.max))
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+            if let Some((distance, normal)) = hit {
+                if distance <= max_distance {
+                    return Some(RayHit { block_pos, normal });
+                }
+            }
+        }
+        let axis = match t_max.min_element() {
+            t if t == t_max.x => 0,
+            t if t == t_max.y => 1,
+            _ => 2,
+        };
+        if t_max[axis] > max_distance {
+            return None;
+        }
+        block_pos[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+    }
+}
@@ -0,0 +1,239 @@
+use bevy::{prelude::*, utils::hashbrown::HashMap};
+use nipahblocks::net::FromServer;
+use nipahblocks::protocol::ServerMessage;
+use std::collections::VecDeque;
+
+use crate::network::TRANSFORM_RATE;
+use crate::GameState;
+
+/// How far behind the newest snapshot remote players are shown, so there is
+/// usually a later snapshot to interpolate towards.
+const INTERPOLATION_DELAY: f64 = 2.5 / TRANSFORM_RATE as f64;
+/// Longest time a remote player keeps moving after its snapshots stop.
+const MAX_EXTRAPOLATION: f64 = 0.25;
+/// Snapshots kept per remote player.
+const SNAPSHOT_HISTORY: usize = 32;
+/// How quickly the estimated clock offset follows new samples.
+const CLOCK_SMOOTHING: f64 = 0.05;
+const AVATAR_SIZE: Vec3 = Vec3::new(0.6, 1.8, 0.6);
+/// Transforms are sent for the player's eyes, the avatar is centered below.
+const AVATAR_OFFSET: Vec3 = Vec3::new(0.0, -0.7, 0.0);
+const NAME_TAG_HEIGHT: f32 = 0.5;
+
+pub struct RemotePlayerPlugin;
+
+impl Plugin for RemotePlayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RemotePlayers>().add_systems(
+            Update,
+            (
+                receive_remote_players,
+                interpolate_remote_players,
+                place_name_tags,
+            )
+                .chain()
+                .run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Snapshot {
+    /// Seconds on the sending player's clock.
+    time: f64,
+    translation: Vec3,
+    rotation: Quat,
+}
+
+#[derive(Debug, Component)]
+pub struct RemotePlayer {
+    pub name: String,
+    snapshots: VecDeque<Snapshot>,
+    /// Local time minus the sender's time, including network delay.
+    clock_offset: Option<f64>,
+    name_tag: Entity,
+}
+
+impl RemotePlayer {
+    fn push(&mut self, snapshot: Snapshot, now: f64) {
+        let offset = now - snapshot.time;
+        self.clock_offset = Some(match self.clock_offset {
+            Some(current) => current + (offset - current) * CLOCK_SMOOTHING,
+            None => offset,
+        });
+        if self.snapshots.len() == SNAPSHOT_HISTORY {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// Transform at `now`, interpolated between the snapshots around it or
+    /// extrapolated from the last two if newer ones are missing.
+    fn sample(&self, now: f64) -> Option<(Vec3, Quat)> {
+        let time = now - self.clock_offset? - INTERPOLATION_DELAY;
+        let last = self.snapshots.back()?;
+        let next = self.snapshots.iter().position(|s| s.time >= time);
+        let (from, to) = match next {
+            Some(0) => return Some((self.snapshots[0].translation, self.snapshots[0].rotation)),
+            Some(i) => (self.snapshots[i - 1], self.snapshots[i]),
+            None if self.snapshots.len() >= 2 => (self.snapshots[self.snapshots.len() - 2], *last),
+            None => return Some((last.translation, last.rotation)),
+        };
+        let time = time.min(last.time + MAX_EXTRAPOLATION);
+        let t = ((time - from.time) / (to.time - from.time).max(f64::EPSILON)) as f32;
+        // Past `to` this continues the movement between the two snapshots.
+        Some((
+            from.translation.lerp(to.translation, t),
+            from.rotation.slerp(to.rotation, t.min(1.0)),
+        ))
+    }
+}
+
+/// Remote player entities by client ID.
+#[derive(Debug, Default, Resource, Deref, DerefMut)]
+struct RemotePlayers(HashMap<u32, Entity>);
+
+#[derive(Debug, Component)]
+struct NameTag;
+
+/// Color derived from the client ID, so players keep theirs between frames.
+fn avatar_color(client_id: u32) -> Color {
+    Color::hsl((client_id as f32 * 137.5) % 360.0, 0.6, 0.5)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn receive_remote_players(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut remote_players: ResMut<RemotePlayers>,
+    mut from_server: EventReader<FromServer>,
+    mut players_q: Query<&mut RemotePlayer>,
+) {
+    let now = time.elapsed_secs_f64();
+    for FromServer(message) in from_server.read() {
+        match message {
+            ServerMessage::PlayerJoined { client_id, name } => {
+                info!("{name} joined the game");
+                let name_tag = commands
+                    .spawn((
+                        NameTag,
+                        Text::new(name.clone()),
+                        TextColor(Color::WHITE),
+                        TextFont {
+                            font: asset_server.load("fonts/RobotoMono-Regular.ttf"),
+                            font_size: 16.0,
+                            ..default()
+                        },
+                        Node {
+                            position_type: PositionType::Absolute,
+                            ..default()
+                        },
+                        Visibility::Hidden,
+                    ))
+                    .id();
+                let avatar = commands
+                    .spawn((
+                        RemotePlayer {
+                            name: name.clone(),
+                            snapshots: VecDeque::new(),
+                            clock_offset: None,
+                            name_tag,
+                        },
+                        Mesh3d(meshes.add(Cuboid::from_size(AVATAR_SIZE))),
+                        MeshMaterial3d(materials.add(avatar_color(*client_id))),
+                        Transform::default(),
+                        Visibility::Hidden,
+                    ))
+                    .id();
+                if let Some(old) = remote_players.insert(*client_id, avatar) {
+                    despawn_remote_player(&mut commands, &players_q, old);
+                }
+            }
+            ServerMessage::PlayerLeft { client_id } => {
+                if let Some(avatar) = remote_players.remove(client_id) {
+                    despawn_remote_player(&mut commands, &players_q, avatar);
+                }
+            }
+            ServerMessage::PlayerTransform {
+                client_id,
+                tick,
+                translation,
+                rotation,
+            } => {
+                let Some(mut player) = remote_players
+                    .get(client_id)
+                    .and_then(|avatar| players_q.get_mut(*avatar).ok())
+                else {
+                    continue;
+                };
+                let snapshot = Snapshot {
+                    time: *tick as f64 / TRANSFORM_RATE as f64,
+                    translation: *translation,
+                    rotation: *rotation,
+                };
+                player.push(snapshot, now);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn despawn_remote_player(
+    commands: &mut Commands,
+    players_q: &Query<&mut RemotePlayer>,
+    avatar: Entity,
+) {
+    if let Ok(player) = players_q.get(avatar) {
+        info!("{} left the game", player.name);
+        commands.entity(player.name_tag).despawn();
+    }
+    commands.entity(avatar).despawn();
+}
+
+fn interpolate_remote_players(
+    time: Res<Time>,
+    mut players_q: Query<(&RemotePlayer, &mut Transform, &mut Visibility)>,
+) {
+    let now = time.elapsed_secs_f64();
+    for (player, mut transform, mut visibility) in &mut players_q {
+        let Some((translation, rotation)) = player.sample(now) else {
+            continue;
+        };
+        // Only the yaw turns the body.
+        let (yaw, _, _) = rotation.to_euler(EulerRot::YXZ);
+        *transform = Transform::from_translation(translation + AVATAR_OFFSET)
+            .with_rotation(Quat::from_rotation_y(yaw));
+        *visibility = Visibility::Inherited;
+    }
+}
+
+fn place_name_tags(
+    camera_q: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    players_q: Query<(&RemotePlayer, &Transform, &Visibility), Without<NameTag>>,
+    mut tags_q: Query<(&mut Node, &mut Visibility, &ComputedNode), With<NameTag>>,
+) {
+    let Ok((camera, camera_transform)) = camera_q.get_single() else {
+        return;
+    };
+    for (player, transform, avatar_visibility) in &players_q {
+        let Ok((mut node, mut visibility, computed)) = tags_q.get_mut(player.name_tag) else {
+            continue;
+        };
+        let head = transform.translation + Vec3::Y * (AVATAR_SIZE.y / 2.0 + NAME_TAG_HEIGHT);
+        let position = match avatar_visibility {
+            Visibility::Hidden => None,
+            _ => camera.world_to_viewport(camera_transform, head).ok(),
+        };
+        let Some(position) = position else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        let size = computed.size() * computed.inverse_scale_factor();
+        node.left = Val::Px(position.x - size.x / 2.0);
+        node.top = Val::Px(position.y - size.y);
+        *visibility = Visibility::Inherited;
+    }
+}
@@ -0,0 +1,116 @@
+use bevy::prelude::*;
+use nipahblocks::chunk::ChunkMap;
+use nipahblocks::generator::WorldSeed;
+use nipahblocks::persistence::{WorldSave, UNKNOWN_BLOCK};
+use nipahblocks::world_time::WorldTime;
+use std::path::PathBuf;
+
+use crate::chunks::refresh_chunks;
+use crate::daylight::TimeCommand;
+use crate::{GameResources, GameState};
+
+const AUTOSAVE_INTERVAL_SECS: f32 = 30.0;
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AutosaveTimer(Timer::from_seconds(
+            AUTOSAVE_INTERVAL_SECS,
+            TimerMode::Repeating,
+        )))
+        .add_systems(OnEnter(GameState::InGame), open_world)
+        .add_systems(
+            Update,
+            (
+                sync_block_ids.run_if(resource_exists_and_changed::<GameResources>),
+                autosave,
+            )
+                .chain()
+                .after(refresh_chunks)
+                .run_if(in_state(GameState::InGame).and(resource_exists::<WorldSave>)),
+        )
+        .add_systems(
+            Last,
+            save_on_exit.run_if(resource_exists::<WorldSave>.and(on_event::<AppExit>)),
+        );
+    }
+}
+
+#[derive(Debug, Resource)]
+pub struct SaveConfig {
+    pub dir: PathBuf,
+    pub seed: Option<u32>,
+    /// Overrides the saved time of day.
+    pub time: Option<f32>,
+}
+
+#[derive(Debug, Resource, Deref, DerefMut)]
+struct AutosaveTimer(Timer);
+
+fn open_world(
+    mut commands: Commands,
+    config: Res<SaveConfig>,
+    game_resources: Res<GameResources>,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut time_commands: EventWriter<TimeCommand>,
+) -> Result {
+    let world_save = WorldSave::open(&config.dir, config.seed, &game_resources.block_names)?;
+    // Chunks are loaded with the registry's indices from the first frame on,
+    // before `refresh_chunks` runs.
+    chunk_map.set_registry(
+        game_resources.block_names.clone(),
+        game_resources.blocks.clone(),
+        UNKNOWN_BLOCK,
+    );
+    info!(
+        "Opened world {} with seed {}",
+        config.dir.display(),
+        world_save.seed()
+    );
+    commands.insert_resource(WorldSeed(world_save.seed()));
+    commands.insert_resource(WorldTime::new(world_save.time_of_day()));
+    if let Some(time) = config.time {
+        time_commands.send(TimeCommand::Set(time));
+    }
+    commands.insert_resource(world_save);
+    Ok(())
+}
+
+fn sync_block_ids(mut world_save: ResMut<WorldSave>, game_resources: Res<GameResources>) -> Result {
+    world_save.map_block_ids(&game_resources.block_names)?;
+    Ok(())
+}
+
+fn autosave(
+    time: Res<Time>,
+    mut timer: ResMut<AutosaveTimer>,
+    mut world_save: ResMut<WorldSave>,
+    mut chunk_map: ResMut<ChunkMap>,
+    world_time: Option<Res<WorldTime>>,
+) -> Result {
+    if !timer.tick(time.delta()).just_finished() {
+        return Ok(());
+    }
+    if let Some(world_time) = world_time {
+        world_save.save_time(world_time.time_of_day)?;
+    }
+    let saved = world_save.save_dirty_chunks(&mut chunk_map)?;
+    if saved > 0 {
+        info!("Autosaved {saved} chunks");
+    }
+    Ok(())
+}
+
+fn save_on_exit(
+    mut world_save: ResMut<WorldSave>,
+    mut chunk_map: ResMut<ChunkMap>,
+    world_time: Option<Res<WorldTime>>,
+) -> Result {
+    if let Some(world_time) = world_time {
+        world_save.save_time(world_time.time_of_day)?;
+    }
+    let saved = world_save.save_dirty_chunks(&mut chunk_map)?;
+    info!("Saved {saved} chunks to {}", world_save.dir().display());
+    Ok(())
+}
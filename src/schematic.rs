@@ -0,0 +1,211 @@
+use anyhow::anyhow;
+use bevy::{prelude::*, utils::hashbrown::HashMap};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use std::{
+    collections::BTreeSet,
+    fs,
+    io::{Read, Write},
+    path::Path,
+};
+
+use crate::persistence::UNKNOWN_BLOCK;
+use crate::world_edit::{Clipboard, MAX_VOLUME};
+
+pub const SCHEMATIC_EXTENSION: &str = "schem";
+const MAGIC: &[u8; 4] = b"NBSC";
+const FORMAT_VERSION: u32 = 1;
+/// Longest uncompressed schematic read, well above what a schematic of
+/// [`MAX_VOLUME`] blocks with a full registry as its palette takes.
+const MAX_DATA_LEN: u64 = 16 << 20;
+/// Farthest the minimum corner may be from the paste origin on any axis, so
+/// pasting can't overflow block positions.
+const MAX_OFFSET: i32 = 1 << 24;
+
+/// A saved build. Blocks are stored by name so schematics can be shared
+/// between worlds with different block registries.
+///
+/// Files are zlib compressed and contain, little endian: magic `NBSC`, the
+/// format version (`u32`), size (3 `u32`), offset of the minimum corner from
+/// the paste origin (3 `i32`), the palette (`u16` count, then `u16` length
+/// prefixed names) and the block data. Blocks are palette indices plus one
+/// with `0` for air, packed into `u64` words with as many bits as the largest
+/// index needs and no entry spanning two words.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schematic {
+    pub size: UVec3,
+    pub offset: IVec3,
+    pub palette: Vec<String>,
+    /// In [`Clipboard`] order.
+    blocks: Vec<u16>,
+}
+
+fn bits_per_block(palette_len: usize) -> u32 {
+    (usize::BITS - palette_len.leading_zeros()).max(1)
+}
+
+impl Schematic {
+    /// `block_names` is indexed by the IDs in `clipboard`.
+    pub fn from_clipboard(clipboard: &Clipboard, block_names: &[String]) -> Self {
+        let mut palette = Vec::new();
+        let mut indices = HashMap::new();
+        let blocks = clipboard
+            .blocks()
+            .iter()
+            .map(|block| match block {
+                None => 0,
+                Some(id) => *indices.entry(*id).or_insert_with(|| {
+                    palette.push(block_names[*id].clone());
+                    palette.len() as u16
+                }),
+            })
+            .collect();
+        Self {
+            size: clipboard.size(),
+            offset: clipboard.offset(),
+            palette,
+            blocks,
+        }
+    }
+
+    /// Maps block names through `blocks_map`. Names missing from it become
+    /// [`UNKNOWN_BLOCK`], or air without one, and are returned sorted.
+    pub fn to_clipboard(&self, blocks_map: &HashMap<String, usize>) -> (Clipboard, Vec<String>) {
+        let unknown_block = blocks_map.get(UNKNOWN_BLOCK).copied();
+        let mut unknown = BTreeSet::new();
+        let ids = self
+            .palette
+            .iter()
+            .map(|name| {
+                blocks_map.get(name).copied().or_else(|| {
+                    unknown.insert(name.clone());
+                    unknown_block
+                })
+            })
+            .collect::<Vec<_>>();
+        let blocks = self
+            .blocks
+            .iter()
+            .map(|&index| match index {
+                0 => None,
+                index => ids[index as usize - 1],
+            })
+            .collect();
+        (
+            Clipboard::from_blocks(self.size, blocks, self.offset),
+            unknown.into_iter().collect(),
+        )
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        for value in self.size.to_array() {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in self.offset.to_array() {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&(self.palette.len() as u16).to_le_bytes());
+        for name in &self.palette {
+            data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            data.extend_from_slice(name.as_bytes());
+        }
+        let bits = bits_per_block(self.palette.len());
+        let per_word = (u64::BITS / bits) as usize;
+        for blocks in self.blocks.chunks(per_word) {
+            let word = blocks.iter().enumerate().fold(0u64, |word, (i, &block)| {
+                word | ((block as u64) << (i as u32 * bits))
+            });
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+        data
+    }
+
+    pub fn decode(data: &[u8]) -> anyhow::Result<Self> {
+        let mut data = data;
+        let mut take = |len: usize| {
+            let (head, rest) = data
+                .split_at_checked(len)
+                .ok_or(anyhow!("Schematic is truncated"))?;
+            data = rest;
+            anyhow::Ok(head)
+        };
+        let u32 = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap());
+        if take(4)? != MAGIC {
+            return Err(anyhow!("Not a schematic"));
+        }
+        let version = u32(take(4)?);
+        if version != FORMAT_VERSION {
+            return Err(anyhow!("Unsupported schematic version {version}"));
+        }
+        let mut size = UVec3::ZERO;
+        for axis in 0..3 {
+            size[axis] = u32(take(4)?);
+        }
+        let mut offset = IVec3::ZERO;
+        for axis in 0..3 {
+            offset[axis] = u32(take(4)?) as i32;
+        }
+        if offset.max_element() > MAX_OFFSET || offset.min_element() < -MAX_OFFSET {
+            return Err(anyhow!("Schematic offset {offset} is too large"));
+        }
+        let palette_len = u16::from_le_bytes(take(2)?.try_into()?) as usize;
+        let palette = (0..palette_len)
+            .map(|_| {
+                let len = u16::from_le_bytes(take(2)?.try_into()?) as usize;
+                Ok(String::from_utf8(take(len)?.to_vec())?)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let volume = (size.x as u64 * size.y as u64)
+            .checked_mul(size.z as u64)
+            .filter(|&volume| volume <= MAX_VOLUME as u64)
+            .ok_or(anyhow!(
+                "Schematic of size {size} has more than {MAX_VOLUME} blocks"
+            ))? as usize;
+        let bits = bits_per_block(palette_len);
+        let per_word = (u64::BITS / bits) as usize;
+        let words = take(volume.div_ceil(per_word) * 8)?;
+        if !data.is_empty() {
+            return Err(anyhow!("Schematic has {} trailing bytes", data.len()));
+        }
+        let mask = (1u64 << bits) - 1;
+        let blocks = (0..volume)
+            .map(|i| {
+                let word = u64::from_le_bytes(words[i / per_word * 8..][..8].try_into().unwrap());
+                let block = ((word >> ((i % per_word) as u32 * bits)) & mask) as u16;
+                match block as usize <= palette_len {
+                    true => Ok(block),
+                    false => Err(anyhow!("Schematic has invalid palette index {block}")),
+                }
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self {
+            size,
+            offset,
+            palette,
+            blocks,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut encoder = ZlibEncoder::new(fs::File::create(path)?, Compression::default());
+        encoder.write_all(&self.encode())?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let mut data = Vec::new();
+        ZlibDecoder::new(fs::File::open(path)?)
+            .take(MAX_DATA_LEN + 1)
+            .read_to_end(&mut data)?;
+        if data.len() as u64 > MAX_DATA_LEN {
+            return Err(anyhow!("Schematic is larger than {MAX_DATA_LEN} bytes"));
+        }
+        Self::decode(&data)
+    }
+}
@@ -0,0 +1,40 @@
+use bevy::prelude::*;
+use nipahblocks::chunk::block_min_corner;
+use nipahblocks::world_edit::{EditSession, Region};
+
+use crate::player::Player;
+
+/// Grows the outline slightly so it isn't hidden inside block faces.
+const OUTLINE_MARGIN: f32 = 0.01;
+const CORNER_COLORS: [Color; 2] = [Color::srgb(1.0, 0.4, 0.2), Color::srgb(0.2, 0.6, 1.0)];
+const SELECTION_COLOR: Color = Color::srgb(1.0, 1.0, 1.0);
+
+/// Outlines the world-edit selection of the player.
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, draw_selection);
+    }
+}
+
+fn region_outline(region: Region) -> Transform {
+    let min = block_min_corner(region.min);
+    let max = block_min_corner(region.max) + Vec3::ONE;
+    Transform::from_translation((min + max) / 2.0)
+        .with_scale(max - min + Vec3::splat(OUTLINE_MARGIN * 2.0))
+}
+
+fn draw_selection(mut gizmos: Gizmos, session_q: Query<&EditSession, With<Player>>) {
+    let Ok(session) = session_q.get_single() else {
+        return;
+    };
+    for (corner, color) in session.corners.iter().zip(CORNER_COLORS) {
+        if let Some(pos) = corner {
+            gizmos.cuboid(region_outline(Region::new(*pos, *pos)), color);
+        }
+    }
+    if let Some(region) = session.selection() {
+        gizmos.cuboid(region_outline(region), SELECTION_COLOR);
+    }
+}
@@ -0,0 +1,296 @@
+use anyhow::anyhow;
+use bevy::{prelude::*, utils::hashbrown::HashMap};
+use std::{fs, path::Path};
+
+use crate::block_registry::{BlockInfoRegistry, BlockShapeInfo, Transparency};
+use crate::command::AIR;
+use crate::persistence::UNKNOWN_BLOCK;
+use crate::world_edit::{Clipboard, MAX_VOLUME};
+
+pub const VOX_EXTENSION: &str = "vox";
+const MAGIC: &[u8; 4] = b"VOX ";
+/// Largest model side MagicaVoxel supports, voxel coordinates are `u8`s.
+const MAX_MODEL_SIZE: u32 = 256;
+
+/// The first model of a MagicaVoxel `.vox` file. Positions are in
+/// MagicaVoxel's axes, with z pointing up.
+#[derive(Debug, Clone)]
+pub struct VoxModel {
+    pub size: UVec3,
+    /// Voxel positions and their palette indices.
+    pub voxels: Vec<(UVec3, u8)>,
+    /// RGBA colors by palette index, index `0` is unused.
+    pub palette: [[u8; 4]; 256],
+    /// Models in the file, only the first one is read.
+    pub model_count: usize,
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> anyhow::Result<&'a [u8]> {
+    let (head, rest) = data
+        .split_at_checked(len)
+        .ok_or(anyhow!("Vox file is truncated"))?;
+    *data = rest;
+    Ok(head)
+}
+
+fn read_u32(data: &mut &[u8]) -> anyhow::Result<u32> {
+    Ok(u32::from_le_bytes(take(data, 4)?.try_into()?))
+}
+
+/// Reads a chunk's ID, content and children.
+fn read_chunk<'a>(data: &mut &'a [u8]) -> anyhow::Result<([u8; 4], &'a [u8], &'a [u8])> {
+    let id = take(data, 4)?.try_into()?;
+    let content_len = read_u32(data)? as usize;
+    let children_len = read_u32(data)? as usize;
+    Ok((id, take(data, content_len)?, take(data, children_len)?))
+}
+
+/// The palette MagicaVoxel uses for files that don't store one: a 6×6×6
+/// color cube without black, then red, green, blue and gray ramps.
+fn default_palette() -> [[u8; 4]; 256] {
+    let steps = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    let ramp = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+    let cube = steps
+        .into_iter()
+        .flat_map(|r| {
+            steps
+                .into_iter()
+                .flat_map(move |g| steps.into_iter().map(move |b| [r, g, b]))
+        })
+        .take(215);
+    let ramps = [[1, 0, 0], [0, 1, 0], [0, 0, 1], [1, 1, 1]]
+        .into_iter()
+        .flat_map(|channels: [u8; 3]| ramp.map(|value| channels.map(|c| c * value)));
+    let mut palette = [[0; 4]; 256];
+    for (entry, [r, g, b]) in palette.iter_mut().skip(1).zip(cube.chain(ramps)) {
+        *entry = [r, g, b, 0xff];
+    }
+    palette
+}
+
+/// Parses a `#rrggbb` mapping key.
+fn parse_color(key: &str) -> Option<[u8; 3]> {
+    let hex = key.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let [_, r, g, b] = u32::from_str_radix(hex, 16).ok()?.to_be_bytes();
+    Some([r, g, b])
+}
+
+fn color_distance(a: Srgba, b: Srgba) -> f32 {
+    Oklaba::from(a)
+        .to_vec3()
+        .distance_squared(Oklaba::from(b).to_vec3())
+}
+
+impl VoxModel {
+    pub fn decode(data: &[u8]) -> anyhow::Result<Self> {
+        let mut data = data;
+        if take(&mut data, 4)? != MAGIC {
+            return Err(anyhow!("Not a MagicaVoxel file"));
+        }
+        let version = read_u32(&mut data)?;
+        if version < 150 {
+            return Err(anyhow!("Unsupported vox version {version}"));
+        }
+        let (id, _, mut children) = read_chunk(&mut data)?;
+        if &id != b"MAIN" {
+            return Err(anyhow!("Vox file has no MAIN chunk"));
+        }
+        let mut size = None;
+        let mut voxels = None;
+        let mut palette = None;
+        let mut model_count = 0;
+        // Scene graph, material and other chunks are ignored.
+        while !children.is_empty() {
+            let (id, mut content, _) = read_chunk(&mut children)?;
+            match &id {
+                b"SIZE" => {
+                    model_count += 1;
+                    if size.is_none() {
+                        size = Some(UVec3::new(
+                            read_u32(&mut content)?,
+                            read_u32(&mut content)?,
+                            read_u32(&mut content)?,
+                        ));
+                    }
+                }
+                b"XYZI" if voxels.is_none() => {
+                    let count = read_u32(&mut content)? as usize;
+                    let bytes = take(&mut content, count * 4)?;
+                    voxels = Some(
+                        bytes
+                            .chunks_exact(4)
+                            .filter(|voxel| voxel[3] != 0)
+                            .map(|voxel| {
+                                (
+                                    UVec3::new(voxel[0] as u32, voxel[1] as u32, voxel[2] as u32),
+                                    voxel[3],
+                                )
+                            })
+                            .collect::<Vec<_>>(),
+                    );
+                }
+                b"RGBA" => {
+                    let bytes = take(&mut content, 256 * 4)?;
+                    let mut colors = [[0; 4]; 256];
+                    // Entry i holds the color of palette index i + 1.
+                    for (entry, color) in colors.iter_mut().skip(1).zip(bytes.chunks_exact(4)) {
+                        *entry = color.try_into()?;
+                    }
+                    palette = Some(colors);
+                }
+                _ => {}
+            }
+        }
+        let size = size.ok_or(anyhow!("Vox file has no model"))?;
+        if size.min_element() == 0 || size.max_element() > MAX_MODEL_SIZE {
+            return Err(anyhow!(
+                "Vox model size {size} is outside of 1 to {MAX_MODEL_SIZE}"
+            ));
+        }
+        let voxels = voxels.ok_or(anyhow!("Vox model has no voxels"))?;
+        if voxels.iter().any(|(pos, _)| pos.cmpge(size).any()) {
+            return Err(anyhow!("Vox model has voxels outside of its size {size}"));
+        }
+        Ok(Self {
+            size,
+            voxels,
+            palette: palette.unwrap_or_else(default_palette),
+            model_count,
+        })
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        Self::decode(&fs::read(path)?)
+    }
+
+    pub fn color(&self, index: u8) -> Srgba {
+        let [r, g, b, _] = self.palette[index as usize];
+        Srgba::rgb_u8(r, g, b)
+    }
+
+    /// Picks a block for every palette index the model uses. Indices listed
+    /// in `mapping` by number (`"12"`) or color (`"#a0522d"`) get the named
+    /// block, with [`AIR`] leaving their voxels out. The others get the
+    /// block from `block_colors` closest in color.
+    pub fn map_palette(
+        &self,
+        mapping: &HashMap<String, String>,
+        blocks_map: &HashMap<String, usize>,
+        block_colors: &[(usize, Srgba)],
+    ) -> anyhow::Result<[Option<usize>; 256]> {
+        let mut by_index = HashMap::new();
+        let mut by_color = HashMap::new();
+        for (key, name) in mapping {
+            let block = match name.as_str() {
+                AIR => None,
+                name => Some(
+                    *blocks_map
+                        .get(name)
+                        .ok_or_else(|| anyhow!("Unknown block \"{name}\" in palette mapping"))?,
+                ),
+            };
+            match (key.parse::<u8>(), parse_color(key)) {
+                (Ok(index), _) if index > 0 => by_index.insert(index, block),
+                (_, Some(color)) => by_color.insert(color, block),
+                _ => {
+                    return Err(anyhow!(
+                    "Palette mapping key \"{key}\" isn't an index from 1 to 255 or a #rrggbb color"
+                ))
+                }
+            };
+        }
+        let mut used = [false; 256];
+        for &(_, index) in &self.voxels {
+            used[index as usize] = true;
+        }
+        let mut blocks = [None; 256];
+        for index in (1..=255).filter(|&index| used[index as usize]) {
+            let [r, g, b, _] = self.palette[index as usize];
+            blocks[index as usize] = match by_index.get(&index).or(by_color.get(&[r, g, b])) {
+                Some(&block) => block,
+                None => {
+                    let color = self.color(index);
+                    let nearest = block_colors.iter().min_by(|(_, a), (_, b)| {
+                        color_distance(color, *a).total_cmp(&color_distance(color, *b))
+                    });
+                    Some(
+                        nearest
+                            .ok_or(anyhow!("No blocks to match palette colors against"))?
+                            .0,
+                    )
+                }
+            };
+        }
+        Ok(blocks)
+    }
+
+    /// Size with y pointing up.
+    pub fn world_size(&self) -> UVec3 {
+        UVec3::new(self.size.x, self.size.z, self.size.y)
+    }
+
+    /// Position of the minimum corner relative to the paste origin, which is
+    /// at the bottom center.
+    pub fn offset(&self) -> IVec3 {
+        let size = self.world_size().as_ivec3();
+        IVec3::new(-size.x / 2, 0, -size.z / 2)
+    }
+
+    /// Blocks relative to the paste origin, turned so MagicaVoxel's z axis
+    /// points up. `palette_blocks` comes from [`VoxModel::map_palette`].
+    pub fn blocks<'a>(
+        &'a self,
+        palette_blocks: &'a [Option<usize>; 256],
+    ) -> impl Iterator<Item = (IVec3, usize)> + 'a {
+        let offset = self.offset();
+        self.voxels.iter().filter_map(move |&(pos, index)| {
+            let pos = UVec3::new(pos.x, pos.z, self.size.y - 1 - pos.y);
+            palette_blocks[index as usize].map(|block| (offset + pos.as_ivec3(), block))
+        })
+    }
+
+    pub fn to_clipboard(&self, palette_blocks: &[Option<usize>; 256]) -> anyhow::Result<Clipboard> {
+        let size = self.world_size();
+        if size.element_product() > MAX_VOLUME {
+            return Err(anyhow!(
+                "Vox model is {size}, more than {MAX_VOLUME} blocks"
+            ));
+        }
+        let mut clipboard = Clipboard::from_blocks(
+            size,
+            vec![None; size.element_product() as usize],
+            self.offset(),
+        );
+        for (pos, block) in self.blocks(palette_blocks) {
+            clipboard.set((pos - self.offset()).as_uvec3(), Some(block));
+        }
+        Ok(clipboard)
+    }
+}
+
+/// Average colors of the full, non-translucent blocks, which palette colors
+/// are matched against. `texture_colors` comes from
+/// [`BlockInfoRegistry::texture_colors`].
+pub fn block_colors(
+    registry: &BlockInfoRegistry,
+    texture_colors: &HashMap<String, Srgba>,
+) -> Vec<(usize, Srgba)> {
+    registry
+        .blocks
+        .iter()
+        .enumerate()
+        .filter(|(_, info)| {
+            info.shape == BlockShapeInfo::Cube
+                && info.transparency != Transparency::Translucent
+                && info.name != UNKNOWN_BLOCK
+        })
+        .filter_map(|(id, info)| {
+            let sum = info.faces().iter().try_fold(Vec3::ZERO, |sum, face| {
+                let color = LinearRgba::from(*texture_colors.get(*face)?);
+                Some(sum + color.to_vec3())
+            })?;
+            let average = sum / 6.0;
+            Some((id, LinearRgba::rgb(average.x, average.y, average.z).into()))
+        })
+        .collect()
+}
@@ -0,0 +1,399 @@
+use anyhow::anyhow;
+use bevy::{prelude::*, utils::hashbrown::HashMap};
+use std::path::PathBuf;
+
+use crate::chunk::{world_to_block, BlockChange, ChunkMap};
+use crate::command::{Position, AIR};
+use crate::raycast::raycast;
+use crate::schematic::{Schematic, SCHEMATIC_EXTENSION};
+
+/// How far away `/pos1` and `/pos2` pick the targeted block.
+const SELECT_DISTANCE: f32 = 64.0;
+/// Largest region a single operation may touch.
+pub const MAX_VOLUME: u32 = 1 << 20;
+pub const SCHEMATICS_DIR: &str = "schematics";
+
+/// Box between two corner blocks, both inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub min: IVec3,
+    pub max: IVec3,
+}
+
+impl Region {
+    pub fn new(a: IVec3, b: IVec3) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    pub fn size(&self) -> UVec3 {
+        (self.max - self.min + IVec3::ONE).as_uvec3()
+    }
+
+    pub fn volume(&self) -> u32 {
+        self.size().element_product()
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = IVec3> {
+        let Region { min, max } = *self;
+        (min.y..=max.y).flat_map(move |y| {
+            (min.z..=max.z).flat_map(move |z| (min.x..=max.x).map(move |x| IVec3::new(x, y, z)))
+        })
+    }
+
+    /// Whether `pos` lies on any face of the box.
+    fn on_shell(&self, pos: IVec3) -> bool {
+        pos.cmpeq(self.min).any() || pos.cmpeq(self.max).any()
+    }
+
+    /// Whether `pos` lies on one of the four vertical sides of the box.
+    fn on_walls(&self, pos: IVec3) -> bool {
+        pos.x == self.min.x || pos.x == self.max.x || pos.z == self.min.z || pos.z == self.max.z
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    fn index(self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+}
+
+/// Blocks copied from the world, positioned relative to where they were
+/// copied from.
+#[derive(Debug, Clone)]
+pub struct Clipboard {
+    size: UVec3,
+    /// Indexed x first, then z, then y, like [`Region::positions`].
+    blocks: Vec<Option<usize>>,
+    /// Position of the minimum corner relative to the paste origin.
+    offset: IVec3,
+}
+
+impl Clipboard {
+    /// Copies `region`, blocks in unloaded chunks become air.
+    pub fn copy(chunk_map: &ChunkMap, region: Region, origin: IVec3) -> Self {
+        Self {
+            size: region.size(),
+            blocks: region
+                .positions()
+                .map(|pos| chunk_map.block_at(pos))
+                .collect(),
+            offset: region.min - origin,
+        }
+    }
+
+    pub fn from_blocks(size: UVec3, blocks: Vec<Option<usize>>, offset: IVec3) -> Self {
+        assert_eq!(blocks.len(), size.element_product() as usize);
+        Self {
+            size,
+            blocks,
+            offset,
+        }
+    }
+
+    pub fn size(&self) -> UVec3 {
+        self.size
+    }
+
+    pub fn blocks(&self) -> &[Option<usize>] {
+        &self.blocks
+    }
+
+    pub fn offset(&self) -> IVec3 {
+        self.offset
+    }
+
+    pub fn set(&mut self, pos: UVec3, block: Option<usize>) {
+        self.blocks[Self::index(self.size, pos)] = block;
+    }
+
+    fn index(size: UVec3, pos: UVec3) -> usize {
+        (pos.x + pos.z * size.x + pos.y * size.x * size.z) as usize
+    }
+
+    fn local_positions(size: UVec3) -> impl Iterator<Item = UVec3> {
+        (0..size.y).flat_map(move |y| {
+            (0..size.z).flat_map(move |z| (0..size.x).map(move |x| UVec3::new(x, y, z)))
+        })
+    }
+
+    /// Rebuilds the blocks with `map` giving each old local position's new
+    /// one in a clipboard of `size`.
+    fn transform(&mut self, size: UVec3, offset: IVec3, map: impl Fn(UVec3) -> UVec3) {
+        let mut blocks = vec![None; self.blocks.len()];
+        for (pos, block) in Self::local_positions(self.size).zip(&self.blocks) {
+            blocks[Self::index(size, map(pos))] = *block;
+        }
+        self.size = size;
+        self.blocks = blocks;
+        self.offset = offset;
+    }
+
+    /// Rotates clockwise around the vertical axis through the paste origin,
+    /// as seen from above.
+    pub fn rotate(&mut self, quarter_turns: u32) {
+        for _ in 0..quarter_turns % 4 {
+            let old = self.size;
+            let size = UVec3::new(old.z, old.y, old.x);
+            let offset = IVec3::new(
+                -(self.offset.z + old.z as i32 - 1),
+                self.offset.y,
+                self.offset.x,
+            );
+            self.transform(size, offset, |pos| {
+                UVec3::new(old.z - 1 - pos.z, pos.y, pos.x)
+            });
+        }
+    }
+
+    /// Mirrors along `axis` through the paste origin.
+    pub fn flip(&mut self, axis: Axis) {
+        let i = axis.index();
+        let size = self.size;
+        let mut offset = self.offset;
+        offset[i] = -(offset[i] + size[i] as i32 - 1);
+        self.transform(size, offset, |mut pos| {
+            pos[i] = size[i] - 1 - pos[i];
+            pos
+        });
+    }
+
+    /// Places the blocks with the paste origin at `origin`. Air is pasted too.
+    pub fn paste(&self, chunk_map: &mut ChunkMap, origin: IVec3) -> Vec<BlockChange> {
+        let min = origin + self.offset;
+        chunk_map.set_blocks(
+            Self::local_positions(self.size)
+                .zip(&self.blocks)
+                .map(|(pos, block)| (min + pos.as_ivec3(), *block)),
+        )
+    }
+}
+
+/// A world-edit operation, see [`EditSession::execute`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditCommand {
+    /// Sets a selection corner, `None` picks the targeted block.
+    SetCorner(usize, Option<Position>),
+    Fill(String),
+    Replace(String, String),
+    /// Fills the faces of the selection and clears the inside.
+    Hollow(String),
+    /// Fills the four vertical sides of the selection.
+    Walls(String),
+    Copy,
+    Paste,
+    Rotate(u32),
+    Flip(Axis),
+    /// Saves the selection as a schematic with the given name.
+    SaveSchematic(String),
+    /// Loads a schematic into the clipboard.
+    LoadSchematic(String),
+}
+
+/// Selection and clipboard of a player.
+#[derive(Debug, Component)]
+pub struct EditSession {
+    pub corners: [Option<IVec3>; 2],
+    clipboard: Option<Clipboard>,
+    schematics_dir: PathBuf,
+}
+
+impl Default for EditSession {
+    fn default() -> Self {
+        Self::new(SCHEMATICS_DIR.into())
+    }
+}
+
+/// Result of an [`EditCommand`].
+#[derive(Debug)]
+pub struct EditOutcome {
+    pub message: String,
+    pub changes: Vec<BlockChange>,
+}
+
+impl EditSession {
+    /// Session saving and loading schematics in `schematics_dir`.
+    pub fn new(schematics_dir: PathBuf) -> Self {
+        Self {
+            corners: [None; 2],
+            clipboard: None,
+            schematics_dir,
+        }
+    }
+
+    fn schematic_path(&self, name: &str) -> PathBuf {
+        self.schematics_dir
+            .join(name)
+            .with_extension(SCHEMATIC_EXTENSION)
+    }
+
+    pub fn selection(&self) -> Option<Region> {
+        match self.corners {
+            [Some(a), Some(b)] => Some(Region::new(a, b)),
+            _ => None,
+        }
+    }
+
+    fn checked_selection(&self) -> anyhow::Result<Region> {
+        let region = self
+            .selection()
+            .ok_or(anyhow!("Select two corners with /pos1 and /pos2 first"))?;
+        if region.volume() > MAX_VOLUME {
+            return Err(anyhow!(
+                "The selection has {} blocks, at most {MAX_VOLUME} are allowed",
+                region.volume()
+            ));
+        }
+        Ok(region)
+    }
+
+    /// Runs `command` for a player at `eye`. Block names are looked up in
+    /// `blocks_map`, with [`AIR`] clearing blocks.
+    pub fn execute(
+        &mut self,
+        command: EditCommand,
+        chunk_map: &mut ChunkMap,
+        blocks_map: &HashMap<String, usize>,
+        eye: &Transform,
+    ) -> anyhow::Result<EditOutcome> {
+        let block = |name: &str| match name {
+            AIR => Ok(None),
+            name => blocks_map
+                .get(name)
+                .map(|id| Some(*id))
+                .ok_or(anyhow!("Unknown block \"{name}\"")),
+        };
+        let origin = world_to_block(eye.translation);
+        let changes = match command {
+            EditCommand::SetCorner(corner, pos) => {
+                let pos = match pos {
+                    Some(pos) => pos.resolve(origin.as_vec3()).floor().as_ivec3(),
+                    None => raycast(chunk_map, eye.translation, *eye.forward(), SELECT_DISTANCE)
+                        .map_or(origin, |hit| hit.block_pos),
+                };
+                self.corners[corner] = Some(pos);
+                let message = match self.selection() {
+                    Some(region) => format!(
+                        "Corner {} set to {pos}, {} blocks selected",
+                        corner + 1,
+                        region.volume()
+                    ),
+                    None => format!("Corner {} set to {pos}", corner + 1),
+                };
+                return Ok(EditOutcome {
+                    message,
+                    changes: Vec::new(),
+                });
+            }
+            EditCommand::Fill(name) => {
+                let (region, id) = (self.checked_selection()?, block(&name)?);
+                chunk_map.set_blocks(region.positions().map(|pos| (pos, id)))
+            }
+            EditCommand::Replace(from, to) => {
+                let (region, from, to) = (self.checked_selection()?, block(&from)?, block(&to)?);
+                let targets = region
+                    .positions()
+                    .filter(|pos| chunk_map.block_at(*pos) == from)
+                    .collect::<Vec<_>>();
+                chunk_map.set_blocks(targets.into_iter().map(|pos| (pos, to)))
+            }
+            EditCommand::Hollow(name) => {
+                let (region, id) = (self.checked_selection()?, block(&name)?);
+                chunk_map.set_blocks(region.positions().map(|pos| match region.on_shell(pos) {
+                    true => (pos, id),
+                    false => (pos, None),
+                }))
+            }
+            EditCommand::Walls(name) => {
+                let (region, id) = (self.checked_selection()?, block(&name)?);
+                chunk_map.set_blocks(
+                    region
+                        .positions()
+                        .filter(|pos| region.on_walls(*pos))
+                        .map(|pos| (pos, id)),
+                )
+            }
+            EditCommand::Copy => {
+                let region = self.checked_selection()?;
+                self.clipboard = Some(Clipboard::copy(chunk_map, region, origin));
+                return Ok(EditOutcome {
+                    message: format!("Copied {} blocks", region.volume()),
+                    changes: Vec::new(),
+                });
+            }
+            EditCommand::Paste => {
+                let clipboard = self.clipboard.as_ref().ok_or(anyhow!("Nothing copied"))?;
+                let volume = clipboard.blocks().len();
+                if volume > MAX_VOLUME as usize {
+                    return Err(anyhow!(
+                        "The clipboard has {volume} blocks, at most {MAX_VOLUME} can be pasted"
+                    ));
+                }
+                clipboard.paste(chunk_map, origin)
+            }
+            EditCommand::Rotate(degrees) => {
+                let clipboard = self.clipboard.as_mut().ok_or(anyhow!("Nothing copied"))?;
+                clipboard.rotate(degrees / 90);
+                return Ok(EditOutcome {
+                    message: format!("Rotated the clipboard by {degrees} degrees"),
+                    changes: Vec::new(),
+                });
+            }
+            EditCommand::Flip(axis) => {
+                let clipboard = self.clipboard.as_mut().ok_or(anyhow!("Nothing copied"))?;
+                clipboard.flip(axis);
+                return Ok(EditOutcome {
+                    message: format!("Flipped the clipboard along {axis:?}"),
+                    changes: Vec::new(),
+                });
+            }
+            EditCommand::SaveSchematic(name) => {
+                let region = self.checked_selection()?;
+                let mut block_names = vec![String::new(); blocks_map.len()];
+                for (block_name, &id) in blocks_map {
+                    block_names[id] = block_name.clone();
+                }
+                let clipboard = Clipboard::copy(chunk_map, region, origin);
+                let path = self.schematic_path(&name);
+                Schematic::from_clipboard(&clipboard, &block_names).save(&path)?;
+                return Ok(EditOutcome {
+                    message: format!("Saved {} blocks to {}", region.volume(), path.display()),
+                    changes: Vec::new(),
+                });
+            }
+            EditCommand::LoadSchematic(name) => {
+                let path = self.schematic_path(&name);
+                let schematic = Schematic::load(&path)
+                    .map_err(|e| anyhow!("Couldn't load {}: {e}", path.display()))?;
+                let (clipboard, unknown) = schematic.to_clipboard(blocks_map);
+                self.clipboard = Some(clipboard);
+                let mut message =
+                    format!("Loaded {name} into the clipboard, size {}", schematic.size);
+                if !unknown.is_empty() {
+                    message += &format!(", unknown blocks: {}", unknown.join(", "));
+                }
+                return Ok(EditOutcome {
+                    message,
+                    changes: Vec::new(),
+                });
+            }
+        };
+        Ok(EditOutcome {
+            message: format!("Changed {} blocks", changes.len()),
+            changes,
+        })
+    }
+}
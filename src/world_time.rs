@@ -0,0 +1,49 @@
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+
+pub const HOURS_PER_DAY: f32 = 24.0;
+const DAY_LENGTH_SECS: f32 = 20.0 * 60.0;
+pub const MIN_DAYLIGHT: f32 = 0.1;
+
+/// Time of day in hours, `0.0..24.0`, with noon at `12.0`.
+#[derive(Debug, Resource)]
+pub struct WorldTime {
+    pub time_of_day: f32,
+    pub paused: bool,
+}
+
+impl WorldTime {
+    pub fn new(time_of_day: f32) -> Self {
+        Self {
+            time_of_day: time_of_day.rem_euclid(HOURS_PER_DAY),
+            paused: false,
+        }
+    }
+
+    /// Advances the clock by `secs` of real time unless paused.
+    pub fn advance(&mut self, secs: f32) {
+        if self.paused {
+            return;
+        }
+        let hours = secs / DAY_LENGTH_SECS * HOURS_PER_DAY;
+        self.time_of_day = (self.time_of_day + hours).rem_euclid(HOURS_PER_DAY);
+    }
+
+    /// Time of day as `HH:MM`.
+    pub fn clock(&self) -> String {
+        let minutes = (self.time_of_day * 60.0) as u32;
+        format!("{:02}:{:02}", minutes / 60, minutes % 60)
+    }
+
+    /// Direction pointing towards the sun, rising in the east at 6:00.
+    pub fn sun_direction(&self) -> Vec3 {
+        let angle = (self.time_of_day - 6.0) / HOURS_PER_DAY * TAU;
+        Vec3::new(angle.cos(), angle.sin(), 0.3).normalize()
+    }
+
+    /// Sky brightness, [`MIN_DAYLIGHT`] at night and `1.0` during the day.
+    pub fn daylight(&self) -> f32 {
+        let t = (self.sun_direction().y * 4.0 + 0.5).clamp(0.0, 1.0);
+        MIN_DAYLIGHT + (1.0 - MIN_DAYLIGHT) * t * t * (3.0 - 2.0 * t)
+    }
+}
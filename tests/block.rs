@@ -0,0 +1,111 @@
+use bevy::prelude::*;
+use nipahblocks::block::{Block, BlockBox, BlockShape, Face};
+use nipahblocks::block_registry::{BlockShapeInfo, Facing, Transparency};
+
+const FULL_FACE: Rect = Rect {
+    min: Vec2::ZERO,
+    max: Vec2::ONE,
+};
+/// Lower half of a side face, as a slab's side projects onto it.
+const LOWER_HALF: Rect = Rect {
+    min: Vec2::ZERO,
+    max: Vec2::new(1.0, 0.5),
+};
+const SIDES: [Face; 4] = [Face::Front, Face::Back, Face::Right, Face::Left];
+
+fn shape(info: BlockShapeInfo) -> BlockShape {
+    BlockShape::from_info(&info)
+}
+
+#[test]
+fn full_block_covers_every_face() {
+    let cube = shape(BlockShapeInfo::Cube);
+    for face in Face::ALL {
+        assert!(cube.covers(face, FULL_FACE), "{face:?}");
+        assert!(cube.covers(face, LOWER_HALF), "{face:?}");
+    }
+}
+
+#[test]
+fn slab_against_full_block() {
+    let slab = shape(BlockShapeInfo::Slab);
+    // A full block's side next to a slab is only half hidden, so it stays.
+    for face in SIDES {
+        assert!(!slab.covers(face, FULL_FACE), "{face:?}");
+    }
+    // The full block below a slab is hidden, the one above isn't.
+    assert!(slab.covers(Face::Bottom, FULL_FACE));
+    assert!(!slab.covers(Face::Top, FULL_FACE));
+    // A slab's side next to a full block is hidden.
+    let cube = shape(BlockShapeInfo::Cube);
+    for face in SIDES {
+        assert!(cube.covers(face, LOWER_HALF), "{face:?}");
+    }
+}
+
+#[test]
+fn slab_against_slab() {
+    let slab = shape(BlockShapeInfo::Slab);
+    // Side by side, the shared faces hide each other.
+    for face in SIDES {
+        assert!(slab.covers(face, LOWER_HALF), "{face:?}");
+    }
+    // Stacked, the lower slab's top doesn't reach the boundary and the upper
+    // slab's bottom isn't hidden by it.
+    assert!(!slab.covers(Face::Top, FULL_FACE));
+}
+
+#[test]
+fn partial_shapes_never_cover_a_full_face() {
+    let shapes = [
+        shape(BlockShapeInfo::FencePost),
+        shape(BlockShapeInfo::Cross),
+        shape(BlockShapeInfo::Boxes {
+            boxes: vec![[0.0, 0.0, 0.0, 16.0, 15.0, 16.0]],
+        }),
+    ];
+    for shape in &shapes {
+        for face in SIDES {
+            assert!(!shape.covers(face, FULL_FACE), "{shape:?} {face:?}");
+        }
+    }
+    // The post touches the top and bottom faces only in their middle.
+    let post = shape(BlockShapeInfo::FencePost);
+    assert!(!post.covers(Face::Top, FULL_FACE));
+    assert!(post.covers(
+        Face::Top,
+        Rect::new(6.0 / 16.0, 6.0 / 16.0, 10.0 / 16.0, 10.0 / 16.0)
+    ));
+    // A cross has no boxes, so nothing is hidden by it.
+    for face in Face::ALL {
+        assert!(!shapes[1].covers(face, LOWER_HALF), "{face:?}");
+    }
+}
+
+#[test]
+fn stairs_cover_their_bottom_half() {
+    let stairs = shape(BlockShapeInfo::Stairs {
+        facing: Facing::North,
+    });
+    assert!(stairs.covers(Face::Bottom, FULL_FACE));
+    assert!(stairs.covers(Face::Front, LOWER_HALF));
+    assert!(!stairs.covers(Face::Front, FULL_FACE));
+}
+
+#[test]
+fn collision_boxes_follow_the_shape() {
+    let block = |info| Block::new([0; 6], shape(info), Transparency::Opaque, 0);
+    assert_eq!(
+        block(BlockShapeInfo::Cube).collision_boxes(),
+        [BlockBox::FULL]
+    );
+    assert_eq!(
+        block(BlockShapeInfo::Slab).collision_boxes(),
+        [BlockBox::new(Vec3::ZERO, Vec3::new(1.0, 0.5, 1.0))]
+    );
+    assert!(block(BlockShapeInfo::Cross).collision_boxes().is_empty());
+    // Only full cubes stop light.
+    assert!(block(BlockShapeInfo::Cube).is_opaque());
+    assert!(!block(BlockShapeInfo::Slab).is_opaque());
+    assert!(!block(BlockShapeInfo::FencePost).is_opaque());
+}
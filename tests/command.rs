@@ -0,0 +1,116 @@
+use bevy::math::Vec3;
+use nipahblocks::command::{complete, Command, Coord, GameMode, Position, TimeAction};
+use nipahblocks::world_edit::{Axis, EditCommand};
+
+#[test]
+fn commands_parse() {
+    let cases = [
+        ("help", Command::Help),
+        ("seed", Command::Seed),
+        (
+            "tp 1 ~ ~-2.5",
+            Command::Teleport(Position([
+                Coord::Absolute(1.0),
+                Coord::Relative(0.0),
+                Coord::Relative(-2.5),
+            ])),
+        ),
+        ("time", Command::Time(TimeAction::Query)),
+        ("time set noon", Command::Time(TimeAction::Set(12.0))),
+        ("time add 1.5", Command::Time(TimeAction::Add(1.5))),
+        ("time pause", Command::Time(TimeAction::Pause(true))),
+        (
+            "setblock 0 64 0 air",
+            Command::SetBlock {
+                pos: Position([
+                    Coord::Absolute(0.0),
+                    Coord::Absolute(64.0),
+                    Coord::Absolute(0.0),
+                ]),
+                block: "air".to_string(),
+            },
+        ),
+        ("gamemode spectator", Command::GameMode(GameMode::Spectator)),
+        ("pos2", Command::Edit(EditCommand::SetCorner(1, None))),
+        (
+            "replace  stone   dirt ",
+            Command::Edit(EditCommand::Replace(
+                "stone".to_string(),
+                "dirt".to_string(),
+            )),
+        ),
+        ("rotate 270", Command::Edit(EditCommand::Rotate(270))),
+        ("flip y", Command::Edit(EditCommand::Flip(Axis::Y))),
+        (
+            "schematic save my_house-2",
+            Command::Edit(EditCommand::SaveSchematic("my_house-2".to_string())),
+        ),
+        ("undo", Command::Undo),
+    ];
+    for (line, command) in cases {
+        assert_eq!(Command::parse(line).unwrap(), command, "{line}");
+    }
+}
+
+#[test]
+fn invalid_commands_are_rejected() {
+    let lines = [
+        "",
+        "explode",
+        "help me",
+        "tp 1 2",
+        "tp 1 2 x",
+        "time set later",
+        "gamemode survival",
+        "rotate 45",
+        "flip w",
+        "schematic save ../escape",
+        "schematic delete house",
+    ];
+    for line in lines {
+        assert!(Command::parse(line).is_err(), "{line}");
+    }
+}
+
+#[test]
+fn positions_resolve_relative_coords() {
+    let Command::Teleport(pos) = Command::parse("tp 10 ~ ~5").unwrap() else {
+        panic!("Parsed a different command");
+    };
+    assert_eq!(
+        pos.resolve(Vec3::new(1.0, 2.0, 3.0)),
+        Vec3::new(10.0, 2.0, 8.0)
+    );
+}
+
+#[test]
+fn command_names_complete() {
+    assert_eq!(complete("ti", &[]), ["time"]);
+    assert_eq!(complete("po", &[]), ["pos1", "pos2"]);
+    assert!(complete("xyz", &[]).is_empty());
+}
+
+#[test]
+fn arguments_complete() {
+    let block_names = ["stone".to_string(), "oak_slab".to_string()];
+    assert_eq!(
+        complete("setblock ~ ~ ~ ", &block_names),
+        [
+            "setblock ~ ~ ~ air",
+            "setblock ~ ~ ~ stone",
+            "setblock ~ ~ ~ oak_slab"
+        ]
+    );
+    assert_eq!(
+        complete("replace stone o", &block_names),
+        ["replace stone oak_slab"]
+    );
+    assert_eq!(complete("gamemode c", &block_names), ["gamemode creative"]);
+    assert_eq!(
+        complete("time set n", &block_names),
+        ["time set noon", "time set night"]
+    );
+    assert!(complete("time add n", &block_names).is_empty());
+    assert_eq!(complete("tp 1 ", &block_names), ["tp 1 ~"]);
+    assert!(complete("seed ", &block_names).is_empty());
+}
@@ -0,0 +1,62 @@
+use bevy::prelude::*;
+use nipahblocks::block::Face;
+use nipahblocks::connectivity::FaceConnectivity;
+
+fn connected_pairs(connectivity: FaceConnectivity) -> Vec<(Face, Face)> {
+    Face::ALL
+        .into_iter()
+        .flat_map(|a| Face::ALL.map(|b| (a, b)))
+        .filter(|&(a, b)| connectivity.connected(a, b))
+        .collect()
+}
+
+#[test]
+fn empty_chunk_connects_all_faces() {
+    let connectivity = FaceConnectivity::compute(|_| false);
+    assert_eq!(connected_pairs(connectivity).len(), 36);
+}
+
+#[test]
+fn solid_chunk_connects_nothing() {
+    let connectivity = FaceConnectivity::compute(|_| true);
+    assert!(connected_pairs(connectivity).is_empty());
+}
+
+#[test]
+fn wall_separates_opposite_faces() {
+    let connectivity = FaceConnectivity::compute(|pos| pos.x == 8);
+    assert!(!connectivity.connected(Face::Left, Face::Right));
+    assert!(connectivity.connected(Face::Left, Face::Top));
+    assert!(connectivity.connected(Face::Right, Face::Front));
+    assert!(connectivity.connected(Face::Top, Face::Bottom));
+    assert!(connectivity.connected(Face::Front, Face::Back));
+
+    // A hole in the wall joins both sides.
+    let connectivity = FaceConnectivity::compute(|pos| pos.x == 8 && pos != IVec3::new(8, 3, 3));
+    assert!(connectivity.connected(Face::Left, Face::Right));
+    assert!(connectivity.connected(Face::Right, Face::Left));
+}
+
+#[test]
+fn enclosed_pocket_connects_nothing() {
+    // A hollow box touching no chunk face.
+    let connectivity = FaceConnectivity::compute(|pos| {
+        let inside = pos.cmpgt(IVec3::splat(2)).all() && pos.cmplt(IVec3::splat(12)).all();
+        !inside || pos.cmpeq(IVec3::splat(3)).any() || pos.cmpeq(IVec3::splat(11)).any()
+    });
+    assert!(connected_pairs(connectivity).is_empty());
+}
+
+#[test]
+fn tunnel_connects_its_ends_only() {
+    let connectivity = FaceConnectivity::compute(|pos| pos.x != 5 || pos.y != 5);
+    assert_eq!(
+        connected_pairs(connectivity),
+        [
+            (Face::Front, Face::Front),
+            (Face::Front, Face::Back),
+            (Face::Back, Face::Front),
+            (Face::Back, Face::Back),
+        ]
+    );
+}
@@ -0,0 +1,61 @@
+use bevy::prelude::*;
+use nipahblocks::chunk::{Chunk, ChunkMap};
+use nipahblocks::history::EditHistory;
+use std::sync::Arc;
+
+fn chunk_map() -> ChunkMap {
+    let mut chunk_map = ChunkMap::default();
+    chunk_map.insert_chunk(IVec3::ZERO, Chunk::new(Arc::new(Vec::new())));
+    chunk_map
+}
+
+#[test]
+fn undo_and_redo_restore_blocks() {
+    let mut chunk_map = chunk_map();
+    let mut history = EditHistory::default();
+    let changes = chunk_map.set_blocks([(IVec3::ONE, Some(1)), (IVec3::new(2, 1, 1), Some(2))]);
+    history.record(changes);
+    let changes = chunk_map.set_blocks([(IVec3::ONE, Some(3))]);
+    history.record(changes);
+
+    let reverted = history.undo(&mut chunk_map).unwrap();
+    assert_eq!(reverted.changes.len(), 1);
+    assert_eq!(chunk_map.block_at(IVec3::ONE), Some(1));
+    history.undo(&mut chunk_map).unwrap();
+    assert_eq!(chunk_map.block_at(IVec3::ONE), None);
+    assert_eq!(chunk_map.block_at(IVec3::new(2, 1, 1)), None);
+    assert!(history.undo(&mut chunk_map).is_none());
+
+    let reverted = history.redo(&mut chunk_map).unwrap();
+    assert_eq!(reverted.describe("Redid"), "Redid 2 block changes");
+    assert_eq!(chunk_map.block_at(IVec3::new(2, 1, 1)), Some(2));
+
+    // A new edit drops what is left to redo.
+    let changes = chunk_map.set_blocks([(IVec3::ZERO, Some(4))]);
+    history.record(changes);
+    assert!(history.redo(&mut chunk_map).is_none());
+}
+
+#[test]
+fn blocks_changed_since_are_skipped() {
+    let mut chunk_map = chunk_map();
+    let mut history = EditHistory::default();
+    let changes = chunk_map.set_blocks([(IVec3::ONE, Some(1)), (IVec3::new(2, 1, 1), Some(1))]);
+    history.record(changes);
+    // Changed by someone else.
+    chunk_map.set_blocks([(IVec3::ONE, Some(2))]);
+
+    let reverted = history.undo(&mut chunk_map).unwrap();
+    assert_eq!(reverted.skipped, 1);
+    assert_eq!(
+        reverted.describe("Undid"),
+        "Undid 1 block changes, skipped 1 changed since"
+    );
+    assert_eq!(chunk_map.block_at(IVec3::ONE), Some(2));
+    assert_eq!(chunk_map.block_at(IVec3::new(2, 1, 1)), None);
+
+    chunk_map.set_blocks([(IVec3::new(2, 1, 1), Some(3))]);
+    let reverted = history.redo(&mut chunk_map).unwrap();
+    assert_eq!((reverted.changes.len(), reverted.skipped), (0, 1));
+    assert_eq!(chunk_map.block_at(IVec3::new(2, 1, 1)), Some(3));
+}
@@ -0,0 +1,118 @@
+use bevy::prelude::*;
+use nipahblocks::block::{Block, BlockShape};
+use nipahblocks::block_registry::{BlockShapeInfo, Transparency};
+use nipahblocks::chunk::{Chunk, ChunkMap, CHUNK_SIZE};
+use nipahblocks::light::{LightChannel, MAX_LIGHT};
+use nipahblocks::persistence::UNKNOWN_BLOCK;
+use std::sync::Arc;
+
+const STONE: usize = 0;
+const LAMP: usize = 1;
+const LAMP_LIGHT: u8 = 12;
+
+fn blocks() -> Arc<Vec<Block>> {
+    let block = |light_emission| {
+        Block::new(
+            [0; 6],
+            BlockShape::from_info(&BlockShapeInfo::Cube),
+            Transparency::Opaque,
+            light_emission,
+        )
+    };
+    Arc::new(vec![block(0), block(LAMP_LIGHT), block(0)])
+}
+
+/// A map with one chunk at the origin, its top layer covered by stone if
+/// `roof` is set.
+fn chunk_map(roof: bool) -> ChunkMap {
+    let blocks = blocks();
+    let mut chunk_map = ChunkMap::default();
+    chunk_map.set_registry(
+        Arc::new(vec![
+            "stone".to_string(),
+            "lamp".to_string(),
+            UNKNOWN_BLOCK.to_string(),
+        ]),
+        blocks.clone(),
+        UNKNOWN_BLOCK,
+    );
+    let mut chunk = Chunk::new(blocks);
+    if roof {
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.set_at(UVec3::new(x, CHUNK_SIZE - 1, z), Some(STONE));
+            }
+        }
+    }
+    chunk_map.insert_chunk(IVec3::ZERO, chunk);
+    chunk_map
+}
+
+fn sky(chunk_map: &ChunkMap, x: i32, y: i32, z: i32) -> u8 {
+    chunk_map
+        .light_at(IVec3::new(x, y, z), LightChannel::Sky)
+        .unwrap()
+}
+
+fn block_light(chunk_map: &ChunkMap, x: i32, y: i32, z: i32) -> u8 {
+    chunk_map
+        .light_at(IVec3::new(x, y, z), LightChannel::Block)
+        .unwrap()
+}
+
+#[test]
+fn open_sky_reaches_the_bottom() {
+    let mut chunk_map = chunk_map(false);
+    assert_eq!(sky(&chunk_map, 0, 0, 0), MAX_LIGHT);
+    chunk_map.set_blocks([(IVec3::new(5, 10, 5), Some(STONE))]);
+    // Lit from the side below the block.
+    assert_eq!(sky(&chunk_map, 5, 9, 5), MAX_LIGHT - 1);
+    assert_eq!(sky(&chunk_map, 5, 0, 5), MAX_LIGHT - 1);
+    assert_eq!(sky(&chunk_map, 6, 0, 5), MAX_LIGHT);
+}
+
+#[test]
+fn roof_shadows_the_chunk() {
+    let mut chunk_map = chunk_map(true);
+    assert_eq!(sky(&chunk_map, 8, 14, 8), 0);
+    assert_eq!(sky(&chunk_map, 8, 0, 8), 0);
+    chunk_map.set_blocks([(IVec3::new(3, 15, 3), None)]);
+    assert_eq!(sky(&chunk_map, 3, 0, 3), MAX_LIGHT);
+    assert_eq!(sky(&chunk_map, 5, 0, 3), MAX_LIGHT - 2);
+    chunk_map.set_blocks([(IVec3::new(3, 15, 3), Some(STONE))]);
+    assert_eq!(sky(&chunk_map, 3, 0, 3), 0);
+    assert_eq!(sky(&chunk_map, 5, 0, 3), 0);
+}
+
+#[test]
+fn block_light_falls_off_with_distance() {
+    let mut chunk_map = chunk_map(true);
+    chunk_map.set_blocks([(IVec3::new(8, 4, 8), Some(LAMP))]);
+    assert_eq!(block_light(&chunk_map, 8, 4, 8), LAMP_LIGHT);
+    assert_eq!(block_light(&chunk_map, 9, 4, 8), LAMP_LIGHT - 1);
+    assert_eq!(block_light(&chunk_map, 8, 2, 10), LAMP_LIGHT - 4);
+    assert_eq!(block_light(&chunk_map, 8, 4, 0), LAMP_LIGHT - 8);
+
+    // Light goes around a block put in its way.
+    chunk_map.set_blocks([(IVec3::new(9, 4, 8), Some(STONE))]);
+    assert_eq!(block_light(&chunk_map, 10, 4, 8), LAMP_LIGHT - 4);
+
+    chunk_map.set_blocks([(IVec3::new(8, 4, 8), None)]);
+    assert_eq!(block_light(&chunk_map, 8, 4, 8), 0);
+    assert_eq!(block_light(&chunk_map, 10, 4, 8), 0);
+}
+
+#[test]
+fn light_spreads_into_inserted_neighbors() {
+    let mut chunk_map = chunk_map(true);
+    chunk_map.set_blocks([(IVec3::new(14, 4, 8), Some(LAMP))]);
+    let mut neighbor = Chunk::new(blocks());
+    for x in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            neighbor.set_at(UVec3::new(x, CHUNK_SIZE - 1, z), Some(STONE));
+        }
+    }
+    chunk_map.insert_chunk(IVec3::new(CHUNK_SIZE as i32, 0, 0), neighbor);
+    assert_eq!(block_light(&chunk_map, 16, 4, 8), LAMP_LIGHT - 2);
+    assert_eq!(block_light(&chunk_map, 20, 4, 8), LAMP_LIGHT - 6);
+}
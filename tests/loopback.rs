@@ -0,0 +1,105 @@
+//! Runs a server and a client app in one process over localhost and checks
+//! that the handshake, a chunk and a block change make it across.
+
+use anyhow::anyhow;
+use bevy::prelude::*;
+use nipahblocks::chunk::Chunk;
+use nipahblocks::net::{
+    FromClient, FromServer, NetClient, NetClientPlugin, NetServer, NetServerPlugin, ServerEvent,
+    Target, ToClients, ToServer,
+};
+use nipahblocks::protocol::{decode_chunk, encode_chunk, ClientMessage, ServerMessage};
+use std::{sync::Arc, thread, time::Duration};
+
+const MAX_UPDATES: usize = 1000;
+
+#[derive(Debug, Default, Resource)]
+struct Received(Vec<ServerMessage>);
+
+fn greet_clients(
+    mut server_events: EventReader<ServerEvent>,
+    mut to_clients: EventWriter<ToClients>,
+) -> Result {
+    for event in server_events.read() {
+        if let ServerEvent::Connected { client, .. } = event {
+            let chunk = Chunk::new(Arc::new(Vec::new()));
+            to_clients.send(ToClients {
+                target: Target::Client(*client),
+                message: ServerMessage::Chunk {
+                    chunk_pos: IVec3::ZERO,
+                    data: encode_chunk(&chunk)?,
+                },
+            });
+        }
+    }
+    Ok(())
+}
+
+fn echo_block_changes(
+    mut from_client: EventReader<FromClient>,
+    mut to_clients: EventWriter<ToClients>,
+) {
+    for FromClient { message, .. } in from_client.read() {
+        if let ClientMessage::SetBlock { pos, block } = *message {
+            to_clients.send(ToClients {
+                target: Target::All,
+                message: ServerMessage::BlockChanged { pos, block },
+            });
+        }
+    }
+}
+
+fn record_messages(
+    mut from_server: EventReader<FromServer>,
+    mut received: ResMut<Received>,
+    mut to_server: EventWriter<ToServer>,
+) {
+    for FromServer(message) in from_server.read() {
+        if let ServerMessage::Welcome { .. } = message {
+            to_server.send(ToServer(ClientMessage::SetBlock {
+                pos: IVec3::new(1, 2, 3),
+                block: None,
+            }));
+        }
+        received.0.push(message.clone());
+    }
+}
+
+#[test]
+fn loopback() -> anyhow::Result<()> {
+    let server = NetServer::bind("127.0.0.1:0".parse()?)?;
+    let addr = server.local_addr()?;
+    let mut server_app = App::new();
+    server_app
+        .add_plugins((MinimalPlugins, NetServerPlugin))
+        .insert_resource(server)
+        .add_systems(Update, (greet_clients, echo_block_changes));
+
+    let mut client_app = App::new();
+    client_app
+        .add_plugins((MinimalPlugins, NetClientPlugin))
+        .insert_resource(NetClient::connect(addr, "loopback")?)
+        .init_resource::<Received>()
+        .add_systems(Update, record_messages);
+
+    for _ in 0..MAX_UPDATES {
+        server_app.update();
+        client_app.update();
+        let received = &client_app.world().resource::<Received>().0;
+        let chunk = received.iter().find_map(|message| match message {
+            ServerMessage::Chunk { data, .. } => Some(data),
+            _ => None,
+        });
+        let block_changed = received.iter().any(|message| {
+            matches!(message, ServerMessage::BlockChanged { pos, block: None }
+                    if *pos == IVec3::new(1, 2, 3))
+        });
+        if let (Some(data), true) = (chunk, block_changed) {
+            let chunk = decode_chunk(data, &[], Arc::new(Vec::new()))?;
+            assert!(chunk.is_empty());
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    Err(anyhow!("Client didn't receive the expected messages"))
+}
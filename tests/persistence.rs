@@ -0,0 +1,157 @@
+use bevy::{prelude::*, utils::hashbrown::HashMap};
+use nipahblocks::chunk::{Chunk, ChunkMap, CHUNK_SIZE};
+use nipahblocks::generator::generate_chunk;
+use nipahblocks::persistence::{WorldSave, UNKNOWN_BLOCK};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// An empty world directory unique to the test, removed when dropped.
+struct TempWorld(PathBuf);
+
+impl TempWorld {
+    fn new(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("nipahblocks-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        Self(dir)
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempWorld {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+fn test_chunk() -> Chunk {
+    let mut chunk = Chunk::new(Arc::new(Vec::new()));
+    for x in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            chunk.set_at(UVec3::new(x, 0, z), Some(0));
+            chunk.set_at(UVec3::new(x, 1, z), Some(((x + z) % 2) as usize));
+        }
+    }
+    chunk
+}
+
+#[test]
+fn chunks_round_trip() {
+    let world = TempWorld::new("round-trip");
+    let block_names = names(&["stone", "dirt", UNKNOWN_BLOCK]);
+    let world_save = WorldSave::open(world.path(), Some(7), &block_names).unwrap();
+    let chunk_pos = IVec3::new(-16, 32, 0);
+    let chunk = test_chunk();
+    world_save.save_chunk(chunk_pos, &chunk).unwrap();
+
+    let world_save = WorldSave::open(world.path(), None, &block_names).unwrap();
+    assert_eq!(world_save.seed(), 7);
+    let loaded = world_save
+        .load_chunk(chunk_pos, Arc::new(Vec::new()))
+        .unwrap()
+        .unwrap();
+    assert_eq!(loaded.blocks(), chunk.blocks());
+    assert!(world_save
+        .load_chunk(IVec3::ZERO, Arc::new(Vec::new()))
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn block_ids_follow_registry_changes() {
+    let world = TempWorld::new("remap");
+    let world_save = WorldSave::open(
+        world.path(),
+        None,
+        &names(&["stone", "dirt", UNKNOWN_BLOCK]),
+    )
+    .unwrap();
+    world_save.save_chunk(IVec3::ZERO, &test_chunk()).unwrap();
+
+    // Reordered, with stone removed and glass added.
+    let block_names = names(&[UNKNOWN_BLOCK, "glass", "dirt"]);
+    let world_save = WorldSave::open(world.path(), None, &block_names).unwrap();
+    let loaded = world_save
+        .load_chunk(IVec3::ZERO, Arc::new(Vec::new()))
+        .unwrap()
+        .unwrap();
+    assert_eq!(loaded.at(UVec3::ZERO), Some(0));
+    assert_eq!(loaded.at(UVec3::new(1, 1, 0)), Some(2));
+    assert_eq!(loaded.at(UVec3::new(0, 2, 0)), None);
+
+    // Glass gets a new ID rather than reusing stone's.
+    let mut chunk = Chunk::new(Arc::new(Vec::new()));
+    chunk.set_at(UVec3::ZERO, Some(1));
+    world_save.save_chunk(IVec3::ZERO, &chunk).unwrap();
+    let world_save = WorldSave::open(
+        world.path(),
+        None,
+        &names(&["stone", "glass", UNKNOWN_BLOCK]),
+    )
+    .unwrap();
+    let loaded = world_save
+        .load_chunk(IVec3::ZERO, Arc::new(Vec::new()))
+        .unwrap()
+        .unwrap();
+    assert_eq!(loaded.at(UVec3::ZERO), Some(1));
+}
+
+#[test]
+fn registry_needs_unknown_block() {
+    let world = TempWorld::new("no-unknown");
+    assert!(WorldSave::open(world.path(), None, &names(&["stone"])).is_err());
+}
+
+#[test]
+fn only_dirty_chunks_are_saved() {
+    let world = TempWorld::new("dirty");
+    let world_save =
+        WorldSave::open(world.path(), None, &names(&["stone", UNKNOWN_BLOCK])).unwrap();
+    let mut chunk_map = ChunkMap::default();
+    chunk_map.insert_chunk(IVec3::ZERO, Chunk::new(Arc::new(Vec::new())));
+    chunk_map.insert_chunk(IVec3::X * 16, Chunk::new(Arc::new(Vec::new())));
+    assert_eq!(world_save.save_dirty_chunks(&mut chunk_map).unwrap(), 0);
+
+    chunk_map.set_blocks([(IVec3::new(17, 0, 0), Some(0))]);
+    assert_eq!(world_save.save_dirty_chunks(&mut chunk_map).unwrap(), 1);
+    assert_eq!(world_save.save_dirty_chunks(&mut chunk_map).unwrap(), 0);
+    let loaded = world_save
+        .load_chunk(IVec3::X * 16, Arc::new(Vec::new()))
+        .unwrap()
+        .unwrap();
+    assert_eq!(loaded.at(UVec3::new(1, 0, 0)), Some(0));
+}
+
+#[test]
+fn generated_chunks_are_not_dirty() {
+    let blocks_map = ["grass", "dirt", "stone"]
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| (name.to_string(), i))
+        .collect::<HashMap<_, _>>();
+    let mut chunk_map = ChunkMap::default();
+    for y in [-16, 0] {
+        let chunk = generate_chunk(
+            Vec3::new(0.0, y as f32, 0.0),
+            1,
+            Arc::new(blocks_map.clone()),
+            Arc::new(Vec::new()),
+        );
+        assert!(!chunk.is_dirty());
+        chunk_map.insert_chunk(IVec3::new(0, y, 0), chunk);
+    }
+    let world = TempWorld::new("generated");
+    let world_save =
+        WorldSave::open(world.path(), None, &names(&["stone", UNKNOWN_BLOCK])).unwrap();
+    assert_eq!(world_save.save_dirty_chunks(&mut chunk_map).unwrap(), 0);
+}
@@ -0,0 +1,263 @@
+use bevy::prelude::*;
+use nipahblocks::chunk::{Chunk, CHUNK_SIZE};
+use nipahblocks::command::GameMode;
+use nipahblocks::protocol::{
+    decode_chunk, decode_client_datagram, decode_server_datagram, encode_chunk,
+    encode_client_datagram, encode_server_datagram, ClientMessage, Connection, ServerMessage,
+    MAX_QUEUED_LEN, MAX_STRING_LEN,
+};
+use std::{
+    net::{TcpListener, TcpStream},
+    sync::Arc,
+};
+
+fn client_messages() -> Vec<ClientMessage> {
+    vec![
+        ClientMessage::Hello {
+            version: 2,
+            name: "player".to_string(),
+        },
+        ClientMessage::PlayerTransform {
+            tick: 7,
+            translation: Vec3::new(1.5, -2.0, 300.25),
+            rotation: Quat::from_xyzw(0.0, 1.0, 0.0, 0.0),
+        },
+        ClientMessage::SetBlock {
+            pos: IVec3::new(-1, 2, -3),
+            block: Some(4),
+        },
+        ClientMessage::SetBlock {
+            pos: IVec3::ZERO,
+            block: None,
+        },
+        ClientMessage::Chat {
+            text: "/fill stone ünïcode".to_string(),
+        },
+        ClientMessage::Disconnect,
+    ]
+}
+
+fn server_messages() -> Vec<ServerMessage> {
+    vec![
+        ServerMessage::Welcome {
+            client_id: 3,
+            token: u64::MAX - 1,
+        },
+        ServerMessage::Rejected {
+            reason: "Wrong version".to_string(),
+        },
+        ServerMessage::WorldInfo {
+            seed: 123456,
+            time_of_day: 8.5,
+            block_names: vec!["unknown".to_string(), "stone".to_string()],
+        },
+        ServerMessage::Chunk {
+            chunk_pos: IVec3::new(16, -32, 48),
+            data: vec![1, 2, 3, 4],
+        },
+        ServerMessage::BlockChanged {
+            pos: IVec3::new(5, 6, 7),
+            block: Some(0),
+        },
+        ServerMessage::PlayerJoined {
+            client_id: 9,
+            name: "other".to_string(),
+        },
+        ServerMessage::PlayerLeft { client_id: 9 },
+        ServerMessage::PlayerTransform {
+            client_id: 9,
+            tick: 100,
+            translation: Vec3::new(0.0, 64.0, 0.0),
+            rotation: Quat::IDENTITY,
+        },
+        ServerMessage::Disconnect {
+            reason: "Server closed".to_string(),
+        },
+        ServerMessage::Chat {
+            text: "Filled 8 blocks".to_string(),
+        },
+        ServerMessage::Teleport {
+            translation: Vec3::new(-10.0, 20.0, 30.0),
+        },
+        ServerMessage::TimeOfDay {
+            time_of_day: 18.0,
+            paused: true,
+        },
+        ServerMessage::SetGameMode {
+            mode: GameMode::Spectator,
+        },
+        ServerMessage::Selection {
+            corners: [Some(IVec3::new(1, 2, 3)), None],
+        },
+        ServerMessage::BlocksChanged {
+            changes: vec![(IVec3::ONE, Some(2)), (IVec3::NEG_ONE, None)],
+        },
+    ]
+}
+
+#[test]
+fn client_messages_round_trip() {
+    for message in client_messages() {
+        let decoded = ClientMessage::decode(&message.encode()).unwrap();
+        assert_eq!(decoded, message);
+        let (client_id, token, decoded) =
+            decode_client_datagram(&encode_client_datagram(1, 2, &message)).unwrap();
+        assert_eq!((client_id, token, decoded), (1, 2, message));
+    }
+}
+
+#[test]
+fn server_messages_round_trip() {
+    for message in server_messages() {
+        let decoded = ServerMessage::decode(&message.encode()).unwrap();
+        assert_eq!(decoded, message);
+        let decoded = decode_server_datagram(&encode_server_datagram(&message)).unwrap();
+        assert_eq!(decoded, message);
+    }
+}
+
+#[test]
+fn truncated_messages_are_rejected() {
+    for message in client_messages() {
+        let data = message.encode();
+        for len in 0..data.len() {
+            assert!(
+                ClientMessage::decode(&data[..len]).is_err(),
+                "{message:?} cut to {len} bytes"
+            );
+        }
+    }
+    for message in server_messages() {
+        let data = message.encode();
+        for len in 0..data.len() {
+            assert!(
+                ServerMessage::decode(&data[..len]).is_err(),
+                "{message:?} cut to {len} bytes"
+            );
+        }
+    }
+}
+
+#[test]
+fn trailing_bytes_are_rejected() {
+    let mut data = ClientMessage::Disconnect.encode();
+    data.push(0);
+    assert!(ClientMessage::decode(&data).is_err());
+}
+
+#[test]
+fn long_strings_are_truncated() {
+    let text = "é".repeat(MAX_STRING_LEN);
+    let message = ServerMessage::Chat { text: text.clone() };
+    let ServerMessage::Chat { text: decoded } = ServerMessage::decode(&message.encode()).unwrap()
+    else {
+        panic!("Decoded a different message");
+    };
+    // Two byte characters, cut before the one crossing the limit.
+    assert_eq!(decoded.len(), MAX_STRING_LEN - 1);
+    assert!(text.starts_with(&decoded));
+}
+
+#[test]
+fn unrepresentable_block_ids_are_sent_as_air() {
+    let message = ServerMessage::BlockChanged {
+        pos: IVec3::ZERO,
+        block: Some(u16::MAX),
+    };
+    assert_eq!(
+        ServerMessage::decode(&message.encode()).unwrap(),
+        ServerMessage::BlockChanged {
+            pos: IVec3::ZERO,
+            block: None,
+        }
+    );
+}
+
+#[test]
+fn invalid_transforms_are_rejected() {
+    let transforms = [
+        (Vec3::ZERO, Quat::from_xyzw(0.0, 0.0, 0.0, 0.0)),
+        (Vec3::ZERO, Quat::from_xyzw(f32::NAN, 0.0, 0.0, 1.0)),
+        (Vec3::ZERO, Quat::from_xyzw(f32::INFINITY, 0.0, 0.0, 1.0)),
+        (Vec3::new(f32::NAN, 0.0, 0.0), Quat::IDENTITY),
+        (Vec3::new(0.0, f32::NEG_INFINITY, 0.0), Quat::IDENTITY),
+    ];
+    for (translation, rotation) in transforms {
+        let message = ClientMessage::PlayerTransform {
+            tick: 0,
+            translation,
+            rotation,
+        };
+        assert!(ClientMessage::decode(&message.encode()).is_err());
+    }
+}
+
+#[test]
+fn rotations_are_normalized() {
+    let message = ClientMessage::PlayerTransform {
+        tick: 0,
+        translation: Vec3::ZERO,
+        rotation: Quat::from_xyzw(0.0, 0.0, 0.0, 2.0),
+    };
+    let ClientMessage::PlayerTransform { rotation, .. } =
+        ClientMessage::decode(&message.encode()).unwrap()
+    else {
+        panic!("Decoded a different message");
+    };
+    assert_eq!(rotation, Quat::IDENTITY);
+}
+
+fn test_chunk() -> Chunk {
+    let mut chunk = Chunk::new(Arc::new(Vec::new()));
+    for x in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            chunk.set_at(UVec3::new(x, 0, z), Some(0));
+            chunk.set_at(UVec3::new(x, 1, z), Some(((x + z) % 3) as usize));
+        }
+    }
+    chunk
+}
+
+#[test]
+fn chunks_round_trip() {
+    let chunk = test_chunk();
+    let data = encode_chunk(&chunk).unwrap();
+    let decoded = decode_chunk(&data, &[0, 1, 2], Arc::new(Vec::new())).unwrap();
+    assert_eq!(decoded.blocks(), chunk.blocks());
+}
+
+#[test]
+fn chunks_are_remapped() {
+    let data = encode_chunk(&test_chunk()).unwrap();
+    let decoded = decode_chunk(&data, &[5, 6, 7], Arc::new(Vec::new())).unwrap();
+    assert_eq!(decoded.at(UVec3::ZERO), Some(5));
+    assert_eq!(decoded.at(UVec3::new(1, 1, 0)), Some(6));
+    assert_eq!(decoded.at(UVec3::new(0, 2, 0)), None);
+}
+
+#[test]
+fn invalid_chunks_are_rejected() {
+    let data = encode_chunk(&test_chunk()).unwrap();
+    for len in 0..data.len() {
+        assert!(decode_chunk(&data[..len], &[0, 1, 2], Arc::new(Vec::new())).is_err());
+    }
+    // Block IDs the remap table doesn't cover.
+    assert!(decode_chunk(&data, &[0], Arc::new(Vec::new())).is_err());
+}
+
+#[test]
+fn peers_falling_behind_are_dropped() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    // Accepted but never read from.
+    let _peer = listener.accept().unwrap();
+    let mut connection = Connection::new(stream).unwrap();
+    let payload = vec![0; 1 << 20];
+    let frames = MAX_QUEUED_LEN / (payload.len() + 4);
+    for _ in 0..frames {
+        connection.send(&payload);
+    }
+    assert!(!connection.is_closed());
+    connection.send(&payload);
+    assert!(connection.is_closed());
+}
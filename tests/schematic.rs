@@ -0,0 +1,100 @@
+use bevy::{prelude::*, utils::hashbrown::HashMap};
+use nipahblocks::persistence::UNKNOWN_BLOCK;
+use nipahblocks::schematic::Schematic;
+use nipahblocks::world_edit::{Clipboard, MAX_VOLUME};
+
+fn block_names() -> Vec<String> {
+    ["stone", "dirt", "glass", UNKNOWN_BLOCK]
+        .map(String::from)
+        .to_vec()
+}
+
+fn clipboard() -> Clipboard {
+    let size = UVec3::new(3, 2, 5);
+    let blocks = (0..size.element_product() as usize)
+        .map(|i| [None, Some(0), Some(2)][i % 3])
+        .collect();
+    Clipboard::from_blocks(size, blocks, IVec3::new(-1, 0, 2))
+}
+
+fn header(size: [u32; 3], offset: [i32; 3], palette: &[&str]) -> Vec<u8> {
+    let mut data = b"NBSC".to_vec();
+    data.extend_from_slice(&1u32.to_le_bytes());
+    for value in size {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    for value in offset {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(&(palette.len() as u16).to_le_bytes());
+    for name in palette {
+        data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        data.extend_from_slice(name.as_bytes());
+    }
+    data
+}
+
+#[test]
+fn schematics_round_trip() {
+    let clipboard = clipboard();
+    let schematic = Schematic::from_clipboard(&clipboard, &block_names());
+    assert_eq!(schematic.palette, ["stone", "glass"]);
+    let decoded = Schematic::decode(&schematic.encode()).unwrap();
+    assert_eq!(decoded, schematic);
+
+    let blocks_map = block_names()
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| (name, i))
+        .collect();
+    let (loaded, unknown) = decoded.to_clipboard(&blocks_map);
+    assert!(unknown.is_empty());
+    assert_eq!(loaded.size(), clipboard.size());
+    assert_eq!(loaded.offset(), clipboard.offset());
+    assert_eq!(loaded.blocks(), clipboard.blocks());
+}
+
+#[test]
+fn missing_blocks_become_unknown() {
+    let schematic = Schematic::from_clipboard(&clipboard(), &block_names());
+    let blocks_map = HashMap::from([("stone".to_string(), 5), (UNKNOWN_BLOCK.to_string(), 9)]);
+    let (loaded, unknown) = schematic.to_clipboard(&blocks_map);
+    assert_eq!(unknown, ["glass"]);
+    assert_eq!(loaded.blocks()[..3], [None, Some(5), Some(9)]);
+}
+
+#[test]
+fn invalid_schematics_are_rejected() {
+    let data = Schematic::from_clipboard(&clipboard(), &block_names()).encode();
+    for len in 0..data.len() {
+        assert!(
+            Schematic::decode(&data[..len]).is_err(),
+            "cut to {len} bytes"
+        );
+    }
+    let mut trailing = data.clone();
+    trailing.push(0);
+    assert!(Schematic::decode(&trailing).is_err());
+    let mut magic = data.clone();
+    magic[0] = b'X';
+    assert!(Schematic::decode(&magic).is_err());
+
+    // A palette index past the palette's end.
+    let mut data = header([1, 1, 1], [0, 0, 0], &["stone", "dirt"]);
+    data.extend_from_slice(&3u64.to_le_bytes());
+    assert!(Schematic::decode(&data).is_err());
+}
+
+#[test]
+fn oversized_schematics_are_rejected() {
+    let side = (MAX_VOLUME as f64).cbrt() as u32 + 1;
+    let mut data = header([side; 3], [0, 0, 0], &[]);
+    data.resize(data.len() + (side.pow(3) as usize).div_ceil(64) * 8, 0);
+    assert!(Schematic::decode(&data).is_err());
+    // Overflows a u32 volume.
+    let data = header([u32::MAX, u32::MAX, 2], [0, 0, 0], &[]);
+    assert!(Schematic::decode(&data).is_err());
+    let mut data = header([1, 1, 1], [i32::MIN, 0, 0], &[]);
+    data.extend_from_slice(&0u64.to_le_bytes());
+    assert!(Schematic::decode(&data).is_err());
+}
@@ -0,0 +1,134 @@
+use bevy::{prelude::*, utils::hashbrown::HashMap};
+use nipahblocks::vox::VoxModel;
+
+fn chunk(id: &[u8; 4], content: &[u8], children: &[u8]) -> Vec<u8> {
+    let mut data = id.to_vec();
+    data.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    data.extend_from_slice(&(children.len() as u32).to_le_bytes());
+    data.extend_from_slice(content);
+    data.extend_from_slice(children);
+    data
+}
+
+/// A vox file with one model, and a palette if `palette` is given.
+fn vox_file(size: [u32; 3], voxels: &[[u8; 4]], palette: Option<&[[u8; 4]; 256]>) -> Vec<u8> {
+    let size = size
+        .into_iter()
+        .flat_map(u32::to_le_bytes)
+        .collect::<Vec<_>>();
+    let mut xyzi = (voxels.len() as u32).to_le_bytes().to_vec();
+    xyzi.extend(voxels.iter().flatten());
+    let mut children = chunk(b"SIZE", &size, &[]);
+    children.extend(chunk(b"XYZI", &xyzi, &[]));
+    if let Some(palette) = palette {
+        children.extend(chunk(b"RGBA", palette.as_flattened(), &[]));
+    }
+    let mut data = b"VOX ".to_vec();
+    data.extend_from_slice(&150u32.to_le_bytes());
+    data.extend(chunk(b"MAIN", &[], &children));
+    data
+}
+
+#[test]
+fn models_decode() {
+    let voxels = [[0, 0, 0, 1], [1, 2, 3, 7], [1, 1, 1, 0]];
+    let model = VoxModel::decode(&vox_file([2, 3, 4], &voxels, None)).unwrap();
+    assert_eq!(model.size, UVec3::new(2, 3, 4));
+    assert_eq!(model.model_count, 1);
+    // Voxels with palette index 0 are empty.
+    assert_eq!(model.voxels, [(UVec3::ZERO, 1), (UVec3::new(1, 2, 3), 7)]);
+    // The default palette starts with white.
+    assert_eq!(model.palette[1], [0xff, 0xff, 0xff, 0xff]);
+
+    let mut palette = [[0; 4]; 256];
+    palette[0] = [10, 20, 30, 255];
+    let model = VoxModel::decode(&vox_file([2, 3, 4], &voxels, Some(&palette))).unwrap();
+    assert_eq!(model.palette[1], [10, 20, 30, 255]);
+}
+
+#[test]
+fn models_turn_z_up() {
+    let voxels = [[0, 0, 0, 1], [1, 2, 3, 2]];
+    let model = VoxModel::decode(&vox_file([2, 3, 4], &voxels, None)).unwrap();
+    assert_eq!(model.world_size(), UVec3::new(2, 4, 3));
+    assert_eq!(model.offset(), IVec3::new(-1, 0, -1));
+    let mut palette_blocks = [None; 256];
+    palette_blocks[1] = Some(5);
+    palette_blocks[2] = Some(6);
+    let blocks = model.blocks(&palette_blocks).collect::<Vec<_>>();
+    assert_eq!(
+        blocks,
+        [(IVec3::new(-1, 0, 1), 5), (IVec3::new(0, 3, -1), 6)]
+    );
+
+    palette_blocks[2] = None;
+    let clipboard = model.to_clipboard(&palette_blocks).unwrap();
+    assert_eq!(clipboard.size(), model.world_size());
+    assert_eq!(clipboard.blocks().iter().flatten().count(), 1);
+}
+
+#[test]
+fn palette_maps_to_blocks() {
+    let mut palette = [[0; 4]; 256];
+    palette[0] = [250, 250, 250, 255];
+    palette[1] = [10, 10, 10, 255];
+    palette[2] = [0x12, 0x34, 0x56, 255];
+    let voxels = [[0, 0, 0, 1], [1, 0, 0, 2], [0, 1, 0, 3], [1, 1, 0, 4]];
+    let model = VoxModel::decode(&vox_file([2, 2, 1], &voxels, Some(&palette))).unwrap();
+    let blocks_map = HashMap::from([
+        ("snow".to_string(), 0),
+        ("coal".to_string(), 1),
+        ("stone".to_string(), 2),
+    ]);
+    let block_colors = [
+        (0, Srgba::rgb_u8(255, 255, 255)),
+        (1, Srgba::rgb_u8(0, 0, 0)),
+    ];
+    let mapping = HashMap::from([
+        ("4".to_string(), "stone".to_string()),
+        ("#123456".to_string(), "air".to_string()),
+    ]);
+    let blocks = model
+        .map_palette(&mapping, &blocks_map, &block_colors)
+        .unwrap();
+    assert_eq!(blocks[1..=4], [Some(0), Some(1), None, Some(2)]);
+    assert_eq!(blocks[5], None);
+
+    for key in ["0", "256", "#12345", "red"] {
+        let mapping = HashMap::from([(key.to_string(), "stone".to_string())]);
+        assert!(model
+            .map_palette(&mapping, &blocks_map, &block_colors)
+            .is_err());
+    }
+    let mapping = HashMap::from([("1".to_string(), "lava".to_string())]);
+    assert!(model
+        .map_palette(&mapping, &blocks_map, &block_colors)
+        .is_err());
+}
+
+#[test]
+fn invalid_models_are_rejected() {
+    let data = vox_file([2, 2, 2], &[[1, 1, 1, 1]], None);
+    for len in 0..data.len() {
+        assert!(
+            VoxModel::decode(&data[..len]).is_err(),
+            "cut to {len} bytes"
+        );
+    }
+    let mut magic = data.clone();
+    magic[0] = b'X';
+    assert!(VoxModel::decode(&magic).is_err());
+    // A voxel outside of the model.
+    assert!(VoxModel::decode(&vox_file([2, 2, 2], &[[2, 0, 0, 1]], None)).is_err());
+}
+
+#[test]
+fn model_sizes_are_limited() {
+    for size in [[0, 1, 1], [1, 1, 0], [257, 1, 1], [1, 1, u32::MAX]] {
+        assert!(
+            VoxModel::decode(&vox_file(size, &[], None)).is_err(),
+            "{size:?}"
+        );
+    }
+    assert!(VoxModel::decode(&vox_file([256, 1, 256], &[], None)).is_ok());
+}
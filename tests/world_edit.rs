@@ -0,0 +1,111 @@
+use bevy::{prelude::*, utils::hashbrown::HashMap};
+use nipahblocks::chunk::{Chunk, ChunkMap};
+use nipahblocks::world_edit::{Axis, Clipboard, Region};
+use std::sync::Arc;
+
+/// Blocks by position relative to the paste origin.
+fn placed(clipboard: &Clipboard) -> HashMap<IVec3, Option<usize>> {
+    let size = clipboard.size();
+    let positions = Region::new(IVec3::ZERO, size.as_ivec3() - 1).positions();
+    positions
+        .zip(clipboard.blocks())
+        .map(|(pos, block)| (clipboard.offset() + pos, *block))
+        .collect()
+}
+
+/// A 2×3×4 clipboard with a distinct block in every cell.
+fn clipboard() -> Clipboard {
+    let size = UVec3::new(2, 3, 4);
+    let blocks = (0..size.element_product() as usize).map(Some).collect();
+    Clipboard::from_blocks(size, blocks, IVec3::new(1, 0, -2))
+}
+
+fn chunk_map() -> ChunkMap {
+    let mut chunk_map = ChunkMap::default();
+    chunk_map.insert_chunk(IVec3::ZERO, Chunk::new(Arc::new(Vec::new())));
+    chunk_map
+}
+
+#[test]
+fn regions_are_normalized() {
+    let region = Region::new(IVec3::new(3, -1, 0), IVec3::new(1, 1, 0));
+    assert_eq!(region.min, IVec3::new(1, -1, 0));
+    assert_eq!(region.max, IVec3::new(3, 1, 0));
+    assert_eq!(region.size(), UVec3::new(3, 3, 1));
+    assert_eq!(region.volume(), 9);
+    let positions = region.positions().collect::<Vec<_>>();
+    assert_eq!(positions.len(), 9);
+    assert_eq!(
+        positions[..4],
+        [
+            IVec3::new(1, -1, 0),
+            IVec3::new(2, -1, 0),
+            IVec3::new(3, -1, 0),
+            IVec3::new(1, 0, 0),
+        ]
+    );
+}
+
+#[test]
+fn rotation_turns_around_the_origin() {
+    let original = clipboard();
+    let mut rotated = original.clone();
+    rotated.rotate(1);
+    assert_eq!(rotated.size(), UVec3::new(4, 3, 2));
+    // Clockwise from above, x turns into z and z into -x.
+    let expected = placed(&original)
+        .into_iter()
+        .map(|(pos, block)| (IVec3::new(-pos.z, pos.y, pos.x), block))
+        .collect();
+    assert_eq!(placed(&rotated), expected);
+
+    rotated.rotate(3);
+    assert_eq!(rotated.size(), original.size());
+    assert_eq!(rotated.offset(), original.offset());
+    assert_eq!(rotated.blocks(), original.blocks());
+
+    rotated.rotate(4);
+    assert_eq!(rotated.blocks(), original.blocks());
+}
+
+#[test]
+fn flipping_mirrors_through_the_origin() {
+    let original = clipboard();
+    for (axis, i) in [(Axis::X, 0), (Axis::Y, 1), (Axis::Z, 2)] {
+        let mut flipped = original.clone();
+        flipped.flip(axis);
+        assert_eq!(flipped.size(), original.size());
+        let expected = placed(&original)
+            .into_iter()
+            .map(|(mut pos, block)| {
+                pos[i] = -pos[i];
+                (pos, block)
+            })
+            .collect();
+        assert_eq!(placed(&flipped), expected);
+
+        flipped.flip(axis);
+        assert_eq!(flipped.offset(), original.offset());
+        assert_eq!(flipped.blocks(), original.blocks());
+    }
+}
+
+#[test]
+fn pasting_copies_back() {
+    let mut chunk_map = chunk_map();
+    let origin = IVec3::new(5, 5, 5);
+    let clipboard = clipboard();
+    let changes = clipboard.paste(&mut chunk_map, origin);
+    assert_eq!(changes.len(), clipboard.blocks().len());
+    for (pos, block) in placed(&clipboard) {
+        assert_eq!(chunk_map.block_at(origin + pos), block);
+    }
+
+    let region = Region::new(
+        origin + clipboard.offset(),
+        origin + clipboard.offset() + clipboard.size().as_ivec3() - 1,
+    );
+    let copy = Clipboard::copy(&chunk_map, region, origin);
+    assert_eq!(copy.offset(), clipboard.offset());
+    assert_eq!(copy.blocks(), clipboard.blocks());
+}